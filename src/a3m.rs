@@ -0,0 +1,181 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* A2M and A3M are FastA files in which case matters: uppercase letters and '-' are match states
+ * (i.e., aligned residues and deletions), while lowercase letters are insertions relative to the
+ * match states. In A2M, insertions are padded with '.', so all rows have the same length and
+ * insert columns can be told apart from match columns. A3M (as written by HHblits) drops the
+ * padding, so rows have different lengths and insertions are not aligned at all; expand_a3m()
+ * turns it into A2M. */
+
+use crate::fasta::{FastaFile, FastaRecord};
+
+fn is_insert_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c == '.'
+}
+
+fn num_match_states(seq: &str) -> usize {
+    seq.chars().filter(|c| !is_insert_char(*c)).count()
+}
+
+// Ragged rows, but the same number of match states in each.
+pub fn is_a3m(records: &FastaFile) -> bool {
+    let Some(first) = records.first() else {
+        return false;
+    };
+    let len = first.sequence.len();
+    let num_match = num_match_states(&first.sequence);
+    records.iter().any(|r| r.sequence.len() != len)
+        && records
+            .iter()
+            .all(|r| num_match_states(&r.sequence) == num_match)
+}
+
+// Rows of the same length, and at least one column made of insert characters, while all the others
+// have none. This excludes ordinary alignments that happen to use lowercase, as their columns
+// would mix cases, or lowercase and '-'.
+pub fn is_a2m(records: &FastaFile) -> bool {
+    let Some(first) = records.first() else {
+        return false;
+    };
+    let len = first.sequence.len();
+    if records.iter().any(|r| r.sequence.len() != len) {
+        return false;
+    }
+    let rows: Vec<&[u8]> = records.iter().map(|r| r.sequence.as_bytes()).collect();
+    let mut has_insert_col = false;
+    for j in 0..len {
+        let num_insert = rows
+            .iter()
+            .filter(|row| is_insert_char(row[j] as char))
+            .count();
+        if num_insert == rows.len() {
+            has_insert_col = true;
+        } else if num_insert != 0 {
+            return false;
+        }
+    }
+    has_insert_col
+}
+
+// Aligns the insertions: the insertions found between two consecutive match states are
+// left-justified and padded with '.' up to the longest insertion at that point in any sequence.
+pub fn expand_a3m(records: FastaFile) -> FastaFile {
+    // Split each sequence into (insertion, match state) pieces; the last piece has no match state.
+    let pieces: Vec<Vec<(String, Option<char>)>> = records
+        .iter()
+        .map(|r| {
+            let mut seq_pieces: Vec<(String, Option<char>)> = Vec::new();
+            let mut insertion = String::new();
+            for c in r.sequence.chars() {
+                if is_insert_char(c) {
+                    if c != '.' {
+                        insertion.push(c);
+                    }
+                } else {
+                    seq_pieces.push((std::mem::take(&mut insertion), Some(c)));
+                }
+            }
+            seq_pieces.push((insertion, None));
+            seq_pieces
+        })
+        .collect();
+
+    let num_slots = pieces.iter().map(|p| p.len()).max().unwrap_or(0);
+    let max_ins_len: Vec<usize> = (0..num_slots)
+        .map(|k| {
+            pieces
+                .iter()
+                .filter_map(|p| p.get(k))
+                .map(|(ins, _)| ins.len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    records
+        .into_iter()
+        .zip(pieces)
+        .map(|(record, seq_pieces)| {
+            let mut sequence = String::new();
+            for (k, (insertion, match_state)) in seq_pieces.iter().enumerate() {
+                sequence.push_str(insertion);
+                sequence.push_str(&".".repeat(max_ins_len[k] - insertion.len()));
+                if let Some(c) = match_state {
+                    sequence.push(*c);
+                }
+            }
+            FastaRecord {
+                header: record.header,
+                sequence,
+            }
+        })
+        .collect()
+}
+
+// Removes the insert columns of an A2M alignment (or an expanded A3M one), leaving only the match
+// states.
+pub fn remove_insert_columns(records: &FastaFile) -> FastaFile {
+    records
+        .iter()
+        .map(|r| FastaRecord {
+            header: r.header.clone(),
+            sequence: r.sequence.chars().filter(|c| !is_insert_char(*c)).collect(),
+        })
+        .collect()
+}
+
+// The reverse of expand_a3m(): drops the padding of the insertions.
+pub fn to_a3m(records: &FastaFile) -> FastaFile {
+    records
+        .iter()
+        .map(|r| FastaRecord {
+            header: r.header.clone(),
+            sequence: r.sequence.chars().filter(|c| *c != '.').collect(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::read_fasta_file;
+
+    #[test]
+    fn test_is_a3m() {
+        let a3m = read_fasta_file("data/test1.a3m").unwrap();
+        assert!(is_a3m(&a3m));
+        assert!(!is_a2m(&a3m));
+        let fasta = read_fasta_file("data/test4.aln").unwrap();
+        assert!(!is_a3m(&fasta));
+        assert!(!is_a2m(&fasta));
+    }
+
+    #[test]
+    fn test_expand_a3m() {
+        let a2m = expand_a3m(read_fasta_file("data/test1.a3m").unwrap());
+        assert_eq!(a2m[0].header, "query");
+        assert_eq!(a2m[0].sequence, "MK...VLAG..IV");
+        assert_eq!(a2m[1].sequence, "MKaa.VLAGg.IV");
+        assert_eq!(a2m[2].sequence, "-Kcde-LAGstI-");
+        assert!(is_a2m(&a2m));
+    }
+
+    #[test]
+    fn test_to_a3m() {
+        let a3m = read_fasta_file("data/test1.a3m").unwrap();
+        let back = to_a3m(&expand_a3m(read_fasta_file("data/test1.a3m").unwrap()));
+        for (r1, r2) in a3m.iter().zip(back.iter()) {
+            assert_eq!(r1.sequence, r2.sequence);
+        }
+    }
+
+    #[test]
+    fn test_remove_insert_columns() {
+        let a2m = expand_a3m(read_fasta_file("data/test1.a3m").unwrap());
+        let matches = remove_insert_columns(&a2m);
+        assert_eq!(matches[0].sequence, "MKVLAGIV");
+        assert_eq!(matches[1].sequence, "MKVLAGIV");
+        assert_eq!(matches[2].sequence, "-K-LAGI-");
+    }
+}
@@ -1,12 +1,15 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2025 Thomas Junier
 mod permutation;
+pub mod pairwise;
 
 use std::collections::HashMap;
 
 use itertools::Itertools;
 
 use crate::fasta::FastaFile;
+use crate::stockholm::Annotations;
+use crate::vec_f64_aux::{normalize, ones_complement, product};
 
 use crate::alignment::SeqType::{Nucleic, Protein};
 
@@ -44,6 +47,10 @@ pub struct Alignment {
     // %IDs. Tried Box, and generics, but the extra work doesn't seem warranted.
     pub relative_seq_len: Vec<f64>,
     pub macromolecule_type: SeqType,
+
+    /* Annotations that come with the alignment file (so far, only Stockholm files have any). These
+     * are not computed, just kept for display. */
+    pub annotations: Annotations,
 }
 
 #[derive(Debug, PartialEq)]
@@ -70,7 +77,7 @@ impl Alignment {
         let relative_seq_len = sequences.iter()
             .map(|seq| seq_len_nogaps(seq))
             .collect();
-        let first_seq = sequences.iter().nth(0);
+        let first_seq = sequences.first();
         let macromolecule_type = seq_type(first_seq.expect("No sequence found."));
 
         Alignment {
@@ -82,6 +89,7 @@ impl Alignment {
             id_wrt_consensus,
             relative_seq_len,
             macromolecule_type,
+            annotations: Annotations::default(),
         }
     }
 
@@ -96,10 +104,63 @@ impl Alignment {
     pub fn macromolecule_type(&self) -> SeqType {
         self.macromolecule_type
     }
+
+    // The most information a column can have, in bits: log2 of the alphabet's size.
+    pub fn max_information(&self) -> f64 {
+        match self.macromolecule_type {
+            Nucleic => 4f64.log2(),
+            Protein => 20f64.log2(),
+        }
+    }
+
+    // A column of the sequence logo: its residues (in upper case), from the least to the most
+    // frequent, with their heights in bits, i.e. their frequency times the column's information
+    // content (the maximal entropy minus the column's). Gaps are left out, but the heights are
+    // scaled by the column's density, so that gappy columns stay low.
+    pub fn logo_stack(&self, col: usize) -> Vec<(char, f64)> {
+        let entropy_bits = self.entropies[col] / 2f64.ln();
+        let information = (self.max_information() - entropy_bits).max(0.0) * self.densities[col];
+        let mut counts: ResidueCounts = HashMap::new();
+        for (residue, count) in res_count(&self.sequences, col) {
+            if !is_gap(residue) {
+                *counts.entry(residue.to_ascii_uppercase()).or_insert(0) += count;
+            }
+        }
+        let total: u64 = counts.values().sum();
+        let mut stack: Vec<(char, f64)> = counts
+            .into_iter()
+            .map(|(residue, count)| (residue, information * count as f64 / total as f64))
+            .collect();
+        stack.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        stack
+    }
+
+    // Per-column conservation, as shown in the bottom pane: high for columns that have few gaps and
+    // low entropy.
+    pub fn conservation(&self) -> Vec<f64> {
+        product(
+            &self.densities,
+            &ones_complement(&normalize(&self.entropies)),
+        )
+    }
 }
 
 // TODO should these be methods of Alignment?
 
+// '.' is the usual gap character in Stockholm files (at least those from Pfam and Rfam), so it
+// must be treated like '-' when computing statistics. So is '?', which denotes missing data in
+// PHYLIP and NEXUS files (the NEXUS reader translates any other declared symbols to '-' and '?').
+pub fn is_gap(c: char) -> bool {
+    c == '-' || c == '.' || c == '?'
+}
+
+// Residues are letters (including the lowercase insert states of A2M/A3M), plus '*' for stops, as
+// in translated sequences. Anything else (digits, non-ASCII characters, etc.) is rejected when
+// reading, so that sequences can be indexed by byte.
+pub fn is_valid_symbol(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '*' || is_gap(c)
+}
+
 fn res_count(sequences: &Vec<String>, col: usize) -> ResidueCounts {
     let mut freqs: ResidueCounts = HashMap::new();
     for seq in sequences {
@@ -144,12 +205,8 @@ pub fn entropies(sequences: &Vec<String>) -> Vec<f64> {
 pub fn col_density(sequences: &Vec<String>, col: usize) -> f64 {
     let mut mass = 0;
     for seq in sequences {
-        match seq.as_bytes()[col] as char {
-            'a'..='z' | 'A'..='Z' => mass += 1,
-            '-' | '.' => {}
-            other => {
-                panic!("Character {other} unexpected in an alignment.");
-            }
+        if !is_gap(seq.as_bytes()[col] as char) {
+            mass += 1;
         }
     }
     mass as f64 / sequences.len() as f64
@@ -181,12 +238,12 @@ fn best_residue(dist: &ResidueCounts) -> BestResidue {
 fn to_freq_distrib(counts: &ResidueCounts) -> ResidueDistribution {
     let total_counts: u64 = counts
         .iter()
-        .filter(|(res, _count)| **res != '-')
+        .filter(|(res, _count)| !is_gap(**res))
         .map(|(_res, count)| count)
         .sum();
     let mut distrib = ResidueDistribution::new();
     for (residue, count) in counts.iter() {
-        if *residue == '-' {
+        if is_gap(*residue) {
             continue;
         }
         distrib.insert(*residue, *count as f64 / total_counts as f64);
@@ -195,8 +252,8 @@ fn to_freq_distrib(counts: &ResidueCounts) -> ResidueDistribution {
 }
 
 fn entropy(freqs: &ResidueDistribution) -> f64 {
-    // Discard '-'s
-    let residues: Vec<&char> = freqs.keys().filter(|&&r| r != '-').collect();
+    // Discard gaps
+    let residues: Vec<&char> = freqs.keys().filter(|&&r| !is_gap(r)).collect();
     let sum: f64 = residues
         .into_iter()
         .map(|res| {
@@ -204,12 +261,12 @@ fn entropy(freqs: &ResidueDistribution) -> f64 {
             p * p.ln()
         })
         .sum();
-    -1.0 * sum
+    -sum
 }
 
 fn percent_identity(s1: &str, s2: &str) -> f64 {
     let num_identical = s1.chars().zip(s2.chars())
-        .filter(|(c1, c2)| c1.to_ascii_uppercase() == c2.to_ascii_uppercase())
+        .filter(|(c1, c2)| c1.eq_ignore_ascii_case(c2))
         .count();
     num_identical as f64 / s1.len() as f64
 }
@@ -242,6 +299,7 @@ mod tests {
         best_residue, consensus, densities, entropies, entropy, percent_identity, res_count, seq_len_nogaps, seq_type, to_freq_distrib, Alignment, BestResidue, ResidueCounts, ResidueDistribution, SeqType::{Nucleic, Protein},
     };
     use crate::fasta::read_fasta_file;
+    use crate::nexus::read_nexus_file;
     use approx::assert_relative_eq;
     use std::collections::HashMap;
 
@@ -346,7 +404,7 @@ mod tests {
     fn test_entropy_2() {
         let eps = 0.00001;
         let distrib: ResidueDistribution = ResidueDistribution::from([('A', 0.5), ('F', 0.5)]);
-        assert_relative_eq!(0.6931471805599453, entropy(&distrib), epsilon = eps);
+        assert_relative_eq!(std::f64::consts::LN_2, entropy(&distrib), epsilon = eps);
     }
 
     #[test]
@@ -382,6 +440,40 @@ mod tests {
         assert_eq!(0.0, dens[5]);
     }
 
+    #[test]
+    fn test_density_stops() {
+        // Stops count as residues.
+        let seqs = vec!["MK*".to_string(), "MK-".to_string()];
+        assert_eq!(densities(&seqs), vec![1.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_logo_stack() {
+        let eps = 0.00001;
+        // seq1 TTGCCG-CGA, seq2 TTCCCGGCGA, seq3 TTACCG-CAA
+        let aln = Alignment::new(read_fasta_file("data/test2.fas").unwrap());
+        assert_relative_eq!(2.0, aln.max_information(), epsilon = eps);
+        assert_eq!(aln.logo_stack(0), vec![('T', 2.0)]);
+        // Three residues, one each: log2(4) - log2(3) bits, shared equally
+        let stack = aln.logo_stack(2);
+        assert_eq!(stack.iter().map(|(c, _)| *c).collect::<String>(), "ACG");
+        assert_relative_eq!((2.0 - 3f64.log2()) / 3.0, stack[0].1, epsilon = eps);
+        // One residue, in a third of the sequences
+        let stack = aln.logo_stack(6);
+        assert_eq!(stack.len(), 1);
+        assert_relative_eq!(2.0 / 3.0, stack[0].1, epsilon = eps);
+    }
+
+    #[test]
+    fn test_density_nexus_symbols() {
+        // GAP=~ and MISSING=N in the file
+        let aln = Alignment::new(read_nexus_file("data/test2.nex").unwrap());
+        let dens = densities(&aln.sequences);
+        assert_relative_eq!(1.0 / 3.0, dens[10], epsilon = 0.001);
+        assert_relative_eq!(2.0 / 3.0, dens[19], epsilon = 0.001);
+        assert_eq!(1.0, dens[20]);
+    }
+
     #[test]
     fn test_order_aln() {
         let fasta = read_fasta_file("./data/test4.aln").unwrap();
@@ -451,4 +543,10 @@ mod tests {
         assert_eq!(Nucleic, seq_type("UUTGAU"));
     }
 
+    #[test]
+    fn test_seq_type_20() {
+        // Stockholm-style gaps must not count as residues
+        assert_eq!(Nucleic, seq_type("..ac...gu.....ag..c"));
+    }
+
 }
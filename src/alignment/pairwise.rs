@@ -0,0 +1,149 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Pairwise comparisons of aligned sequences: identity, i.e. the fraction of the compared columns
+ * in which both sequences have the same residue, and p-distance, the fraction in which they
+ * differ (1 - identity). Residues are compared regardless of case. Which columns are compared
+ * depends on how gaps are handled:
+ *
+ *   ignore-gap-pairs   columns where both sequences have a gap are skipped, and a gap facing a
+ *                      residue is a mismatch (the default)
+ *   mismatch           all columns are compared, and a gap is always a mismatch, even facing
+ *                      another gap */
+
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::alignment::is_gap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GapHandling {
+    IgnoreGapPairs,
+    GapsAsMismatches,
+}
+
+// As on the command line and in the configuration file.
+pub const GAP_HANDLINGS: &[(&str, GapHandling)] = &[
+    ("ignore-gap-pairs", GapHandling::IgnoreGapPairs),
+    ("mismatch", GapHandling::GapsAsMismatches),
+];
+
+impl fmt::Display for GapHandling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            GapHandling::IgnoreGapPairs => "gap pairs ignored",
+            GapHandling::GapsAsMismatches => "gaps as mismatches",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Measure {
+    Identity,
+    PDistance,
+}
+
+// 0 if there is nothing to compare (e.g. two gap-only sequences, when gap pairs are ignored).
+pub fn identity(s1: &str, s2: &str, gaps: GapHandling) -> f64 {
+    let mut num_compared = 0;
+    let mut num_identical = 0;
+    for (c1, c2) in s1.bytes().zip(s2.bytes()) {
+        let (gap1, gap2) = (is_gap(c1 as char), is_gap(c2 as char));
+        if gap1 && gap2 && gaps == GapHandling::IgnoreGapPairs {
+            continue;
+        }
+        num_compared += 1;
+        if !gap1 && !gap2 && c1.eq_ignore_ascii_case(&c2) {
+            num_identical += 1;
+        }
+    }
+    if num_compared == 0 {
+        0.0
+    } else {
+        num_identical as f64 / num_compared as f64
+    }
+}
+
+// Identities of all pairs of sequences, indexed like the sequences. The matrix is symmetric, so
+// only half of it is computed.
+pub fn identity_matrix<S: AsRef<str>>(sequences: &[S], gaps: GapHandling) -> Vec<Vec<f64>> {
+    let n = sequences.len();
+    let mut matrix = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i..n {
+            let id = identity(sequences[i].as_ref(), sequences[j].as_ref(), gaps);
+            matrix[i][j] = id;
+            matrix[j][i] = id;
+        }
+    }
+    matrix
+}
+
+// Writes the matrix (of identities) as tab-separated values, with the IDs as column and row
+// headers.
+pub fn write_matrix<W: Write>(
+    mut writer: W,
+    ids: &[&str],
+    matrix: &[Vec<f64>],
+    measure: Measure,
+) -> io::Result<()> {
+    writeln!(writer, "\t{}", ids.join("\t"))?;
+    for (id, row) in ids.iter().zip(matrix) {
+        let values: Vec<String> = row
+            .iter()
+            .map(|value| match measure {
+                Measure::Identity => format!("{:.4}", value),
+                Measure::PDistance => format!("{:.4}", 1.0 - value),
+            })
+            .collect();
+        writeln!(writer, "{}\t{}", id, values.join("\t"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alignment::pairwise::{
+        identity, identity_matrix, write_matrix,
+        GapHandling::{GapsAsMismatches, IgnoreGapPairs},
+        Measure,
+    };
+
+    #[test]
+    fn test_identity_gap_handling() {
+        // 6 columns, one of which is a gap pair, and one a gap facing a residue.
+        let s1 = "GA-TTC";
+        let s2 = "ga-T-A";
+        assert_eq!(identity(s1, s2, IgnoreGapPairs), 3.0 / 5.0);
+        assert_eq!(identity(s1, s2, GapsAsMismatches), 3.0 / 6.0);
+        assert_eq!(identity("--", "..", IgnoreGapPairs), 0.0);
+    }
+
+    #[test]
+    fn test_identity_matrix() {
+        // seq1 TTGCCG-CGA, seq2 TTCCCGGCGA, seq3 TTACCG-CAA
+        let seqs = ["TTGCCG-CGA", "TTCCCGGCGA", "TTACCG-CAA"];
+        let matrix = identity_matrix(&seqs, IgnoreGapPairs);
+        assert_eq!(matrix[0][0], 1.0);
+        assert_eq!(matrix[0][1], 0.8);
+        assert_eq!(matrix[1][0], 0.8);
+        // The gap pair of seq1 and seq3 is not compared.
+        assert_eq!(matrix[0][2], 7.0 / 9.0);
+        assert_eq!(matrix[1][2], 0.7);
+        let matrix = identity_matrix(&seqs, GapsAsMismatches);
+        assert_eq!(matrix[0][0], 0.9);
+        assert_eq!(matrix[0][2], 0.7);
+    }
+
+    #[test]
+    fn test_write_matrix() {
+        let matrix = vec![vec![1.0, 0.75], vec![0.75, 1.0]];
+        let mut out: Vec<u8> = Vec::new();
+        write_matrix(&mut out, &["a", "b"], &matrix, Measure::PDistance).unwrap();
+        assert_eq!(
+            "\ta\tb\na\t0.0000\t0.2500\nb\t0.2500\t0.0000\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -1,29 +1,60 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2025 Thomas Junier
+use std::collections::HashMap;
 use std::fmt;
+use std::io::Write;
+use std::ops::Range;
 
+use crate::a3m::{expand_a3m, is_a2m, is_a3m, remove_insert_columns, to_a3m};
+use crate::clustal::{read_clustal, write_clustal};
+use crate::alignment::pairwise::{identity, identity_matrix, write_matrix, GapHandling, Measure};
+use crate::errors::InputError;
+use crate::goto::id_matches;
+use crate::fasta::{check_records, read_fasta, seq_id, write_fasta, FastaFile, FastaRecord};
+use crate::file_format::{format_from_extension, sniff_format, FileFormat};
+use crate::input::{open_input, STDIN_PATH};
+use crate::output::create_output;
+use crate::motif::{compile_pattern, Hit, Search};
+use crate::newick::NewickNode;
 
-use crate::fasta::read_fasta_file;
+use regex::{Regex, RegexBuilder};
+use crate::nexus::{read_nexus, write_nexus};
+use crate::phylip::{read_phylip, write_phylip};
+use crate::stockholm::{read_stockholm, write_stockholm, Annotations};
+use crate::tree::{neighbour_joining, upgma, Tree};
+use crate::vec_f64_aux::mean;
 
 use crate::{
-    alignment::Alignment,
-    app::SeqOrdering::{SourceFile, MetricIncr, MetricDecr},
-    app::Metric::{PctIdWrtConsensus, SeqLen},
+    alignment::{is_gap, Alignment},
+    app::SeqOrdering::{SourceFile, MetricIncr, MetricDecr, NjTree, UpgmaTree, FileTree},
+    app::Metric::{PctIdWrtConsensus, PctIdWrtSeq, SeqLen},
 };
 
-#[derive(Clone, Copy)]
+// Guide trees take time cubic in the number of sequences (after the identity matrix, which takes
+// quadratic time), and are computed on the UI thread: they are refused above this many sequences.
+pub const MAX_GUIDE_TREE_SEQS: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum SeqOrdering {
     SourceFile,
     MetricIncr,
     MetricDecr,
+    // By the leaves of a guide tree of the shown sequences (see tree.rs and cycle_guide_tree())
+    NjTree,
+    UpgmaTree,
+    // By the tree read from a file (see App::set_tree())
+    FileTree,
 }
 
 impl fmt::Display for SeqOrdering {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let sord = match self {
-            SourceFile => '-', 
-            MetricIncr => '↑',
-            MetricDecr => '↓',
+            SourceFile => "-", 
+            MetricIncr => "↑",
+            MetricDecr => "↓",
+            NjTree => "NJ",
+            UpgmaTree => "UPGMA",
+            FileTree => "tree",
         };
         write!(f, "{}", sord)
     }
@@ -33,6 +64,8 @@ impl fmt::Display for SeqOrdering {
 pub enum Metric {
     PctIdWrtConsensus,
     SeqLen,
+    // Identity to the sequence chosen by the user (see App::order_by_similarity())
+    PctIdWrtSeq,
 }
 
 impl fmt::Display for Metric {
@@ -40,6 +73,7 @@ impl fmt::Display for Metric {
         let metric = match self {
             PctIdWrtConsensus => "%id (cons)", 
             SeqLen => "seq len",
+            PctIdWrtSeq => "%id (seq)",
         };
         write!(f, "{}", metric)
     }
@@ -47,7 +81,12 @@ impl fmt::Display for Metric {
 
 pub struct App {
     pub filename: String,
+    pub format: FileFormat,
     pub alignment: Alignment,
+    // A2M/A3M only: the same alignment, but without (resp. with) the insert columns. It is swapped
+    // with the current alignment when the user toggles insert columns.
+    alt_alignment: Option<Alignment>,
+    insert_columns_shown: bool,
     ordering_criterion: SeqOrdering,
     metric: Metric,
     // Specifies in which order the aligned sequences should be displayed. The elements of this Vec
@@ -56,27 +95,141 @@ pub struct App {
     // according to the current metric, in which case the ordering becomes that of the metric's
     // value for each sequence.
     pub ordering: Vec<usize>,
+    // The current motif search, if any. Its hits are kept in display order (i.e., that of
+    // `ordering`), so that the user can step through them.
+    search: Option<Search>,
+    // Only sequences whose header matches are shown (i.e., are in `ordering`). The pattern is
+    // kept as typed, for display.
+    label_filter: Option<(String, Regex)>,
+    // Sequences hidden by the user (e.g. from a selection), indexed like the alignment's.
+    hidden: Vec<bool>,
+    // If set (and some sequences are not shown), statistics are those of the shown sequences
+    // only.
+    stats_on_subset: bool,
+    subset_stats: Option<SubsetStats>,
+    // Pairwise identities (see alignment/pairwise.rs). The whole matrix would take time and space
+    // quadratic in the number of sequences, so only the pairs that are needed (e.g. those in view
+    // on the identity map) are computed, and cached by pair (lower index first); the identities
+    // to the sequence chosen for ordering, on the other hand, are always kept.
+    gap_handling: GapHandling,
+    identities: HashMap<(usize, usize), f64>,
+    similar_to: Option<usize>,
+    id_wrt_seq: Vec<f64>,
+    // The tree by which the sequences were last ordered, if any (see tree_ordering()).
+    tree: Option<(SeqOrdering, Tree)>,
+    // The tree read from a file, if any, with all its matched leaves.
+    loaded_tree: Option<Tree>,
+}
+
+// How the leaves of a tree read from a file were matched to the sequences (see App::set_tree()).
+pub struct TreeMatch {
+    // Leaves that match no sequence, or one that another leaf already matched
+    pub unmatched_leaves: Vec<String>,
+    // Sequences that no leaf matches, in file order
+    pub unmatched_seqs: Vec<usize>,
+}
+
+// Newick names cannot contain spaces unless quoted, so programs often replace them by underscores.
+fn leaf_matches(header: &str, name: &str) -> bool {
+    header == name
+        || id_matches(header, name)
+        || (name.contains('_') && header == name.replace('_', " "))
+}
+
+// Statistics (consensus, conservation, metrics) of the shown sequences, i.e. those that pass the
+// label filter and are not hidden. Per-sequence values are indexed like the whole alignment's
+// sequences (the others get 0).
+struct SubsetStats {
+    alignment: Alignment,
+    id_wrt_consensus: Vec<f64>,
+    relative_seq_len: Vec<f64>,
+}
+
+// Statistics of a block of sequences and columns (e.g. a selection).
+pub struct BlockStats {
+    pub consensus: String,
+    // Mean identity of the sequences to the block's consensus (0-1)
+    pub mean_identity: f64,
+    pub gap_fraction: f64,
+}
+
+// Label patterns are regexes, but in practice mostly plain text (e.g. "Homo"), hence the case
+// insensitivity.
+fn label_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
 }
 
 impl App {
-    pub fn new(path: &str) -> Result<App, std::io::Error> {
-        let fasta_file = read_fasta_file(path)?;
-        let alignment =  Alignment::new(fasta_file);
+    // The path may be STDIN_PATH. In any case, the input is read only once (see sniff_format()).
+    pub fn new(path: &str) -> Result<App, InputError> {
+        let (mut format, reader) = sniff_format(open_input(path)?)?;
+        let mut annotations = Annotations::default();
+        let records = match format {
+            FileFormat::FastA | FileFormat::A2M | FileFormat::A3M => {
+                let records = read_fasta(reader)?;
+                if is_a3m(&records) {
+                    format = FileFormat::A3M;
+                    expand_a3m(records)
+                } else {
+                    if is_a2m(&records) {
+                        format = FileFormat::A2M;
+                    }
+                    records
+                }
+            }
+            FileFormat::Stockholm => {
+                let sto_file = read_stockholm(reader)?;
+                annotations = sto_file.annotations;
+                sto_file.records
+            }
+            FileFormat::Clustal => read_clustal(reader)?,
+            FileFormat::Phylip => read_phylip(reader)?,
+            FileFormat::Nexus => read_nexus(reader)?,
+        };
+        // Past this point, the alignment is known to be non-empty and rectangular.
+        check_records(&records)?;
+        let alt_alignment = match format {
+            FileFormat::A2M | FileFormat::A3M => {
+                Some(Alignment::new(remove_insert_columns(&records)))
+            }
+            _ => None,
+        };
+        let mut alignment = Alignment::new(records);
+        alignment.annotations = annotations;
         let len = alignment.num_seq();
         Ok(App {
-            filename: path.to_string(),
+            filename: if path == STDIN_PATH {
+                "<stdin>".to_string()
+            } else {
+                path.to_string()
+            },
+            format,
             alignment,
+            alt_alignment,
+            insert_columns_shown: true,
             ordering_criterion: SourceFile,
             metric: PctIdWrtConsensus,
             ordering: (0..len).collect(),
+            search: None,
+            label_filter: None,
+            hidden: vec![false; len],
+            stats_on_subset: false,
+            subset_stats: None,
+            gap_handling: GapHandling::IgnoreGapPairs,
+            identities: HashMap::new(),
+            similar_to: None,
+            id_wrt_seq: vec![0.0; len],
+            tree: None,
+            loaded_tree: None,
         })
     }
 
     // Computed properties (TODO: could be set in a struct member, as they do not change)
     // FIXME where do we need num_seq as u16?
 
+    // The number of sequences shown, i.e. that pass the label filter (if any) and are not hidden.
     pub fn num_seq(&self) -> u16 {
-        self.alignment.num_seq().try_into().unwrap()
+        self.ordering.len().try_into().unwrap()
     }
 
     pub fn aln_len(&self) -> u16 {
@@ -86,43 +239,571 @@ impl App {
     fn recompute_ordering(&mut self) {
         match self.ordering_criterion {
             MetricIncr => {
-                self.ordering = order(&self.order_values());
+                self.ordering = order(self.order_values());
             }
             MetricDecr => {
-                let mut ord = order(&self.order_values());
+                let mut ord = order(self.order_values());
                 ord.reverse();
                 self.ordering = ord;
             }
             SourceFile => {
                 self.ordering = (0..self.alignment.num_seq()).collect();
             }
+            NjTree | UpgmaTree | FileTree => {
+                self.ordering = self.tree_ordering();
+            }
         }
+        let mut ordering = std::mem::take(&mut self.ordering);
+        ordering.retain(|&i| self.is_shown(i));
+        self.ordering = ordering;
+        self.sort_hits();
+    }
+
+    fn is_shown(&self, seq: usize) -> bool {
+        let passes_filter = match &self.label_filter {
+            Some((_, re)) => re.is_match(&self.alignment.headers[seq]),
+            None => true,
+        };
+        passes_filter && !self.hidden[seq]
     }
 
     pub fn cycle_ordering_criterion(&mut self) {
         self.ordering_criterion = match self.ordering_criterion {
             SourceFile => MetricIncr,
             MetricIncr => MetricDecr,
-            MetricDecr => SourceFile,
+            // Only if a tree was read. Guide trees are not in the cycle, as they can take long to
+            // compute (see cycle_guide_tree()).
+            MetricDecr if self.loaded_tree.is_some() => FileTree,
+            MetricDecr | NjTree | UpgmaTree | FileTree => SourceFile,
         };
         self.recompute_ordering();
     }
 
+    // Orders the sequences by an NJ tree, or by a UPGMA tree if they already are by an NJ one.
+    // Refused if too many sequences are shown (see MAX_GUIDE_TREE_SEQS).
+    pub fn cycle_guide_tree(&mut self) -> Result<SeqOrdering, String> {
+        if self.ordering.len() > MAX_GUIDE_TREE_SEQS {
+            return Err(format!(
+                "too many sequences ({} shown, at most {})",
+                self.ordering.len(),
+                MAX_GUIDE_TREE_SEQS
+            ));
+        }
+        self.ordering_criterion = match self.ordering_criterion {
+            NjTree => UpgmaTree,
+            _ => NjTree,
+        };
+        self.recompute_ordering();
+        Ok(self.ordering_criterion)
+    }
+
+    // The leaves of an NJ or UPGMA tree of the shown sequences, using p-distances. The tree is
+    // only computed again if the shown sequences (or the distances) have changed since. The tree
+    // read from a file is pruned to the shown sequences instead, and those not in it come last.
+    fn tree_ordering(&mut self) -> Vec<usize> {
+        if self.ordering_criterion == FileTree {
+            let tree = self
+                .loaded_tree
+                .as_ref()
+                .and_then(|tree| tree.pruned(|seq| self.is_shown(seq)));
+            let mut ordering = tree.as_ref().map_or(Vec::new(), |tree| tree.leaves());
+            let mut in_tree = vec![false; self.alignment.num_seq()];
+            for &seq in &ordering {
+                in_tree[seq] = true;
+            }
+            ordering.extend((0..self.alignment.num_seq()).filter(|&seq| !in_tree[seq]));
+            self.tree = tree.map(|tree| (FileTree, tree));
+            return ordering;
+        }
+        let shown: Vec<usize> = (0..self.alignment.num_seq())
+            .filter(|&i| self.is_shown(i))
+            .collect();
+        if shown.len() > MAX_GUIDE_TREE_SEQS {
+            // E.g. once hidden sequences are shown again
+            self.ordering_criterion = SourceFile;
+            return shown;
+        }
+        let is_current = self.tree.as_ref().is_some_and(|(kind, tree)| {
+            let mut leaves = tree.leaves();
+            leaves.sort_unstable();
+            *kind == self.ordering_criterion && leaves == shown
+        });
+        if !is_current {
+            // Only the shown sequences are compared, so that trees of a few of many sequences
+            // are quick.
+            let sequences: Vec<&str> = shown
+                .iter()
+                .map(|&seq| self.alignment.sequences[seq].as_str())
+                .collect();
+            let identities = identity_matrix(&sequences, self.gap_handling);
+            let distances: Vec<Vec<f64>> = identities
+                .iter()
+                .enumerate()
+                .map(|(i, row)| {
+                    row.iter()
+                        .enumerate()
+                        .map(|(j, id)| if i == j { 0.0 } else { 1.0 - id })
+                        .collect()
+                })
+                .collect();
+            let mut tree = match self.ordering_criterion {
+                NjTree => neighbour_joining(&distances),
+                _ => upgma(&distances),
+            };
+            tree.renumber_leaves(&shown);
+            self.tree = Some((self.ordering_criterion, tree));
+        }
+        self.tree.as_ref().unwrap().1.leaves()
+    }
+
+    // The tree of the shown sequences, if they are ordered by one (in the order of its leaves).
+    pub fn tree(&self) -> Option<&Tree> {
+        match self.ordering_criterion {
+            NjTree | UpgmaTree | FileTree => self.tree.as_ref().map(|(_, tree)| tree),
+            _ => None,
+        }
+    }
+
+    // Orders the sequences by a tree read from a file, whose leaves are matched to the sequences
+    // by ID (see leaf_matches()). Fails if no leaf matches.
+    pub fn set_tree(&mut self, newick: &NewickNode) -> Result<TreeMatch, String> {
+        let headers = &self.alignment.headers;
+        // The first sequence with a given ID, for the common case of leaves named after IDs
+        let ids: HashMap<&str, usize> = headers
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(seq, header)| (seq_id(header), seq))
+            .collect();
+        let mut matched = vec![false; headers.len()];
+        let mut unmatched_leaves = Vec::new();
+        let tree = Tree::from_newick(newick, |name| {
+            let seq = ids
+                .get(name)
+                .copied()
+                .or_else(|| headers.iter().position(|header| leaf_matches(header, name)));
+            match seq {
+                Some(seq) if !matched[seq] => {
+                    matched[seq] = true;
+                    Some(seq)
+                }
+                _ => {
+                    unmatched_leaves.push(name.to_string());
+                    None
+                }
+            }
+        });
+        self.loaded_tree = Some(tree.ok_or("no leaf of the tree matches a sequence")?);
+        self.ordering_criterion = FileTree;
+        self.recompute_ordering();
+        Ok(TreeMatch {
+            unmatched_leaves,
+            unmatched_seqs: (0..matched.len()).filter(|&seq| !matched[seq]).collect(),
+        })
+    }
+
     pub fn cycle_metric(&mut self) {
         self.metric = match self.metric {
             PctIdWrtConsensus =>  SeqLen,
+            // Only once a sequence has been chosen
+            SeqLen if self.similar_to.is_some() => PctIdWrtSeq,
             SeqLen => PctIdWrtConsensus,
+            PctIdWrtSeq => PctIdWrtConsensus,
         };
         self.recompute_ordering();
     }
 
+    pub fn has_insert_columns(&self) -> bool {
+        self.alt_alignment.is_some()
+    }
+
+    pub fn insert_columns_shown(&self) -> bool {
+        self.insert_columns_shown
+    }
+
+    // No-op unless the alignment is A2M or A3M.
+    pub fn toggle_insert_columns(&mut self) {
+        if let Some(alt) = self.alt_alignment.as_mut() {
+            std::mem::swap(&mut self.alignment, alt);
+            self.insert_columns_shown = !self.insert_columns_shown;
+            // Columns have changed, so hits must be mapped again.
+            if let Some(search) = self.search.as_mut() {
+                search.find(&self.alignment.sequences);
+            }
+            self.refresh_subset_stats();
+            self.refresh_pairwise();
+            self.recompute_ordering();
+        }
+    }
+
+    // Pairwise identity (see alignment/pairwise.rs)
+
+    pub fn gap_handling(&self) -> GapHandling {
+        self.gap_handling
+    }
+
+    pub fn set_gap_handling(&mut self, gaps: GapHandling) {
+        self.gap_handling = gaps;
+        self.refresh_pairwise();
+        self.recompute_ordering();
+    }
+
+    // To be called when the sequences or the gap handling change.
+    fn refresh_pairwise(&mut self) {
+        self.identities.clear();
+        self.tree = None;
+        if let Some(seq) = self.similar_to {
+            self.id_wrt_seq = self.identities_to(seq);
+        }
+    }
+
+    // Computes the identities of each sequence of `seqs1` to each of `seqs2` (indices into the
+    // alignment's), unless they are already known.
+    pub fn compute_identities(&mut self, seqs1: &[usize], seqs2: &[usize]) {
+        let sequences = &self.alignment.sequences;
+        for &seq1 in seqs1 {
+            for &seq2 in seqs2 {
+                self.identities
+                    .entry((seq1.min(seq2), seq1.max(seq2)))
+                    .or_insert_with(|| {
+                        identity(&sequences[seq1], &sequences[seq2], self.gap_handling)
+                    });
+            }
+        }
+    }
+
+    // Identity of two sequences (indices into the alignment's), provided it has been computed
+    // (see compute_identities()).
+    pub fn pairwise_identity(&self, seq1: usize, seq2: usize) -> Option<f64> {
+        self.identities.get(&(seq1.min(seq2), seq1.max(seq2))).copied()
+    }
+
+    fn identities_to(&self, seq: usize) -> Vec<f64> {
+        let chosen = &self.alignment.sequences[seq];
+        self.alignment
+            .sequences
+            .iter()
+            .map(|s| identity(s, chosen, self.gap_handling))
+            .collect()
+    }
+
+    // Orders the sequences by decreasing identity to the given one (which thus comes first, unless
+    // it has duplicates).
+    pub fn order_by_similarity(&mut self, seq: usize) {
+        self.similar_to = Some(seq);
+        self.id_wrt_seq = self.identities_to(seq);
+        self.metric = PctIdWrtSeq;
+        self.ordering_criterion = MetricDecr;
+        self.recompute_ordering();
+    }
+
+    // Writes the identity or p-distance matrix of the shown sequences (in the current order) as
+    // TSV, to a file or to standard output (see create_output()). If `columns` is given, only
+    // those are compared.
+    pub fn export_pairwise(
+        &self,
+        path: &str,
+        measure: Measure,
+        columns: Option<Range<usize>>,
+    ) -> std::io::Result<()> {
+        let columns = columns.unwrap_or(0..self.alignment.aln_len());
+        let records = self.records(&self.ordering, &columns);
+        let sequences: Vec<&str> = records.iter().map(|r| r.sequence.as_str()).collect();
+        let matrix = identity_matrix(&sequences, self.gap_handling);
+        let ids: Vec<&str> = records.iter().map(|r| seq_id(&r.header)).collect();
+        let mut writer = create_output(path)?;
+        write_matrix(&mut writer, &ids, &matrix, measure)?;
+        writer.flush()
+    }
+
+    // Motif search (see motif.rs)
+
+    // Returns the number of hits.
+    pub fn search(&mut self, pattern: &str) -> Result<usize, regex::Error> {
+        let regex = compile_pattern(pattern)?;
+        self.search = Some(Search::new(pattern, regex, &self.alignment.sequences));
+        self.sort_hits();
+        Ok(self.num_hits())
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search = None;
+    }
+
+    pub fn search_pattern(&self) -> Option<&str> {
+        self.search.as_ref().map(|s| s.pattern.as_str())
+    }
+
+    pub fn num_hits(&self) -> usize {
+        self.search.as_ref().map_or(0, |s| s.hits.len())
+    }
+
+    // The current hit and its (0-based) number, if any.
+    pub fn current_hit(&self) -> Option<(usize, &Hit)> {
+        let search = self.search.as_ref()?;
+        let current = search.current?;
+        Some((current, &search.hits[current]))
+    }
+
+    // Makes the next (or previous) hit the current one, wrapping around at either end.
+    pub fn step_hit(&mut self, forward: bool) -> Option<(usize, &Hit)> {
+        let search = self.search.as_mut()?;
+        let n = search.hits.len();
+        if n == 0 {
+            return None;
+        }
+        search.current = Some(match (search.current, forward) {
+            (None, true) => 0,
+            (None, false) => n - 1,
+            (Some(i), true) => (i + 1) % n,
+            (Some(i), false) => (i + n - 1) % n,
+        });
+        self.current_hit()
+    }
+
+    pub fn is_hit(&self, seq: usize, col: usize) -> bool {
+        self.search.as_ref().is_some_and(|s| s.is_hit(seq, col))
+    }
+
+    fn sort_hits(&mut self) {
+        if let Some(search) = self.search.as_mut() {
+            search.arrange(&self.ordering);
+        }
+    }
+
+    // Label search and filter
+
+    // Positions (in the current ordering) of the shown sequences whose header matches.
+    pub fn find_labels(&self, pattern: &str) -> Result<Vec<usize>, regex::Error> {
+        let re = label_regex(pattern)?;
+        Ok(self
+            .ordering
+            .iter()
+            .enumerate()
+            .filter(|(_, &seq)| re.is_match(&self.alignment.headers[seq]))
+            .map(|(row, _)| row)
+            .collect())
+    }
+
+    // Returns the number of matching sequences. If there are none, the filter is not applied
+    // (there would be nothing to show).
+    pub fn set_label_filter(&mut self, pattern: &str) -> Result<usize, regex::Error> {
+        let re = label_regex(pattern)?;
+        let num_matches = self
+            .alignment
+            .headers
+            .iter()
+            .zip(&self.hidden)
+            .filter(|(h, &hidden)| !hidden && re.is_match(h))
+            .count();
+        if num_matches > 0 {
+            self.label_filter = Some((pattern.to_string(), re));
+            self.refresh_subset_stats();
+            self.recompute_ordering();
+        }
+        Ok(num_matches)
+    }
+
+    pub fn clear_label_filter(&mut self) {
+        self.label_filter = None;
+        self.refresh_subset_stats();
+        self.recompute_ordering();
+    }
+
+    pub fn label_filter(&self) -> Option<&str> {
+        self.label_filter.as_ref().map(|(pattern, _)| pattern.as_str())
+    }
+
+    // Hidden sequences
+
+    // Returns the number of sequences hidden. Hiding all the shown sequences is refused (there
+    // would be nothing to show), in which case nothing is hidden.
+    pub fn hide_seqs(&mut self, seqs: &[usize]) -> usize {
+        let newly_hidden: Vec<usize> = seqs.iter().copied().filter(|&i| !self.hidden[i]).collect();
+        if newly_hidden.len() >= self.ordering.len() {
+            return 0;
+        }
+        for &i in &newly_hidden {
+            self.hidden[i] = true;
+        }
+        self.refresh_subset_stats();
+        self.recompute_ordering();
+        newly_hidden.len()
+    }
+
+    // Hides all the shown sequences except these.
+    pub fn show_only(&mut self, seqs: &[usize]) -> usize {
+        let others: Vec<usize> = self
+            .ordering
+            .iter()
+            .copied()
+            .filter(|i| !seqs.contains(i))
+            .collect();
+        self.hide_seqs(&others)
+    }
+
+    // Returns the number of sequences that were hidden.
+    pub fn unhide_all(&mut self) -> usize {
+        let num_hidden = self.num_hidden();
+        self.hidden.fill(false);
+        self.refresh_subset_stats();
+        self.recompute_ordering();
+        num_hidden
+    }
+
+    pub fn num_hidden(&self) -> usize {
+        self.hidden.iter().filter(|&&h| h).count()
+    }
+
+    pub fn toggle_stats_on_subset(&mut self) {
+        self.stats_on_subset = !self.stats_on_subset;
+        self.refresh_subset_stats();
+        self.recompute_ordering();
+    }
+
+    // True if the statistics currently shown are those of a subset.
+    pub fn stats_are_on_subset(&self) -> bool {
+        self.subset_stats.is_some()
+    }
+
+    fn refresh_subset_stats(&mut self) {
+        self.subset_stats = None;
+        if !self.stats_on_subset || (self.label_filter.is_none() && self.num_hidden() == 0) {
+            return;
+        }
+        let members: Vec<usize> = (0..self.alignment.num_seq())
+            .filter(|&i| self.is_shown(i))
+            .collect();
+        let records = members
+            .iter()
+            .map(|&i| FastaRecord {
+                header: self.alignment.headers[i].clone(),
+                sequence: self.alignment.sequences[i].clone(),
+            })
+            .collect();
+        let alignment = Alignment::new(records);
+        let mut id_wrt_consensus = vec![0.0; self.alignment.num_seq()];
+        let mut relative_seq_len = vec![0.0; self.alignment.num_seq()];
+        for (k, &i) in members.iter().enumerate() {
+            id_wrt_consensus[i] = alignment.id_wrt_consensus[k];
+            relative_seq_len[i] = alignment.relative_seq_len[k];
+        }
+        self.subset_stats = Some(SubsetStats {
+            alignment,
+            id_wrt_consensus,
+            relative_seq_len,
+        });
+    }
+
+    // The alignment whose column statistics are shown: the whole one, or the shown subset.
+    fn stats_alignment(&self) -> &Alignment {
+        self.subset_stats
+            .as_ref()
+            .map_or(&self.alignment, |s| &s.alignment)
+    }
+
+    pub fn consensus(&self) -> &str {
+        &self.stats_alignment().consensus
+    }
+
+    pub fn conservation(&self) -> Vec<f64> {
+        self.stats_alignment().conservation()
+    }
+
+    pub fn logo_stack(&self, col: usize) -> Vec<(char, f64)> {
+        self.stats_alignment().logo_stack(col)
+    }
+
+    pub fn max_information(&self) -> f64 {
+        self.stats_alignment().max_information()
+    }
+
+    // Statistics of the given sequences (which should not be empty), restricted to the given
+    // columns.
+    pub fn block_stats(&self, seqs: &[usize], columns: Range<usize>) -> BlockStats {
+        let records = self.records(seqs, &columns);
+        let num_residues = records.len() * columns.len();
+        let num_gaps: usize = records
+            .iter()
+            .map(|r| r.sequence.chars().filter(|&c| is_gap(c)).count())
+            .sum();
+        let block = Alignment::new(records);
+        BlockStats {
+            mean_identity: mean(&block.id_wrt_consensus),
+            gap_fraction: num_gaps as f64 / num_residues as f64,
+            consensus: block.consensus,
+        }
+    }
+
     pub fn output_info(&self) {
         println!("name: {}", self.filename);
+        println!("format: {}", self.format);
         println!("nb_sequences: {}", self.num_seq());
         println!("nb_columns: {}", self.aln_len());
         println!();
     }
 
+    // The given sequences, restricted to the given columns.
+    fn records(&self, seqs: &[usize], columns: &Range<usize>) -> FastaFile {
+        seqs.iter()
+            .map(|&i| FastaRecord {
+                header: self.alignment.headers[i].clone(),
+                sequence: self.alignment.sequences[i]
+                    .chars()
+                    .skip(columns.start)
+                    .take(columns.len())
+                    .collect(),
+            })
+            .collect()
+    }
+
+    // Writes the given sequences (e.g. all the shown ones, in the current order, or a selection) in
+    // the given format. If `columns` is given, only those are written (e.g., the ones shown in the
+    // zoom box).
+    pub fn export_seqs<W: Write>(
+        &self,
+        writer: W,
+        format: FileFormat,
+        seqs: &[usize],
+        columns: Option<Range<usize>>,
+    ) -> std::io::Result<()> {
+        let columns = columns.unwrap_or(0..self.alignment.aln_len());
+        let records = self.records(seqs, &columns);
+        match format {
+            FileFormat::FastA | FileFormat::A2M => write_fasta(writer, &records),
+            FileFormat::A3M => write_fasta(writer, &to_a3m(&records)),
+            FileFormat::Stockholm => write_stockholm(
+                writer,
+                &records,
+                &self.alignment.annotations.columns(&columns),
+            ),
+            // In these formats, '.' is not a gap (if anything, it's a match character).
+            FileFormat::Clustal => write_clustal(writer, &dots_to_dashes(records)),
+            FileFormat::Phylip => write_phylip(writer, &dots_to_dashes(records)),
+            FileFormat::Nexus => write_nexus(
+                writer,
+                &dots_to_dashes(records),
+                self.alignment.macromolecule_type,
+            ),
+        }
+    }
+
+    // Like export_seqs(), but to a file whose format is given by its extension (or to standard
+    // output, see create_output()). All the shown sequences are written unless `seqs` is given.
+    pub fn export_to_file(
+        &self,
+        path: &str,
+        seqs: Option<&[usize]>,
+        columns: Option<Range<usize>>,
+    ) -> std::io::Result<FileFormat> {
+        let format = format_from_extension(path);
+        let seqs = seqs.unwrap_or(&self.ordering);
+        let mut writer = create_output(path)?;
+        self.export_seqs(&mut writer, format, seqs, columns)?;
+        writer.flush()?;
+        Ok(format)
+    }
+
     pub fn get_seq_ordering(&self) -> SeqOrdering {
         self.ordering_criterion
     }
@@ -132,9 +813,18 @@ impl App {
     }
 
     pub fn order_values(&self) -> &Vec<f64> {
+        if let Some(stats) = &self.subset_stats {
+            return match self.metric {
+                PctIdWrtConsensus => &stats.id_wrt_consensus,
+                SeqLen => &stats.relative_seq_len,
+                // Pairwise identities do not depend on the other sequences.
+                PctIdWrtSeq => &self.id_wrt_seq,
+            };
+        }
          match self.metric {
             PctIdWrtConsensus => &self.alignment.id_wrt_consensus,
             SeqLen => &self.alignment.relative_seq_len, 
+            PctIdWrtSeq => &self.id_wrt_seq,
         }
     }
 }
@@ -151,10 +841,23 @@ fn order(nums: &Vec<f64>) -> Vec<usize> {
     unsorted_pairs.into_iter().map(|(u, _)| *u).collect::<Vec<usize>>()
 }
 
+fn dots_to_dashes(records: FastaFile) -> FastaFile {
+    records
+        .into_iter()
+        .map(|r| FastaRecord {
+            header: r.header,
+            sequence: r.sequence.replace('.', "-"),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
 
-    use crate::app::order;
+    use crate::alignment::pairwise::{GapHandling, Measure};
+    use crate::app::{leaf_matches, order, App, MAX_GUIDE_TREE_SEQS};
+    use crate::file_format::FileFormat;
+    use crate::newick::{parse_newick, read_newick_file};
 
     #[test]
     fn test_order_00() {
@@ -171,4 +874,196 @@ mod tests {
             order(&vec![12.23, 34.89, 7.0, -23.2, 100.0]),
             );
     }
+
+    #[test]
+    fn test_search_hits_in_display_order() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        // seq1 TTGCCG-CGA, seq2 TTCCCGGCGA, seq3 TTACCG-CAA
+        assert_eq!(app.search("cg").unwrap(), 5);
+        app.ordering = vec![2, 1, 0];
+        app.sort_hits();
+        assert_eq!(app.step_hit(true).unwrap().1.seq, 2);
+        assert_eq!(app.step_hit(false).unwrap().1.cols, 7..9);
+        assert!(app.is_hit(0, 4) && app.is_hit(0, 8) && !app.is_hit(0, 6));
+        assert!(app.search("C(").is_err());
+    }
+
+    #[test]
+    fn test_label_filter() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        assert_eq!(app.set_label_filter("nomatch").unwrap(), 0);
+        assert_eq!(app.num_seq(), 3);
+        assert_eq!(app.set_label_filter("SEQ[13]").unwrap(), 2);
+        assert_eq!(app.ordering, vec![0, 2]);
+        assert_eq!(app.find_labels("3").unwrap(), vec![1]);
+        // Statistics stay those of the whole alignment unless asked for. (A single sequence is
+        // its own consensus, whereas ties make the consensus of several ones unpredictable.)
+        let consensus = app.consensus().to_string();
+        app.set_label_filter("seq2").unwrap();
+        app.toggle_stats_on_subset();
+        assert!(app.stats_are_on_subset());
+        assert_eq!(app.consensus().to_uppercase(), "TTCCCGGCGA");
+        app.clear_label_filter();
+        assert!(!app.stats_are_on_subset());
+        assert_eq!(app.consensus(), consensus);
+        assert_eq!(app.num_seq(), 3);
+    }
+
+    #[test]
+    fn test_export_ordering_and_columns() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        app.ordering = vec![2, 0];
+        let mut out: Vec<u8> = Vec::new();
+        app.export_seqs(&mut out, FileFormat::FastA, &app.ordering, Some(2..6)).unwrap();
+        assert_eq!(">seq3\nACCG\n>seq1\nGCCG\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_hide_and_show_only() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        assert_eq!(app.hide_seqs(&[1]), 1);
+        assert_eq!(app.ordering, vec![0, 2]);
+        // Hiding all the shown sequences is refused.
+        assert_eq!(app.hide_seqs(&[0, 2]), 0);
+        assert_eq!(app.show_only(&[2]), 1);
+        assert_eq!(app.ordering, vec![2]);
+        assert_eq!(app.unhide_all(), 2);
+        assert_eq!(app.ordering, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_block_stats() {
+        let app = App::new("data/test2.fas").unwrap();
+        // Columns 5-8 of seq1 and seq3: CG-C and CG-C
+        let stats = app.block_stats(&[0, 2], 4..8);
+        assert_eq!(stats.consensus, "CG-C");
+        assert_eq!(stats.mean_identity, 1.0);
+        assert_eq!(stats.gap_fraction, 0.25);
+    }
+
+    #[test]
+    fn test_order_by_similarity() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        // seq1 TTGCCG-CGA, seq2 TTCCCGGCGA, seq3 TTACCG-CAA
+        app.order_by_similarity(1);
+        assert_eq!(app.ordering, vec![1, 0, 2]);
+        assert_eq!(app.order_values(), &vec![0.8, 1.0, 0.7]);
+        // The chosen sequence's metric comes after the others.
+        app.cycle_metric();
+        app.cycle_metric();
+        assert_eq!(app.ordering, vec![1, 2, 0]);
+        app.cycle_metric();
+        assert_eq!(app.ordering, vec![1, 0, 2]);
+        // seq2 has no gaps, but seq1 and seq3 have a gap pair.
+        app.set_gap_handling(GapHandling::GapsAsMismatches);
+        assert_eq!(app.pairwise_identity(0, 2), None);
+        app.compute_identities(&[2], &[0, 1]);
+        assert_eq!(app.pairwise_identity(0, 2), Some(0.7));
+        assert_eq!(app.pairwise_identity(2, 0), Some(0.7));
+        assert_eq!(app.pairwise_identity(0, 1), None);
+    }
+
+    #[test]
+    fn test_tree_ordering() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        // p-distances: 0.2 (seq1, seq2), 2/9 (seq1, seq3), 0.3 (seq2, seq3)
+        assert!(app.cycle_guide_tree().is_ok());
+        assert_eq!(app.get_seq_ordering().to_string(), "NJ");
+        assert_eq!(app.ordering, vec![2, 0, 1]);
+        assert_eq!(app.tree().unwrap().leaves(), app.ordering);
+        // Only the shown sequences are compared, and not through the identity map's cache.
+        assert!(app.pairwise_identity(0, 1).is_none());
+        // Trees are of the shown sequences only.
+        app.hide_seqs(&[0]);
+        assert_eq!(app.ordering, vec![1, 2]);
+        assert!(app.cycle_guide_tree().is_ok());
+        assert_eq!(app.get_seq_ordering().to_string(), "UPGMA");
+        assert_eq!(app.tree().unwrap().leaves(), app.ordering);
+        assert!(app.cycle_guide_tree().is_ok());
+        assert_eq!(app.get_seq_ordering().to_string(), "NJ");
+        // Guide trees are not part of the 'o' cycle.
+        app.cycle_ordering_criterion();
+        assert!(app.tree().is_none());
+        assert_eq!(app.get_seq_ordering().to_string(), "-");
+    }
+
+    #[test]
+    fn test_guide_tree_limit() {
+        let path = std::env::temp_dir().join("termal-test-guide-tree.fas");
+        let fasta: String = (0..=MAX_GUIDE_TREE_SEQS)
+            .map(|i| format!(">seq{}\nAC\n", i))
+            .collect();
+        std::fs::write(&path, fasta).unwrap();
+        let mut app = App::new(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(app.cycle_guide_tree().is_err());
+        assert_eq!(app.get_seq_ordering().to_string(), "-");
+        // Below the limit, but not once the hidden sequence is shown again.
+        app.hide_seqs(&[0]);
+        assert!(app.cycle_guide_tree().is_ok());
+        assert!(app.tree().is_some());
+        app.unhide_all();
+        assert_eq!(app.get_seq_ordering().to_string(), "-");
+        assert!(app.tree().is_none());
+    }
+
+    #[test]
+    fn test_set_tree() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        let newick = read_newick_file("data/test2.nwk").unwrap();
+        let tree_match = app.set_tree(&newick).unwrap();
+        assert_eq!(tree_match.unmatched_leaves, vec!["unknown"]);
+        assert!(tree_match.unmatched_seqs.is_empty());
+        assert_eq!(app.get_seq_ordering().to_string(), "tree");
+        assert_eq!(app.ordering, vec![2, 0, 1]);
+        // The tree is pruned to the shown sequences.
+        app.hide_seqs(&[0]);
+        assert_eq!(app.tree().unwrap().leaves(), vec![2, 1]);
+        // The tree's ordering is part of the cycle once it is loaded.
+        app.cycle_ordering_criterion();
+        assert_eq!(app.get_seq_ordering().to_string(), "-");
+        for _ in 0..3 {
+            app.cycle_ordering_criterion();
+        }
+        assert_eq!(app.get_seq_ordering().to_string(), "tree");
+
+        // Leaves match by ID or whole header (with underscores for spaces), each sequence only
+        // once; sequences not in the tree come last.
+        assert!(leaf_matches("sp|P1|NAME desc", "P1"));
+        assert!(leaf_matches("seq1 some desc", "seq1_some_desc"));
+        let newick = parse_newick("(('seq3',seq2),seq2,nothing);").unwrap();
+        let mut app = App::new("data/test2.fas").unwrap();
+        let tree_match = app.set_tree(&newick).unwrap();
+        assert_eq!(tree_match.unmatched_leaves, vec!["seq2", "nothing"]);
+        assert_eq!(tree_match.unmatched_seqs, vec![0]);
+        assert_eq!(app.ordering, vec![2, 1, 0]);
+        let newick = parse_newick("(a,b);").unwrap();
+        assert!(app.set_tree(&newick).is_err());
+    }
+
+    #[test]
+    fn test_export_pairwise() {
+        let app = App::new("data/test2.fas").unwrap();
+        let path = std::env::temp_dir().join("termal-test-pairwise.tsv");
+        let path = path.to_str().unwrap();
+        app.export_pairwise(path, Measure::Identity, Some(0..3)).unwrap();
+        let text = std::fs::read_to_string(path).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(
+            text,
+            "\tseq1\tseq2\tseq3\nseq1\t1.0000\t0.6667\t0.6667\n\
+             seq2\t0.6667\t1.0000\t0.6667\nseq3\t0.6667\t0.6667\t1.0000\n"
+        );
+    }
+
+    #[test]
+    fn test_export_stockholm_annotations() {
+        let app = App::new("data/test1.sto").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        app.export_seqs(&mut out, FileFormat::Stockholm, &app.ordering, Some(0..5)).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text
+            .lines()
+            .any(|l| l.starts_with("#=GC SS_cons") && l.ends_with(" HHHHH")));
+    }
 }
@@ -0,0 +1,157 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use crate::fasta::{seq_id, FastaFile, FastaRecord};
+use crate::errors::InputError;
+use crate::input::numbered_lines;
+
+#[cfg(test)]
+pub fn read_clustal_file<P: AsRef<std::path::Path>>(path: P) -> Result<FastaFile, InputError> {
+    read_clustal(crate::input::open_input(path)?)
+}
+
+// Reads Clustal W/X/Omega output (MUSCLE and a few others write the same format, but with a
+// different first word in the header). The alignment comes in blocks separated by blank lines;
+// each block has one line per sequence (name, a chunk of the aligned sequence, and optionally a
+// residue count), followed by a conservation line made of '*', ':', '.' and spaces. The
+// conservation line is discarded, as termal computes its own conservation.
+pub fn read_clustal<R: BufRead>(reader: R) -> Result<FastaFile, InputError> {
+    let mut result: FastaFile = Vec::new();
+    let mut seq_index: HashMap<String, usize> = HashMap::new();
+    let mut seen_header = false;
+
+    for line in numbered_lines(reader) {
+        let (line_num, l) = line?;
+        if l.trim().is_empty() {
+            continue;
+        }
+        if !seen_header {
+            if !is_clustal_header(&l) {
+                return Err(InputError::syntax(
+                    Some(line_num),
+                    "expected a Clustal header",
+                ));
+            }
+            seen_header = true;
+            continue;
+        }
+        // Sequence names cannot start with whitespace, so this is a conservation line.
+        if l.starts_with(char::is_whitespace) {
+            continue;
+        }
+
+        let fields: Vec<&str> = l.split_whitespace().collect();
+        let (name, chunk) = match fields.len() {
+            2 => (fields[0], fields[1]),
+            3 if fields[2].parse::<usize>().is_ok() => (fields[0], fields[1]),
+            _ => {
+                return Err(InputError::syntax(
+                    Some(line_num),
+                    "expected '<name> <aligned sequence> [<count>]'",
+                ))
+            }
+        };
+        match seq_index.get(name) {
+            Some(&ndx) => result[ndx].sequence.push_str(chunk),
+            None => {
+                seq_index.insert(name.to_string(), result.len());
+                result.push(FastaRecord {
+                    header: name.to_string(),
+                    sequence: chunk.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+const BLOCK_WIDTH: usize = 60;
+
+// Writes the alignment in blocks of BLOCK_WIDTH columns. The conservation line only marks fully
+// conserved columns ('*'), as the residue groups behind ':' and '.' are protein-specific.
+pub fn write_clustal<W: Write>(mut writer: W, records: &FastaFile) -> std::io::Result<()> {
+    let names: Vec<&str> = records.iter().map(|r| seq_id(&r.header)).collect();
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0) + 4;
+    let rows: Vec<Vec<char>> = records.iter().map(|r| r.sequence.chars().collect()).collect();
+    let aln_len = rows.first().map_or(0, |r| r.len());
+
+    writeln!(writer, "CLUSTAL multiple sequence alignment\n")?;
+    for start in (0..aln_len).step_by(BLOCK_WIDTH) {
+        let end = (start + BLOCK_WIDTH).min(aln_len);
+        writeln!(writer)?;
+        for (name, row) in names.iter().zip(rows.iter()) {
+            let chunk: String = row[start..end].iter().collect();
+            writeln!(writer, "{:width$}{}", name, chunk)?;
+        }
+        let conservation: String = (start..end)
+            .map(|j| {
+                let c = rows[0][j];
+                if c.is_alphabetic() && rows.iter().all(|r| r[j].eq_ignore_ascii_case(&c)) {
+                    '*'
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+        writeln!(writer, "{:width$}{}", "", conservation)?;
+    }
+    Ok(())
+}
+
+pub fn is_clustal_header(line: &str) -> bool {
+    ["CLUSTAL", "MUSCLE", "PROBCONS"]
+        .iter()
+        .any(|prog| line.starts_with(prog))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_clustal_file_1() {
+        let aln = read_clustal_file("data/test-clustal.aln").expect("Test file not found");
+        assert_eq!(aln.len(), 3);
+        assert_eq!(aln[0].header, "seq1");
+        assert_eq!(aln[0].sequence, "MKV-LAAGIVGLLLAQPAMAAEHHHHHH");
+        assert_eq!(aln[1].header, "seq2");
+        assert_eq!(aln[1].sequence, "MKVALAAGIVGLLLA-PAMAAE------");
+        assert_eq!(aln[2].header, "seq3");
+        assert_eq!(aln[2].sequence, "MRV-LAAGLVGLLLAQPAMSAEHHH---");
+    }
+
+    #[test]
+    fn test_read_clustal_no_header() {
+        let text = ">seq1\nACGT\n";
+        let err = read_clustal(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, InputError::Syntax { line: Some(1), .. }));
+    }
+
+    #[test]
+    fn test_write_clustal() {
+        let aln = read_clustal_file("data/test-clustal.aln").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        write_clustal(&mut out, &aln).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("CLUSTAL"));
+        assert!(text.contains("\nseq1    MKV-LAAGIVGLLLAQPAMAAEHHHHHH\n"));
+        assert!(text.contains("\n        * * ****"));
+        let aln2 = read_clustal(text.as_bytes()).unwrap();
+        assert_eq!(aln.len(), aln2.len());
+        for (r1, r2) in aln.iter().zip(aln2.iter()) {
+            assert_eq!(r1.header, r2.header);
+            assert_eq!(r1.sequence, r2.sequence);
+        }
+    }
+
+    #[test]
+    fn test_is_clustal_header() {
+        assert!(is_clustal_header("CLUSTAL W (1.83) multiple sequence alignment"));
+        assert!(is_clustal_header("CLUSTAL O(1.2.4) multiple sequence alignment"));
+        assert!(is_clustal_header("MUSCLE (3.8) multiple sequence alignment"));
+        assert!(!is_clustal_header(">CLUSTAL"));
+    }
+}
@@ -0,0 +1,276 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* The user's configuration file, in TOML: $XDG_CONFIG_HOME/termal/config.toml, or
+ * ~/.config/termal/config.toml if XDG_CONFIG_HOME is not set (--config gives another file). The
+ * top-level settings are defaults for command-line options (named after the long option, e.g.
+ * no-color = true), plus a few that have no option, such as the initial zoom level (see
+ * Settings). The [keys] table remaps key bindings (see ui/keymap.rs). */
+
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use crate::{
+    alignment::{
+        pairwise::{GapHandling, GAP_HANDLINGS},
+        SeqType,
+    },
+    ui::{
+        color_scheme::{Theme, THEMES},
+        BottomPanePosition, VideoMode, ZoomLevel,
+    },
+};
+
+// Defaults for command-line options. Options that name an output or only make sense for one run
+// (e.g. --export, --render-once, --info) are not among them.
+const OPTIONS: &[&str] = &[
+    "color-map",
+    "colormap-dir",
+    "hide-labels-pane",
+    "hide-bottom-pane",
+    "debug",
+    "no-color",
+    "no-mouse",
+    "no-scrollbars",
+    "poll-wait-time",
+    "no-zoom-box",
+    "no-zb-guides",
+    "hide-insert-columns",
+    "match-char",
+    "theme",
+    "gaps",
+];
+
+// Settings without an option, and tables.
+const SETTINGS: &[&str] = &[
+    "zoom",
+    "video",
+    "label-pane-width",
+    "bottom-pane",
+    "colormap",
+    "keys",
+];
+
+#[derive(Default)]
+pub struct Config {
+    table: toml::Table,
+}
+
+// The initial state of the UI, where it differs from the default (see UI::apply_settings()).
+#[derive(Default)]
+pub struct Settings {
+    pub zoom_level: Option<ZoomLevel>,
+    pub video_mode: Option<VideoMode>,
+    pub label_pane_width: Option<u16>,
+    pub bottom_pane_position: Option<BottomPanePosition>,
+    // Names of the initial colormaps for each kind of sequence.
+    pub colormaps: Vec<(SeqType, String)>,
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("termal"))
+}
+
+pub fn default_config_path() -> Option<PathBuf> {
+    Some(config_dir()?.join("config.toml"))
+}
+
+// Gecos color maps (*.json) in this directory are loaded at startup, if it exists.
+pub fn default_colormap_dir() -> Option<PathBuf> {
+    Some(config_dir()?.join("colormaps"))
+}
+
+impl Config {
+    // Unknown settings are errors, as they are most likely typos.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let table = text.parse::<toml::Table>().map_err(|e| e.to_string())?;
+        if let Some(name) = table
+            .keys()
+            .find(|name| !OPTIONS.contains(&name.as_str()) && !SETTINGS.contains(&name.as_str()))
+        {
+            return Err(format!("unknown setting '{}'", name));
+        }
+        Ok(Config { table })
+    }
+
+    // Reads the given file, or else the default one. The default file need not exist (the
+    // configuration is then empty), but a given one must.
+    pub fn read(path: Option<&str>) -> Result<Config, String> {
+        let (path, must_exist) = match path {
+            Some(path) => (PathBuf::from(path), true),
+            None => match default_config_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound && !must_exist => {
+                return Ok(Config::default())
+            }
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        Config::parse(&text).map_err(|e| format!("{}: {}", path.display(), e.trim_end()))
+    }
+
+    // The [keys] table, if any.
+    pub fn keys(&self) -> Result<Option<&toml::Table>, String> {
+        self.table("keys")
+    }
+
+    fn table(&self, name: &str) -> Result<Option<&toml::Table>, String> {
+        match self.table.get(name) {
+            None => Ok(None),
+            Some(toml::Value::Table(table)) => Ok(Some(table)),
+            Some(_) => Err(format!("'{}' should be a table ([{}])", name, name)),
+        }
+    }
+
+    // Absent flags are false.
+    pub fn flag(&self, name: &str) -> Result<bool, String> {
+        match self.table.get(name) {
+            None => Ok(false),
+            Some(value) => value
+                .as_bool()
+                .ok_or_else(|| format!("'{}' should be true or false", name)),
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Result<Option<&str>, String> {
+        self.table
+            .get(name)
+            .map(|value| value.as_str().ok_or_else(|| format!("'{}' should be a string", name)))
+            .transpose()
+    }
+
+    pub fn character(&self, name: &str) -> Result<Option<char>, String> {
+        let error = || format!("'{}' should be a single character", name);
+        self.string(name)
+            .map_err(|_| error())?
+            .map(|s| {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(c),
+                    _ => Err(error()),
+                }
+            })
+            .transpose()
+    }
+
+    pub fn number<T: TryFrom<i64>>(&self, name: &str) -> Result<Option<T>, String> {
+        self.table
+            .get(name)
+            .map(|value| {
+                value
+                    .as_integer()
+                    .and_then(|n| T::try_from(n).ok())
+                    .ok_or_else(|| format!("'{}' should be a (non-negative) number", name))
+            })
+            .transpose()
+    }
+
+    pub fn theme(&self) -> Result<Option<Theme>, String> {
+        self.choice("theme", THEMES)
+    }
+
+    pub fn gap_handling(&self) -> Result<Option<GapHandling>, String> {
+        self.choice("gaps", GAP_HANDLINGS)
+    }
+
+    // A setting that takes one of a few values, e.g. zoom = "out".
+    fn choice<T: Copy>(&self, name: &str, choices: &[(&str, T)]) -> Result<Option<T>, String> {
+        let Some(value) = self.string(name)? else {
+            return Ok(None);
+        };
+        match choices.iter().find(|(s, _)| *s == value) {
+            Some((_, choice)) => Ok(Some(*choice)),
+            None => {
+                let names: Vec<&str> = choices.iter().map(|(s, _)| *s).collect();
+                Err(format!("'{}' should be one of {}", name, names.join(", ")))
+            }
+        }
+    }
+
+    pub fn settings(&self) -> Result<Settings, String> {
+        let mut colormaps = Vec::new();
+        for (name, value) in self.table("colormap")?.into_iter().flatten() {
+            let seq_type = match name.as_str() {
+                "protein" => SeqType::Protein,
+                "nucleic" => SeqType::Nucleic,
+                _ => return Err(format!("'colormap' has protein and nucleic, not '{}'", name)),
+            };
+            let map = value
+                .as_str()
+                .ok_or_else(|| format!("'colormap.{}' should be a string", name))?;
+            colormaps.push((seq_type, map.to_string()));
+        }
+        Ok(Settings {
+            zoom_level: self.choice(
+                "zoom",
+                &[
+                    ("in", ZoomLevel::ZoomedIn),
+                    ("out", ZoomLevel::ZoomedOut),
+                    ("out-ar", ZoomLevel::ZoomedOutAR),
+                ],
+            )?,
+            video_mode: self.choice(
+                "video",
+                &[("inverse", VideoMode::Inverse), ("direct", VideoMode::Direct)],
+            )?,
+            label_pane_width: self.number("label-pane-width")?,
+            bottom_pane_position: self.choice(
+                "bottom-pane",
+                &[
+                    ("adjacent", BottomPanePosition::Adjacent),
+                    ("screen-bottom", BottomPanePosition::ScreenBottom),
+                ],
+            )?,
+            colormaps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_table() {
+        let config = Config::parse("[keys]\nline-down = \"<C-n>\"\n").unwrap();
+        let keys = config.keys().unwrap().unwrap();
+        assert_eq!(keys["line-down"].as_str(), Some("<C-n>"));
+        assert!(Config::parse("").unwrap().keys().unwrap().is_none());
+        assert!(Config::parse("keys = 3").unwrap().keys().is_err());
+        assert!(Config::parse("[keys\n").is_err());
+    }
+
+    #[test]
+    fn test_settings() {
+        let text = "no-color = true\npoll-wait-time = 50\nmatch-char = \"*\"\nzoom = \"out-ar\"\n\
+                    label-pane-width = 30\n[colormap]\nprotein = \"Lesk\"\n";
+        let config = Config::parse(text).unwrap();
+        assert!(config.flag("no-color").unwrap());
+        assert!(!config.flag("no-mouse").unwrap());
+        assert_eq!(config.number::<u64>("poll-wait-time").unwrap(), Some(50));
+        assert_eq!(config.character("match-char").unwrap(), Some('*'));
+        let settings = config.settings().unwrap();
+        assert_eq!(settings.zoom_level, Some(ZoomLevel::ZoomedOutAR));
+        assert_eq!(settings.label_pane_width, Some(30));
+        assert!(config.theme().unwrap().is_none());
+        assert_eq!(settings.colormaps, vec![(SeqType::Protein, "Lesk".to_string())]);
+
+        assert!(Config::parse("no-colour = true").is_err());
+        let bad = |text: &str| Config::parse(text).unwrap().settings().is_err();
+        assert!(bad("zoom = \"far\""));
+        assert!(bad("label-pane-width = -3"));
+        assert!(bad("[colormap]\nrna = \"Lesk\""));
+        assert!(Config::parse("no-color = 1").unwrap().flag("no-color").is_err());
+        assert!(Config::parse("match-char = \"ab\"").unwrap().character("match-char").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+use std::fmt;
+use std::io;
+
+// Problems found while reading an alignment. Line numbers start at 1; they are not always
+// available (e.g. when a check is made on the alignment as a whole, after reading it).
+#[derive(Debug)]
+pub enum InputError {
+    Io(io::Error),
+    Empty,
+    NotUtf8 {
+        line: usize,
+    },
+    SequenceBeforeHeader {
+        line: usize,
+    },
+    UnequalLength {
+        line: Option<usize>,
+        id: String,
+        expected: usize,
+        found: usize,
+    },
+    DuplicateId {
+        line: Option<usize>,
+        id: String,
+    },
+    // A character that is neither a residue nor a gap (see alignment::is_valid_symbol()).
+    InvalidResidue {
+        line: Option<usize>,
+        id: String,
+        residue: char,
+    },
+    // Anything specific to a format (malformed header, unknown markup, etc.).
+    Syntax {
+        line: Option<usize>,
+        msg: String,
+    },
+}
+
+impl InputError {
+    pub fn syntax(line: Option<usize>, msg: impl Into<String>) -> InputError {
+        InputError::Syntax {
+            line,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            InputError::Io(_) | InputError::Empty => None,
+            InputError::NotUtf8 { line } | InputError::SequenceBeforeHeader { line } => Some(*line),
+            InputError::UnequalLength { line, .. }
+            | InputError::DuplicateId { line, .. }
+            | InputError::InvalidResidue { line, .. }
+            | InputError::Syntax { line, .. } => *line,
+        }
+    }
+}
+
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line() {
+            write!(f, "line {}: ", line)?;
+        }
+        match self {
+            InputError::Io(e) => write!(f, "{}", e),
+            InputError::Empty => write!(f, "no sequences found"),
+            InputError::NotUtf8 { .. } => write!(f, "invalid UTF-8"),
+            InputError::SequenceBeforeHeader { .. } => {
+                write!(f, "sequence data before the first header")
+            }
+            InputError::UnequalLength {
+                id,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "sequence '{}' has length {}, expected {}",
+                id, found, expected
+            ),
+            InputError::DuplicateId { id, .. } => write!(f, "duplicate sequence ID '{}'", id),
+            InputError::InvalidResidue { id, residue, .. } => write!(
+                f,
+                "sequence '{}' contains '{}', which is neither a residue nor a gap",
+                id, residue
+            ),
+            InputError::Syntax { msg, .. } => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for InputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InputError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for InputError {
+    fn from(e: io::Error) -> Self {
+        InputError::Io(e)
+    }
+}
@@ -1,6 +1,12 @@
-use std::fs::File;
-use std::path::Path;
-use std::io::{BufRead, BufReader};
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+
+use itertools::Itertools;
+
+use crate::a3m::is_a3m;
+use crate::alignment::is_valid_symbol;
+use crate::errors::InputError;
+use crate::input::numbered_lines;
 
 #[derive(Debug)]
 pub struct FastaRecord {
@@ -10,14 +16,32 @@ pub struct FastaRecord {
 
 pub type FastaFile = Vec<FastaRecord>;
 
-pub fn read_fasta_file<P: AsRef<Path>>(path: P) -> Result<FastaFile, std::io::Error> {
-    let file = File::open(path)?;
+const LINE_WIDTH: usize = 60;
+
+// The ID is the header's first word, the rest being a description. Formats other than FastA only
+// have room for the ID.
+pub fn seq_id(header: &str) -> &str {
+    header.split_whitespace().next().unwrap_or("")
+}
+
+// For tests: App::new() uses read_fasta() directly, as it has already opened the input (which
+// may be compressed, see input.rs) to find out its format.
+#[cfg(test)]
+pub fn read_fasta_file<P: AsRef<std::path::Path>>(path: P) -> Result<FastaFile, InputError> {
+    read_fasta(crate::input::open_input(path)?)
+}
+
+// Rows of unequal length are accepted only if they make up an A3M alignment (see a3m.rs).
+pub fn read_fasta<R: BufRead>(reader: R) -> Result<FastaFile, InputError> {
     let mut result: FastaFile = Vec::new();
     let mut current_record = FastaRecord { header: String::new(), sequence: String::new() };
     let mut first_header = true;
+    // Line number of each record's header, for error messages.
+    let mut header_lines: Vec<usize> = Vec::new();
 
-    for line in BufReader::new(file).lines() {
-        let l: String = line.unwrap();
+    for line in numbered_lines(reader) {
+        // Read errors are not only I/O errors, but also corrupt compressed data.
+        let (line_num, l) = line?;
         if l.starts_with(">") { 
             if first_header {
                 first_header = false;
@@ -26,16 +50,111 @@ pub fn read_fasta_file<P: AsRef<Path>>(path: P) -> Result<FastaFile, std::io::Er
                 result.push(current_record);
             }
             current_record = FastaRecord { header: String::new(), sequence: String::new() };
-            current_record.header.push_str(&l[1..]);
+            current_record.header.push_str(l.strip_prefix('>').unwrap());
+            header_lines.push(line_num);
+        } else if first_header {
+            // Comment lines are allowed before the first header, e.g. "#A3M#" in HHblits output.
+            if !l.trim().is_empty() && !l.starts_with(['#', ';']) {
+                return Err(InputError::SequenceBeforeHeader { line: line_num });
+            }
         } else {
+            // Editors and pipelines often leave trailing whitespace.
+            let l = l.trim_end();
+            if let Some(residue) = l.chars().find(|&c| !is_valid_symbol(c)) {
+                return Err(InputError::InvalidResidue {
+                    line: Some(line_num),
+                    id: seq_id(&current_record.header).to_string(),
+                    residue,
+                });
+            }
             // append line to current record'd sequence
-            current_record.sequence.push_str(&l);
+            current_record.sequence.push_str(l);
         }
     }
+    if first_header {
+        return Err(InputError::Empty);
+    }
     result.push(current_record);
+    // Headers without residues are no more an alignment than no headers at all.
+    if result.iter().all(|record| record.sequence.is_empty()) {
+        return Err(InputError::Empty);
+    }
+
+    check_ids(&result, Some(&header_lines))?;
+    if !is_a3m(&result) {
+        check_lengths(&result, Some(&header_lines))?;
+    }
     Ok(result)
 }
 
+// Checks that apply whatever the format, once the alignment has been read. Line numbers are not
+// known at this stage; readers that know them make the same checks themselves.
+pub fn check_records(records: &FastaFile) -> Result<(), InputError> {
+    if records.iter().all(|record| record.sequence.is_empty()) {
+        return Err(InputError::Empty);
+    }
+    check_ids(records, None)?;
+    check_residues(records)?;
+    check_lengths(records, None)
+}
+
+fn check_residues(records: &FastaFile) -> Result<(), InputError> {
+    for record in records {
+        if let Some(residue) = record.sequence.chars().find(|&c| !is_valid_symbol(c)) {
+            return Err(InputError::InvalidResidue {
+                line: None,
+                id: seq_id(&record.header).to_string(),
+                residue,
+            });
+        }
+    }
+    Ok(())
+}
+
+// If known, `lines` contains the line number of each record.
+fn check_ids(records: &FastaFile, lines: Option<&[usize]>) -> Result<(), InputError> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    for (i, record) in records.iter().enumerate() {
+        let id = seq_id(&record.header);
+        if !seen.insert(id) {
+            return Err(InputError::DuplicateId {
+                line: lines.map(|l| l[i]),
+                id: id.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+// Lengths are in bytes, which are the same as characters once residues have been checked.
+fn check_lengths(records: &FastaFile, lines: Option<&[usize]>) -> Result<(), InputError> {
+    let Some(first) = records.first() else {
+        return Ok(());
+    };
+    let expected = first.sequence.len();
+    for (i, record) in records.iter().enumerate() {
+        if record.sequence.len() != expected {
+            return Err(InputError::UnequalLength {
+                line: lines.map(|l| l[i]),
+                id: record.header.clone(),
+                expected,
+                found: record.sequence.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn write_fasta<W: Write>(mut writer: W, records: &FastaFile) -> std::io::Result<()> {
+    for record in records {
+        writeln!(writer, ">{}", record.header)?;
+        for chunk in &record.sequence.chars().chunks(LINE_WIDTH) {
+            writeln!(writer, "{}", chunk.collect::<String>())?;
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -67,5 +186,85 @@ mod tests {
         assert_eq!(fasta[0].header, "Some larger FastA record, with several lines");
         assert_eq!(fasta[0].sequence, "HWYQYDSWSWHQIQDPWVASLMTGSEHNTTIVDLNVLGAMDCLWLCYCQPECFEVFSLCIEVDLPSCCWAKALCAFHMWDSMAKQCWMPEMGEVSYFYALSMFHYFLLHSRPIQPWQTHHIPYDSIVVDLIANYFYNMIVQDVDKNSNIRFDRSVMRDVMIYEFENTYATGVVFNVNGKCGQFCKNMIYVGTIETQKEYEMFKNLDCAVQKRHNLQPNCENIAMKMRIQYNGKRFRMDYWERYRCNDIKQVLPQPFTEVAMEHRTFKLWPTTRLMMSNPKCRQCLEWAAVETGWIFTTNF");
     }
-}
 
+    #[test]
+    fn test_read_fasta_file_compressed() {
+        for path in ["data/test2.fas.gz", "data/test2.fas.zst"] {
+            let fasta: FastaFile = read_fasta_file(path).expect("Test file not found");
+            assert_eq!(fasta.len(), 3);
+            assert_eq!(fasta[0].sequence, "TTGCCG-CGA");
+            assert_eq!(fasta[2].sequence, "TTACCG-CAA");
+        }
+    }
+
+    #[test]
+    fn test_read_fasta_empty() {
+        assert!(matches!(read_fasta("".as_bytes()), Err(InputError::Empty)));
+        assert!(matches!(read_fasta("\n\n".as_bytes()), Err(InputError::Empty)));
+        // Zero columns
+        assert!(matches!(read_fasta(">s1\n\n>s2\n\n".as_bytes()), Err(InputError::Empty)));
+        let records = vec![FastaRecord { header: "s".into(), sequence: String::new() }];
+        assert!(matches!(check_records(&records), Err(InputError::Empty)));
+    }
+
+    #[test]
+    fn test_read_fasta_sequence_before_header() {
+        let text = "\nACGT\n>seq1\nACGT\n";
+        let err = read_fasta(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, InputError::SequenceBeforeHeader { line: 2 }));
+        // Comments are fine, though.
+        assert!(read_fasta("#A3M#\n>seq1\nACGT\n".as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_read_fasta_duplicate_id() {
+        let text = ">seq1 first\nACGT\n>seq2\nACGT\n>seq1 second\nACGT\n";
+        let err = read_fasta(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, InputError::DuplicateId { line: Some(5), ref id } if id == "seq1"));
+        assert_eq!("line 5: duplicate sequence ID 'seq1'", err.to_string());
+    }
+
+    #[test]
+    fn test_read_fasta_unequal_length() {
+        let text = ">seq1\nACGT\n>seq2\nAC\nG\n";
+        let err = read_fasta(text.as_bytes()).err().unwrap();
+        assert!(matches!(
+            err,
+            InputError::UnequalLength { line: Some(3), expected: 4, found: 3, .. }
+        ));
+    }
+
+    #[test]
+    fn test_read_fasta_residues() {
+        // Stops are accepted...
+        let text = ">seq1\nACDE*\n>seq2\nACD-*\n";
+        assert_eq!(read_fasta(text.as_bytes()).unwrap()[1].sequence, "ACD-*");
+        // ...and so is trailing whitespace (which is dropped).
+        let text = ">seq1\nACGT \n>seq2\nAC\t\nGT\r\n";
+        assert_eq!(read_fasta(text.as_bytes()).unwrap()[1].sequence, "ACGT");
+        // ...but not digits, nor non-ASCII characters (which would also make lengths disagree).
+        let text = ">seq1 desc\nACDEF\n>seq2\nAC\nD1F\n";
+        let err = read_fasta(text.as_bytes()).err().unwrap();
+        assert!(matches!(
+            err,
+            InputError::InvalidResidue { line: Some(5), ref id, residue: '1' } if id == "seq2"
+        ));
+        assert_eq!(
+            "line 5: sequence 'seq2' contains '1', which is neither a residue nor a gap",
+            err.to_string()
+        );
+        let text = ">seq1\nACDEF\n>seq2\nACDé\n";
+        let err = read_fasta(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, InputError::InvalidResidue { line: Some(4), residue: 'é', .. }));
+        let records = vec![FastaRecord { header: "s".into(), sequence: "Aé".into() }];
+        let err = check_records(&records).err().unwrap();
+        assert!(matches!(err, InputError::InvalidResidue { line: None, residue: 'é', .. }));
+    }
+
+    #[test]
+    fn test_read_fasta_not_utf8() {
+        let text: &[u8] = b">seq1\nACGT\n>seq2\nAC\xe9T\n";
+        let err = read_fasta(text).err().unwrap();
+        assert!(matches!(err, InputError::NotUtf8 { line: 4 }));
+    }
+}
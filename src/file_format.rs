@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+use std::fmt;
+use std::io::{BufRead, Cursor, Error, Read};
+use std::path::Path;
+
+use crate::clustal::is_clustal_header;
+use crate::phylip::parse_phylip_header;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FileFormat {
+    FastA,
+    Stockholm,
+    Clustal,
+    Phylip,
+    Nexus,
+    // These two cannot be told from FastA by the first line; App::new() looks at the sequences.
+    A2M,
+    A3M,
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            FileFormat::FastA => "FastA",
+            FileFormat::Stockholm => "Stockholm",
+            FileFormat::Clustal => "Clustal",
+            FileFormat::Phylip => "PHYLIP",
+            FileFormat::Nexus => "NEXUS",
+            FileFormat::A2M => "A2M",
+            FileFormat::A3M => "A3M",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// Guesses the format from the first non-blank line of the input - file names are not looked at,
+// since aligners and users are not very consistent in that respect (e.g. '.aln' is used for
+// Clustal as well as FastA alignments). Anything that is not recognized is assumed to be FastA,
+// and is left to the FastA reader to accept or reject.
+// The input is not rewound (it may be a pipe), so the lines read here are handed back in front of
+// the rest, as a reader that yields the whole input.
+pub fn sniff_format<R: BufRead>(mut reader: R) -> Result<(FileFormat, impl BufRead), Error> {
+    let mut head: Vec<u8> = Vec::new();
+    let mut format = FileFormat::FastA;
+    loop {
+        let start = head.len();
+        if reader.read_until(b'\n', &mut head)? == 0 {
+            break;
+        }
+        let line = String::from_utf8_lossy(&head[start..]);
+        if !line.trim().is_empty() {
+            format = format_from_first_line(line.trim_end_matches(['\n', '\r']));
+            break;
+        }
+    }
+    Ok((format, Cursor::new(head).chain(reader)))
+}
+
+// For writing, the format can only come from the file name. Anything unknown is written as FastA.
+pub fn format_from_extension<P: AsRef<Path>>(path: P) -> FileFormat {
+    let ext = path
+        .as_ref()
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("sto") | Some("sth") | Some("stk") | Some("stockholm") => FileFormat::Stockholm,
+        Some("aln") | Some("clustal") | Some("clw") => FileFormat::Clustal,
+        Some("phy") | Some("phylip") => FileFormat::Phylip,
+        Some("nex") | Some("nexus") | Some("nxs") => FileFormat::Nexus,
+        Some("a2m") => FileFormat::A2M,
+        Some("a3m") => FileFormat::A3M,
+        _ => FileFormat::FastA,
+    }
+}
+
+fn format_from_first_line(line: &str) -> FileFormat {
+    if line.starts_with("# STOCKHOLM") {
+        FileFormat::Stockholm
+    } else if is_clustal_header(line) {
+        FileFormat::Clustal
+    } else if line.trim_start().to_ascii_uppercase().starts_with("#NEXUS") {
+        FileFormat::Nexus
+    } else if parse_phylip_header(line).is_some() {
+        FileFormat::Phylip
+    } else {
+        FileFormat::FastA
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::open_input;
+
+    fn sniff(path: &str) -> FileFormat {
+        sniff_format(open_input(path).unwrap()).unwrap().0
+    }
+
+    #[test]
+    fn test_sniff_format() {
+        assert_eq!(FileFormat::FastA, sniff("data/test2.fas"));
+        // Despite the extension...
+        assert_eq!(FileFormat::FastA, sniff("data/test4.aln"));
+        assert_eq!(FileFormat::Clustal, sniff("data/test-clustal.aln"));
+        assert_eq!(FileFormat::Stockholm, sniff("data/test1.sto"));
+        assert_eq!(FileFormat::Phylip, sniff("data/test-int.phy"));
+        assert_eq!(FileFormat::Nexus, sniff("data/test2.nex"));
+    }
+
+    #[test]
+    fn test_sniff_format_compressed() {
+        assert_eq!(FileFormat::FastA, sniff("data/test2.fas.gz"));
+        assert_eq!(FileFormat::Clustal, sniff("data/test-clustal.aln.bgz"));
+        assert_eq!(FileFormat::Stockholm, sniff("data/test1.sto.gz"));
+        assert_eq!(FileFormat::Nexus, sniff("data/test2.nex.zst"));
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(FileFormat::FastA, format_from_extension("out.fa"));
+        assert_eq!(FileFormat::FastA, format_from_extension("out"));
+        assert_eq!(FileFormat::Stockholm, format_from_extension("out.STO"));
+        assert_eq!(FileFormat::Stockholm, format_from_extension("out.sth"));
+        assert_eq!(FileFormat::Clustal, format_from_extension("dir.d/out.aln"));
+        assert_eq!(FileFormat::Phylip, format_from_extension("out.phy"));
+        assert_eq!(FileFormat::Nexus, format_from_extension("out.nex"));
+        assert_eq!(FileFormat::A3M, format_from_extension("out.a3m"));
+    }
+
+    #[test]
+    fn test_sniff_format_keeps_input() {
+        let text = "\n\n>seq1\nACGT\n";
+        let (format, mut reader) = sniff_format(text.as_bytes()).unwrap();
+        assert_eq!(FileFormat::FastA, format);
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(text, rest);
+    }
+}
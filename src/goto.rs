@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Targets of the go-to command (':'). All numbers are 1-based, as shown on screen:
+ *
+ *   1830         alignment column 1830
+ *   #12          sequence 12 (its number in the label pane)
+ *   P12345:245   residue 245 (ungapped) of the sequence whose ID is P12345
+ *   #12:245      residue 245 of sequence 12
+ *
+ * A sequence ID is the first word of the header, or any of its '|'-separated fields (so that
+ * P12345 finds "sp|P12345|ABC_HUMAN"). */
+
+use crate::alignment::{is_gap, Alignment};
+use crate::fasta::seq_id;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SeqRef {
+    Number(usize),
+    Id(String),
+}
+
+// Numbers are 0-based from here on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Column(usize),
+    Sequence(SeqRef),
+    Residue(SeqRef, usize),
+}
+
+// Parses a 1-based number, returning it 0-based.
+fn parse_number(s: &str, what: &str) -> Result<usize, String> {
+    match s.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("bad {} '{}'", what, s.trim())),
+    }
+}
+
+pub fn parse_seq_ref(s: &str) -> Result<SeqRef, String> {
+    let s = s.trim();
+    match s.strip_prefix('#') {
+        Some(num) => Ok(SeqRef::Number(parse_number(num, "sequence number")?)),
+        None if s.is_empty() => Err("missing sequence ID".to_string()),
+        None => Ok(SeqRef::Id(s.to_string())),
+    }
+}
+
+pub fn parse_target(input: &str) -> Result<Target, String> {
+    let input = input.trim();
+    // IDs may themselves contain ':', so the position is whatever follows the last one.
+    if let Some((seq, pos)) = input.rsplit_once(':') {
+        return Ok(Target::Residue(
+            parse_seq_ref(seq)?,
+            parse_number(pos, "position")?,
+        ));
+    }
+    if input.starts_with('#') {
+        return Ok(Target::Sequence(parse_seq_ref(input)?));
+    }
+    Ok(Target::Column(parse_number(input, "column")?))
+}
+
+pub fn id_matches(header: &str, id: &str) -> bool {
+    let first = seq_id(header);
+    first == id || first.split('|').any(|field| field == id)
+}
+
+// Index of the referred sequence in the alignment.
+pub fn resolve_seq_ref(aln: &Alignment, seq_ref: &SeqRef) -> Result<usize, String> {
+    match seq_ref {
+        SeqRef::Number(n) if *n < aln.num_seq() => Ok(*n),
+        SeqRef::Number(n) => Err(format!(
+            "no sequence #{} (there are {})",
+            n + 1,
+            aln.num_seq()
+        )),
+        SeqRef::Id(id) => aln
+            .headers
+            .iter()
+            .position(|h| id_matches(h, id))
+            .ok_or_else(|| format!("no sequence with ID '{}'", id)),
+    }
+}
+
+// The alignment column of the pos-th (0-based) residue of a sequence, if it has that many.
+pub fn residue_column(sequence: &str, pos: usize) -> Option<usize> {
+    sequence
+        .chars()
+        .enumerate()
+        .filter(|(_, c)| !is_gap(*c))
+        .nth(pos)
+        .map(|(col, _)| col)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fasta::FastaRecord;
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(parse_target("1830"), Ok(Target::Column(1829)));
+        assert_eq!(
+            parse_target(" #12 "),
+            Ok(Target::Sequence(SeqRef::Number(11)))
+        );
+        assert_eq!(
+            parse_target("P12345:245"),
+            Ok(Target::Residue(SeqRef::Id("P12345".to_string()), 244))
+        );
+        assert_eq!(
+            parse_target("#3:1"),
+            Ok(Target::Residue(SeqRef::Number(2), 0))
+        );
+        assert!(parse_target("0").is_err());
+        assert!(parse_target("abc").is_err());
+        assert!(parse_target(":5").is_err());
+    }
+
+    #[test]
+    fn test_resolve_and_residue_column() {
+        let aln = Alignment::new(vec![
+            FastaRecord {
+                header: "sp|P12345|ABC_HUMAN Some protein".to_string(),
+                sequence: "--MK-LV".to_string(),
+            },
+            FastaRecord {
+                header: "seq2".to_string(),
+                sequence: "MAKELV-".to_string(),
+            },
+        ]);
+        let p12345 = SeqRef::Id("P12345".to_string());
+        assert_eq!(resolve_seq_ref(&aln, &p12345), Ok(0));
+        assert_eq!(resolve_seq_ref(&aln, &SeqRef::Id("seq2".to_string())), Ok(1));
+        assert!(resolve_seq_ref(&aln, &SeqRef::Number(2)).is_err());
+        assert_eq!(residue_column(&aln.sequences[0], 2), Some(5));
+        assert_eq!(residue_column(&aln.sequences[0], 4), None);
+    }
+}
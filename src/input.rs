@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Opening of input files. Compressed files are recognized by their magic bytes (not their
+ * extension) and decompressed on the fly, so that all the readers (FastA, Stockholm, etc.) get
+ * plain text without having to know about compression. The input is read in a single pass, so it
+ * can also come from standard input or a named pipe. */
+
+use std::fs::File;
+use std::io::{stdin, BufRead, BufReader, Cursor, Error, ErrorKind, Read};
+use std::path::Path;
+
+use flate2::bufread::MultiGzDecoder;
+use ruzstd::decoding::StreamingDecoder;
+
+use crate::errors::InputError;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const MAGIC_LEN: usize = 4;
+
+// Passing this as the file name means reading from standard input.
+pub const STDIN_PATH: &str = "-";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    // Also covers BGZF (bgzip), which is a series of gzip members.
+    Gzip,
+    Zstd,
+}
+
+pub fn compression_from_magic(bytes: &[u8]) -> Compression {
+    if bytes.starts_with(GZIP_MAGIC) {
+        Compression::Gzip
+    } else if bytes.starts_with(ZSTD_MAGIC) {
+        Compression::Zstd
+    } else {
+        Compression::None
+    }
+}
+
+pub fn open_input<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>, Error> {
+    if path.as_ref() == Path::new(STDIN_PATH) {
+        decompress(stdin().lock())
+    } else {
+        let file = File::open(path)?;
+        decompress(BufReader::new(file))
+    }
+}
+
+// Wraps the reader into the appropriate decoder, if any. The magic bytes are put back in front of
+// the rest of the input, so the decoder (or the reader, for uncompressed input) sees all of it.
+pub fn decompress<R: BufRead + 'static>(mut reader: R) -> Result<Box<dyn BufRead>, Error> {
+    // A pipe may deliver fewer bytes than asked for, hence the loop.
+    let mut magic = Vec::with_capacity(MAGIC_LEN);
+    while magic.len() < MAGIC_LEN {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        let n = buf.len().min(MAGIC_LEN - magic.len());
+        magic.extend_from_slice(&buf[..n]);
+        reader.consume(n);
+    }
+    let compression = compression_from_magic(&magic);
+    let reader = Cursor::new(magic).chain(reader);
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => {
+            let decoder = StreamingDecoder::new(reader)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("zstd: {}", e)))?;
+            Box::new(BufReader::new(decoder))
+        }
+    })
+}
+
+// Like BufRead::lines(), but also yields the line numbers (starting at 1), and reports invalid
+// UTF-8 as such (BufRead::lines() only says "invalid data", without saying where).
+pub fn numbered_lines<R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<(usize, String), InputError>> {
+    reader.split(b'\n').enumerate().map(|(i, bytes)| {
+        let line_num = i + 1;
+        let mut bytes = bytes?;
+        if bytes.last() == Some(&b'\r') {
+            bytes.pop();
+        }
+        String::from_utf8(bytes)
+            .map(|l| (line_num, l))
+            .map_err(|_| InputError::NotUtf8 { line: line_num })
+    })
+}
+
+// Reads the whole input as text, for formats that are not line-oriented (NEXUS).
+pub fn read_text<R: BufRead>(mut reader: R) -> Result<String, InputError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| {
+        let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+        InputError::NotUtf8 {
+            line: valid.iter().filter(|&&b| b == b'\n').count() + 1,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_to_string<P: AsRef<Path>>(path: P) -> String {
+        let mut text = String::new();
+        open_input(path).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_compression_from_magic() {
+        assert_eq!(Compression::Gzip, compression_from_magic(&[0x1f, 0x8b, 0x08]));
+        assert_eq!(Compression::Zstd, compression_from_magic(&[0x28, 0xb5, 0x2f, 0xfd]));
+        assert_eq!(Compression::None, compression_from_magic(b">seq1"));
+        assert_eq!(Compression::None, compression_from_magic(b""));
+    }
+
+    #[test]
+    fn test_open_input_compressed() {
+        let plain = read_to_string("data/test2.fas");
+        assert_eq!(plain, read_to_string("data/test2.fas.gz"));
+        assert_eq!(plain, read_to_string("data/test2.fas.zst"));
+    }
+
+    #[test]
+    fn test_open_input_multi_member_gzip() {
+        // Like bgzip output, this consists of several concatenated gzip members.
+        let plain = read_to_string("data/test-clustal.aln");
+        assert_eq!(plain, read_to_string("data/test-clustal.aln.bgz"));
+    }
+
+    // Delivers its contents one byte at a time, like a slow pipe.
+    struct Trickle(Vec<u8>, usize);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.1 == self.0.len() || buf.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[self.1];
+            self.1 += 1;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_decompress_short_reads() {
+        let compressed = std::fs::read("data/test2.fas.zst").unwrap();
+        let reader = BufReader::with_capacity(1, Trickle(compressed, 0));
+        let mut text = String::new();
+        decompress(reader).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(read_to_string("data/test2.fas"), text);
+    }
+
+    #[test]
+    fn test_numbered_lines() {
+        let text: &[u8] = b"one\r\ntwo\n\xff\xfe\nfour\n";
+        let lines: Vec<_> = numbered_lines(text).collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0].as_ref().unwrap(), &(1, "one".to_string()));
+        assert_eq!(lines[1].as_ref().unwrap(), &(2, "two".to_string()));
+        assert!(matches!(lines[2], Err(InputError::NotUtf8 { line: 3 })));
+    }
+
+    #[test]
+    fn test_read_text_not_utf8() {
+        let text: &[u8] = b"#NEXUS\nbegin data;\n\xff\n";
+        assert!(matches!(read_text(text), Err(InputError::NotUtf8 { line: 3 })));
+    }
+}
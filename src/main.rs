@@ -5,39 +5,76 @@ mod app;
 mod ui;
 mod vec_f64_aux;
 mod fasta;
+mod a3m;
+mod stockholm;
+mod clustal;
+mod phylip;
+mod nexus;
+mod file_format;
+mod input;
+mod output;
+mod errors;
+mod motif;
+mod goto;
+mod config;
+mod tree;
+mod newick;
 
 use log::{debug, info};
 
-use std::io::{stdout, Result};
-//use std::process::exit;
+use std::io::{stdin, stdout, IsTerminal, Result, Stdout, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process::exit;
 
-use clap::{arg, command, Parser};
+use clap::{ArgGroup, Parser};
 
 use crossterm::{
-    event::{self, KeyEventKind},
+    event::{self, DisableMouseCapture, EnableMouseCapture, KeyEvent, KeyEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 
 use ratatui::{
+    backend::TestBackend,
     prelude::{CrosstermBackend, Rect, Terminal},
     TerminalOptions, Viewport,
 };
 
-use crate::app::App;
+use crate::alignment::{
+    pairwise::{GapHandling, Measure, GAP_HANDLINGS},
+    SeqType,
+};
+use crate::app::{App, TreeMatch};
+use crate::config::{default_colormap_dir, Config};
+use crate::fasta::seq_id;
+use crate::input::STDIN_PATH;
+use crate::output::create_output;
+use crate::newick::read_newick_file;
 use crate::ui::{
-    color_map::colormap_gecos,
-    key_handling::handle_key_press,
+    color_map::{colormap_gecos, gecos_colormaps, ColorMap},
+    ansi::buffer_to_ansi,
+    background::detect_theme,
+    color_scheme::{Theme, THEMES},
+    key_handling::{handle_key_press, parse_keys},
+    keymap::Keymap,
+    mouse_handling::handle_mouse_event,
     render::render_ui,
     {ZoomLevel, UI},
 };
 
 #[derive(Debug, Parser)]
 #[command(version, about, long_about = None) ]
+#[command(group(ArgGroup::new("output")
+    .args(["export", "figure", "identity_matrix", "distance_matrix"])))]
 struct Cli {
-    /// Alignment file
+    /// Alignment file ('-' for standard input, which is also the default if it is not a terminal)
     aln_fname: Option<String>,
 
+    /// Read the configuration from FILE (default: $XDG_CONFIG_HOME/termal/config.toml)
+    #[arg(long = "config", value_name = "FILE")]
+    config: Option<String>,
+
     /// Show key bindings and exit successfully
     #[arg(short = 'b', long = "show-bindings")]
     show_bindings: bool,
@@ -46,10 +83,14 @@ struct Cli {
     #[arg(short, long)]
     info: bool,
 
-    /// Gecos color map
+    /// Gecos color map (made the current one)
     #[arg(short, long = "color-map")]
     color_map: Option<String>,
 
+    /// Load all Gecos color maps (*.json) in DIR (default: $XDG_CONFIG_HOME/termal/colormaps)
+    #[arg(long = "colormap-dir", value_name = "DIR")]
+    colormap_dir: Option<String>,
+
     /// Fixed terminal width (mostly used for testing/debugging)
     #[arg(short, long, requires = "height")]
     width: Option<u16>,
@@ -59,86 +100,433 @@ struct Cli {
     height: Option<u16>,
 
     /// Start with labels pane hidden
-    #[arg(short = 'L', long)]
+    #[arg(short = 'L', long, overrides_with = "show_labels_pane")]
     hide_labels_pane: bool,
 
+    /// Undo --hide-labels-pane (e.g. when set in the configuration file)
+    #[arg(long = "show-labels-pane", overrides_with = "hide_labels_pane")]
+    show_labels_pane: bool,
+
     /// Start with bottom pane hidden
-    #[arg(short = 'B', long)]
+    #[arg(short = 'B', long, overrides_with = "show_bottom_pane")]
     hide_bottom_pane: bool,
 
+    /// Undo --hide-bottom-pane (e.g. when set in the configuration file)
+    #[arg(long = "show-bottom-pane", overrides_with = "hide_bottom_pane")]
+    show_bottom_pane: bool,
+
     /// (Currently no effect)
-    #[arg(short = 'D', long)]
+    #[arg(short = 'D', long, overrides_with = "no_debug")]
     debug: bool,
 
+    /// Undo --debug (e.g. when set in the configuration file)
+    #[arg(long = "no-debug", overrides_with = "debug")]
+    no_debug: bool,
+
     /// Disable color
-    #[arg(short = 'C', long = "no-color")]
+    #[arg(short = 'C', long = "no-color", overrides_with = "color")]
     no_color: bool,
 
+    /// Undo --no-color (e.g. when set in the configuration file)
+    #[arg(long = "color", overrides_with = "no_color")]
+    color: bool,
+
+    /// Color theme: dark, light or mono (default: guessed from the terminal's background, or dark)
+    #[arg(long = "theme", value_name = "THEME", value_parser = parse_theme)]
+    theme: Option<Theme>,
+
+    /// Do not capture the mouse (e.g. to select text with it)
+    #[arg(long = "no-mouse", overrides_with = "mouse")]
+    no_mouse: bool,
+
+    /// Undo --no-mouse (e.g. when set in the configuration file)
+    #[arg(long = "mouse", overrides_with = "no_mouse")]
+    mouse: bool,
+
     /// Disable scrollbars (mostly for testing)
-    #[arg(long = "no-scrollbars")]
+    #[arg(long = "no-scrollbars", overrides_with = "scrollbars")]
     no_scrollbars: bool,
 
-    /// Poll wait time [ms]
-    #[clap(long = "poll-wait-time", default_value_t = 100)]
-    poll_wait_time: u64,
+    /// Undo --no-scrollbars (e.g. when set in the configuration file)
+    #[arg(long = "scrollbars", overrides_with = "no_scrollbars")]
+    scrollbars: bool,
+
+    /// Poll wait time [ms] [default: 100]
+    #[clap(long = "poll-wait-time")]
+    poll_wait_time: Option<u64>,
 
     /// Panic (for testing)
     #[clap(long = "panic")]
     panic: bool,
 
     /// Do not show zoom box (zooming itself is not disabled)
-    #[arg(long = "no-zoom-box")]
+    #[arg(long = "no-zoom-box", overrides_with = "zoombox")]
     no_zoombox: bool,
 
+    /// Undo --no-zoom-box (e.g. when set in the configuration file)
+    #[arg(long = "zoom-box", overrides_with = "no_zoombox")]
+    zoombox: bool,
+
     /// Do not show zoom box guides (only useful if zoom box not shown)
-    #[arg(long = "no-zb-guides")]
+    #[arg(long = "no-zb-guides", overrides_with = "zb_guides")]
     no_zb_guides: bool,
+
+    /// Undo --no-zb-guides (e.g. when set in the configuration file)
+    #[arg(long = "zb-guides", overrides_with = "no_zb_guides")]
+    zb_guides: bool,
+
+    /// Start with insert columns hidden (A2M/A3M only)
+    #[arg(short = 'I', long = "hide-insert-columns", overrides_with = "show_insert_columns")]
+    hide_insert_columns: bool,
+
+    /// Undo --hide-insert-columns (e.g. when set in the configuration file)
+    #[arg(long = "show-insert-columns", overrides_with = "hide_insert_columns")]
+    show_insert_columns: bool,
+
+    /// Character shown for residues identical to the reference sequence (see 'R') [default: .]
+    #[arg(long = "match-char", value_name = "C")]
+    match_char: Option<char>,
+
+    /// Write the alignment to FILE and exit (no TUI); the format is given by the extension
+    /// (FastA if unknown, '-' for standard output)
+    #[arg(long = "export", value_name = "FILE")]
+    export: Option<String>,
+
+    /// Write a picture of the alignment to FILE and exit (no TUI): HTML if the extension is
+    /// .html or .htm, SVG otherwise ('-' for standard output)
+    #[arg(long = "figure", value_name = "FILE")]
+    figure: Option<String>,
+
+    /// Write the pairwise identities of the sequences to FILE as TSV and exit (no TUI); '-' for
+    /// standard output
+    #[arg(long = "identity-matrix", value_name = "FILE")]
+    identity_matrix: Option<String>,
+
+    /// Same as --identity-matrix, but with p-distances (1 - identity)
+    #[arg(long = "distance-matrix", value_name = "FILE")]
+    distance_matrix: Option<String>,
+
+    /// Pairwise identity: ignore-gap-pairs (skip columns where both sequences have a gap) or
+    /// mismatch (count all gaps as mismatches) [default: ignore-gap-pairs]
+    #[arg(long = "gaps", value_name = "MODE", value_parser = parse_gap_handling)]
+    gaps: Option<GapHandling>,
+
+    /// Order the sequences by the Newick tree in FILE, whose leaves are matched to the sequence
+    /// IDs, and draw it next to the labels
+    #[arg(long = "tree", value_name = "FILE")]
+    tree: Option<String>,
+
+    /// Only export, draw or compare these columns (1-based and inclusive, e.g. 10-50)
+    #[arg(long = "columns", value_name = "FROM-TO", requires = "output",
+          value_parser = parse_column_range)]
+    columns: Option<Range<usize>>,
+
+    /// Only draw these sequences, in the order shown (1-based and inclusive, e.g. 1-20)
+    #[arg(long = "rows", value_name = "FROM-TO", requires = "figure",
+          value_parser = parse_column_range)]
+    rows: Option<Range<usize>>,
+
+    /// Render a single frame (of size --width x --height, default 80x24) to FILE as text with
+    /// ANSI colors, and exit (no TUI); '-' for standard output
+    #[arg(long = "render-once", value_name = "FILE")]
+    render_once: Option<String>,
+
+    /// Keys to press before rendering, e.g. 'zzjj<Right>' (see the manual for <...> names)
+    #[arg(long = "keys", value_name = "KEYS", requires = "render_once")]
+    keys: Option<String>,
+}
+
+// Options that are not on the command line are taken from the configuration file, if it has them.
+// Flags set in the file can be unset on the command line with their negation (e.g. --color).
+fn apply_config(cli: &mut Cli, config: &Config) -> std::result::Result<(), String> {
+    let flag = |name, given: bool, negated: bool| -> std::result::Result<bool, String> {
+        Ok(given || (!negated && config.flag(name)?))
+    };
+    if cli.color_map.is_none() {
+        cli.color_map = config.string("color-map")?.map(String::from);
+    }
+    if cli.colormap_dir.is_none() {
+        cli.colormap_dir = config.string("colormap-dir")?.map(String::from);
+    }
+    cli.hide_labels_pane = flag("hide-labels-pane", cli.hide_labels_pane, cli.show_labels_pane)?;
+    cli.hide_bottom_pane = flag("hide-bottom-pane", cli.hide_bottom_pane, cli.show_bottom_pane)?;
+    cli.debug = flag("debug", cli.debug, cli.no_debug)?;
+    cli.no_color = flag("no-color", cli.no_color, cli.color)?;
+    if cli.theme.is_none() {
+        cli.theme = config.theme()?;
+    }
+    cli.no_mouse = flag("no-mouse", cli.no_mouse, cli.mouse)?;
+    cli.no_scrollbars = flag("no-scrollbars", cli.no_scrollbars, cli.scrollbars)?;
+    if cli.poll_wait_time.is_none() {
+        cli.poll_wait_time = config.number("poll-wait-time")?;
+    }
+    cli.no_zoombox = flag("no-zoom-box", cli.no_zoombox, cli.zoombox)?;
+    cli.no_zb_guides = flag("no-zb-guides", cli.no_zb_guides, cli.zb_guides)?;
+    cli.hide_insert_columns = flag("hide-insert-columns", cli.hide_insert_columns, cli.show_insert_columns)?;
+    if cli.match_char.is_none() {
+        cli.match_char = config.character("match-char")?;
+    }
+    if cli.gaps.is_none() {
+        cli.gaps = config.gap_handling()?;
+    }
+    Ok(())
+}
+
+// The color maps of the colormap directory, then the one given with -c, if any (the current one,
+// see below). The default directory need not exist.
+fn custom_colormaps(cli: &Cli) -> std::result::Result<Vec<ColorMap>, String> {
+    let mut colormaps = match &cli.colormap_dir {
+        Some(dir) => gecos_colormaps(Path::new(dir))?,
+        None => match default_colormap_dir().filter(|dir| dir.is_dir()) {
+            Some(dir) => gecos_colormaps(&dir)?,
+            None => Vec::new(),
+        },
+    };
+    if let Some(path) = &cli.color_map {
+        let mut colormap =
+            colormap_gecos(Path::new(path)).map_err(|e| format!("{}: {}", path, e))?;
+        // It is used whatever the sequences, and replaces any map of the same name.
+        colormap.seq_types = vec![SeqType::Nucleic, SeqType::Protein];
+        colormaps.retain(|cm| cm.name != colormap.name);
+        colormaps.push(colormap);
+    }
+    Ok(colormaps)
+}
+
+fn parse_theme(s: &str) -> std::result::Result<Theme, String> {
+    parse_choice(s, THEMES)
+}
+
+fn parse_gap_handling(s: &str) -> std::result::Result<GapHandling, String> {
+    parse_choice(s, GAP_HANDLINGS)
+}
+
+fn parse_choice<T: Copy>(s: &str, choices: &[(&str, T)]) -> std::result::Result<T, String> {
+    match choices.iter().find(|(name, _)| *name == s) {
+        Some((_, choice)) => Ok(*choice),
+        None => {
+            let names: Vec<&str> = choices.iter().map(|(name, _)| *name).collect();
+            Err(format!("expected one of {}", names.join(", ")))
+        }
+    }
+}
+
+// Turns a 1-based, inclusive range like "10-50" into a (0-based, half-open) Range. Used for rows
+// as well as columns.
+fn parse_column_range(s: &str) -> std::result::Result<Range<usize>, String> {
+    let (from, to) = s
+        .split_once('-')
+        .ok_or_else(|| format!("expected FROM-TO, got '{}'", s))?;
+    let from: usize = from.trim().parse().map_err(|_| format!("bad column '{}'", from))?;
+    let to: usize = to.trim().parse().map_err(|_| format!("bad column '{}'", to))?;
+    if from == 0 || from > to {
+        return Err(format!("bad column range '{}'", s));
+    }
+    Ok(from - 1..to)
+}
+
+// At most this many leaves (resp. sequences) are named when reporting those that do not match.
+const MAX_UNMATCHED_LISTED: usize = 10;
+
+fn name_list<S: AsRef<str>>(names: &[S]) -> String {
+    let mut list: Vec<&str> = names
+        .iter()
+        .take(MAX_UNMATCHED_LISTED)
+        .map(|name| match name.as_ref() {
+            "" => "(unnamed)",
+            name => name,
+        })
+        .collect();
+    if names.len() > MAX_UNMATCHED_LISTED {
+        list.push("...");
+    }
+    list.join(", ")
+}
+
+fn count(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+// Lists the leaves and sequences that were not matched on stderr, and returns a summary for the
+// message line.
+fn report_tree_match(path: &str, app: &App, tree_match: &TreeMatch) -> String {
+    let TreeMatch {
+        unmatched_leaves,
+        unmatched_seqs,
+    } = tree_match;
+    if !unmatched_leaves.is_empty() {
+        eprintln!(
+            "termal: {}: {} no sequence (or one matched by another leaf): {}",
+            path,
+            count(unmatched_leaves.len(), "leaf matches", "leaves match"),
+            name_list(unmatched_leaves)
+        );
+    }
+    if !unmatched_seqs.is_empty() {
+        let ids: Vec<&str> = unmatched_seqs
+            .iter()
+            .map(|&seq| seq_id(&app.alignment.headers[seq]))
+            .collect();
+        eprintln!(
+            "termal: {}: {} not in the tree (shown last): {}",
+            path,
+            count(ids.len(), "sequence is", "sequences are"),
+            name_list(&ids)
+        );
+    }
+    let name = Path::new(path).file_name().map_or(path.into(), |name| name.to_string_lossy());
+    let num_seq = app.alignment.num_seq();
+    let mut message = format!(
+        " {}: {} of {} sequences in the tree",
+        name,
+        num_seq - unmatched_seqs.len(),
+        num_seq
+    );
+    if !unmatched_leaves.is_empty() {
+        message += &format!(", {} unmatched", count(unmatched_leaves.len(), "leaf", "leaves"));
+    }
+    if !unmatched_leaves.is_empty() || !unmatched_seqs.is_empty() {
+        message += " (listed on exit)";
+    }
+    message + " "
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     info!("Starting log");
 
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
     if cli.panic {
         panic!("User-requested panic");
     }
 
+    // Bad configurations are reported even if no alignment is shown.
+    let (keymap, settings) = match Config::read(cli.config.as_deref()).and_then(|config| {
+        apply_config(&mut cli, &config)?;
+        Ok((Keymap::from_config(config.keys()?)?, config.settings()?))
+    }) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("termal: configuration: {}", e);
+            exit(1);
+        }
+    };
+
+    let colormaps = match custom_colormaps(&cli) {
+        Ok(colormaps) => colormaps,
+        Err(e) => {
+            eprintln!("termal: color map: {}", e);
+            exit(1);
+        }
+    };
+
     if cli.show_bindings {
-        println!("{}", include_str!("ui/bindings.md"));
+        print!("{}", keymap.help_text(true));
         return Ok(());
     }
 
-    if let Some(fasta_file) = &cli.aln_fname {
-        let mut app = App::new(fasta_file)?;
+    // Keys are then read from the terminal (crossterm falls back to /dev/tty when stdin is not one).
+    let aln_fname = cli
+        .aln_fname
+        .clone()
+        .or_else(|| (!stdin().is_terminal()).then(|| STDIN_PATH.to_string()));
+
+    if let Some(fasta_file) = &aln_fname {
+        let mut app = match App::new(fasta_file) {
+            Ok(app) => app,
+            Err(e) => {
+                let name = if fasta_file == STDIN_PATH { "<stdin>" } else { fasta_file };
+                eprintln!("termal: {}: {}", name, e);
+                exit(1);
+            }
+        };
+        if cli.hide_insert_columns {
+            app.toggle_insert_columns();
+        }
+        if let Some(gaps) = cli.gaps {
+            app.set_gap_handling(gaps);
+        }
+        // Before any output, which follows the tree's order
+        let mut tree_message = None;
+        if let Some(path) = &cli.tree {
+            match read_newick_file(path)
+                .map_err(|e| e.to_string())
+                .and_then(|newick| app.set_tree(&newick))
+            {
+                Ok(tree_match) => tree_message = Some(report_tree_match(path, &app, &tree_match)),
+                Err(e) => {
+                    eprintln!("termal: {}: {}", path, e);
+                    exit(1);
+                }
+            }
+        }
 
-            if cli.info {
-                info!("Running in debug mode.");
-                app.output_info();
+            if let Some(columns) = &cli.columns {
+                if columns.end > app.aln_len() as usize {
+                    eprintln!("termal: the alignment has only {} columns", app.aln_len());
+                    exit(1);
+                }
+            }
+            if let Some(rows) = &cli.rows {
+                if rows.end > app.num_seq() as usize {
+                    eprintln!("termal: the alignment has only {} sequences", app.num_seq());
+                    exit(1);
+                }
+            }
+
+            if let Some(path) = &cli.export {
+                if let Err(e) = app.export_to_file(path, None, cli.columns.clone()) {
+                    eprintln!("termal: {}: {}", path, e);
+                    exit(1);
+                }
                 return Ok(());
             }
 
-            stdout().execute(EnterAlternateScreen)?;
-            enable_raw_mode()?;
+            let pairwise = match (&cli.identity_matrix, &cli.distance_matrix) {
+                (Some(path), _) => Some((path, Measure::Identity)),
+                (_, Some(path)) => Some((path, Measure::PDistance)),
+                _ => None,
+            };
+            if let Some((path, measure)) = pairwise {
+                if let Err(e) = app.export_pairwise(path, measure, cli.columns.clone()) {
+                    eprintln!("termal: {}: {}", path, e);
+                    exit(1);
+                }
+                return Ok(());
+            }
 
-            let backend = CrosstermBackend::new(stdout());
-            let viewport: Viewport;
-            // Fix viewport dimensions IFF supplied (mainly for tests)
-            //
-            if let Some(width) = cli.width {
-                // height must be defined too (see 'requires' in struct Cli above)
-                let height = cli.height.unwrap();
-                viewport = Viewport::Fixed(Rect::new(0, 0, width, height));
-            } else {
-                viewport = Viewport::Fullscreen;
+            if cli.info {
+                info!("Running in debug mode.");
+                app.output_info();
+                return Ok(());
             }
-            let mut terminal = Terminal::with_options(backend, TerminalOptions { viewport })?;
-            terminal.clear()?;
 
+            let figure_rows = cli.rows.clone().unwrap_or(0..app.num_seq() as usize);
+            let figure_cols = cli.columns.clone().unwrap_or(0..app.aln_len() as usize);
             let mut app_ui = UI::new(&mut app);
+            app_ui.set_keymap(keymap);
+            // The map given with -c (the last one) overrides the settings.
+            let current_colormap = match cli.color_map {
+                Some(_) => colormaps.last().map(|cm| cm.name.clone()),
+                None => None,
+            };
+            for colormap in colormaps {
+                app_ui.add_colormap(colormap);
+            }
+            if let Err(e) = app_ui.apply_settings(&settings) {
+                eprintln!("termal: configuration: {}", e);
+                exit(1);
+            }
+            if let Some(name) = current_colormap {
+                // The map was just added, so this cannot fail.
+                let _ = app_ui.set_colormap(&name);
+            }
             if cli.no_scrollbars {
                 app_ui.disable_scrollbars();
             }
+            if let Some(theme) = cli.theme {
+                app_ui.set_theme(theme);
+            }
             if cli.no_color {
                 app_ui.set_monochrome();
             }
@@ -149,41 +537,155 @@ fn main() -> Result<()> {
                 app_ui.set_zoombox_guides(false);
             }
             if cli.hide_labels_pane {
-                app_ui.set_label_pane_width(0);
+                app_ui.hide_label_pane();
             }
             if cli.hide_bottom_pane {
                 app_ui.set_bottom_pane_height(0);
             }
+            app_ui.set_match_char(cli.match_char.unwrap_or('.'));
+            if let Some(message) = tree_message {
+                app_ui.set_message(message);
+            }
 
-            if let Some(path) = cli.color_map {
-                let _cmap = colormap_gecos(path.into());
-                // TODO: add to colormaps (and remove the underscore)
-            }
-
-            // main loop
-            loop {
-                debug!("\n**** Draw Iteration ****");
-                debug!("terminal size: {:?}", terminal.size().unwrap());
-                terminal.draw(|f| render_ui(f, &mut app_ui))?;
-                // handle events
-                if event::poll(std::time::Duration::from_millis(cli.poll_wait_time))? {
-                    if let event::Event::Key(key) = event::read()? {
-                        if key.kind == KeyEventKind::Press {
-                            // handle_key_press() returns true IFF user quits
-                            let done = handle_key_press(&mut app_ui, key);
-                            if done {
-                                break;
-                            }
-                        }
+            // Pictures reflect the display options (color, theme, etc.), so the UI is needed.
+            if let Some(path) = &cli.figure {
+                if let Err(e) = app_ui.write_figure_to_file(path, figure_rows, figure_cols) {
+                    eprintln!("termal: {}: {}", path, e);
+                    exit(1);
+                }
+                return Ok(());
+            }
+
+            if let Some(path) = &cli.render_once {
+                let size = (cli.width.unwrap_or(80), cli.height.unwrap_or(24));
+                let keys = match parse_keys(cli.keys.as_deref().unwrap_or("")) {
+                    Ok(keys) => keys,
+                    Err(e) => {
+                        eprintln!("termal: --keys: {}", e);
+                        exit(1);
                     }
+                };
+                let result = render_once(&mut app_ui, size, &keys).and_then(|frame| {
+                    let mut writer = create_output(path)?;
+                    writer.write_all(frame.as_bytes())?;
+                    writer.flush()
+                });
+                if let Err(e) = result {
+                    eprintln!("termal: {}: {}", path, e);
+                    exit(1);
                 }
+                return Ok(());
             }
 
-            stdout().execute(LeaveAlternateScreen)?;
-            disable_raw_mode()?;
+            // Panic messages would be unreadable (or even invisible) in raw mode and in the
+            // alternate screen.
+            let default_panic_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore_terminal();
+                default_panic_hook(info);
+            }));
 
-            Ok(())
+            stdout().execute(EnterAlternateScreen)?;
+            enable_raw_mode()?;
+            // Unless the theme was chosen, it follows the terminal's background (this needs raw
+            // mode).
+            if cli.theme.is_none() && !cli.no_color {
+                if let Some(theme) = detect_theme() {
+                    app_ui.set_theme(theme);
+                }
+            }
+            if !cli.no_mouse {
+                stdout().execute(EnableMouseCapture)?;
+            }
+
+            let backend = CrosstermBackend::new(stdout());
+            let viewport: Viewport;
+            // Fix viewport dimensions IFF supplied (mainly for tests)
+            //
+            if let Some(width) = cli.width {
+                // height must be defined too (see 'requires' in struct Cli above)
+                let height = cli.height.unwrap();
+                viewport = Viewport::Fixed(Rect::new(0, 0, width, height));
+            } else {
+                viewport = Viewport::Fullscreen;
+            }
+            let mut terminal = Terminal::with_options(backend, TerminalOptions { viewport })?;
+            terminal.clear()?;
+
+            app_ui.enable_clipboard();
+            // The terminal is restored even if the main loop fails.
+            let result = main_loop(&mut terminal, &mut app_ui, cli.poll_wait_time.unwrap_or(100));
+            restore_terminal();
+            result
         } else {
             panic!("Expected filename argument");
         }
     }
+
+fn main_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app_ui: &mut UI,
+    poll_wait_time: u64,
+) -> Result<()> {
+    loop {
+        debug!("\n**** Draw Iteration ****");
+        debug!("terminal size: {:?}", terminal.size().unwrap());
+        terminal.draw(|f| render_ui(f, app_ui))?;
+        // handle events
+        if event::poll(std::time::Duration::from_millis(poll_wait_time))? {
+            match event::read()? {
+                event::Event::Key(key) if key.kind == KeyEventKind::Press => {
+                    // handle_key_press() returns true IFF user quits
+                    let done = handle_key_press(app_ui, key);
+                    if done {
+                        return Ok(());
+                    }
+                }
+                event::Event::Mouse(mouse_event) => handle_mouse_event(app_ui, mouse_event),
+                _ => {}
+            }
+        }
+    }
+}
+
+// Like main_loop(), but off-screen and with the keys given in advance. As in the main loop, a
+// frame is drawn before each key (some key bindings depend on the layout).
+fn render_once(app_ui: &mut UI, (width, height): (u16, u16), keys: &[KeyEvent]) -> Result<String> {
+    let mut terminal = Terminal::new(TestBackend::new(width, height))?;
+    for key in keys {
+        terminal.draw(|f| render_ui(f, app_ui))?;
+        if handle_key_press(app_ui, *key) {
+            break;
+        }
+    }
+    terminal.draw(|f| render_ui(f, app_ui))?;
+    Ok(buffer_to_ansi(terminal.backend().buffer()))
+}
+
+// Errors are ignored, as this is also called when things have already gone wrong.
+fn restore_terminal() {
+    let _ = stdout().execute(DisableMouseCapture);
+    let _ = disable_raw_mode();
+    let _ = stdout().execute(LeaveAlternateScreen);
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::config::Config;
+    use crate::{apply_config, Cli};
+
+    #[test]
+    fn test_apply_config_flags() {
+        let config = Config::parse("no-color = true\nno-mouse = true").unwrap();
+        let mut cli = Cli::try_parse_from(["termal", "--color", "aln.fas"]).unwrap();
+        apply_config(&mut cli, &config).unwrap();
+        assert!(!cli.no_color);
+        assert!(cli.no_mouse);
+        // The last of a flag and its negation wins.
+        let mut cli = Cli::try_parse_from(["termal", "--color", "-C", "aln.fas"]).unwrap();
+        apply_config(&mut cli, &config).unwrap();
+        assert!(cli.no_color);
+    }
+}
@@ -0,0 +1,250 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Motif search. Patterns are regular expressions or PROSITE patterns (e.g.
+ * "C-x(2,4)-C-x(3)-[LIVMFYWC]"); they are matched (ignoring case) against the ungapped sequences,
+ * and the hits are then mapped back to alignment columns. */
+
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::alignment::is_gap;
+
+// A match of the pattern in a sequence; `seq` is an index into the alignment's sequences (not a
+// position in the current ordering), `cols` are alignment columns, including any gaps inside the
+// match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hit {
+    pub seq: usize,
+    pub cols: Range<usize>,
+}
+
+// A search, as kept by App: the pattern as typed, its compiled form (so that the search can be
+// redone, e.g. when insert columns are toggled), the hits, and the one the user is at.
+pub struct Search {
+    pub pattern: String,
+    pub regex: Regex,
+    // Hits in shown sequences, in display order (see arrange()).
+    pub hits: Vec<Hit>,
+    pub current: Option<usize>,
+    all_hits: Vec<Hit>,
+    // The hits' columns, by sequence (for highlighting).
+    by_seq: Vec<Vec<Range<usize>>>,
+}
+
+impl Search {
+    pub fn new(pattern: &str, regex: Regex, sequences: &[String]) -> Search {
+        let mut search = Search {
+            pattern: pattern.to_string(),
+            regex,
+            hits: Vec::new(),
+            current: None,
+            all_hits: Vec::new(),
+            by_seq: Vec::new(),
+        };
+        search.find(sequences);
+        search
+    }
+
+    // (Re)computes the hits, e.g. after the columns have changed.
+    pub fn find(&mut self, sequences: &[String]) {
+        self.all_hits = find_hits(sequences, &self.regex);
+        self.hits = self.all_hits.clone();
+        self.current = None;
+        self.by_seq = vec![Vec::new(); sequences.len()];
+        for hit in &self.all_hits {
+            self.by_seq[hit.seq].push(hit.cols.clone());
+        }
+    }
+
+    // Puts the hits in the order in which the sequences are shown (`ordering`, which may leave
+    // out some sequences, whose hits are then skipped). The current hit stays current if it is
+    // still shown.
+    pub fn arrange(&mut self, ordering: &[usize]) {
+        let mut rank: Vec<Option<usize>> = vec![None; self.by_seq.len()];
+        for (pos, &seq) in ordering.iter().enumerate() {
+            rank[seq] = Some(pos);
+        }
+        let current = self.current.map(|i| self.hits[i].clone());
+        self.hits = self
+            .all_hits
+            .iter()
+            .filter(|hit| rank[hit.seq].is_some())
+            .cloned()
+            .collect();
+        self.hits.sort_by_key(|hit| (rank[hit.seq], hit.cols.start));
+        self.current = current.and_then(|c| self.hits.iter().position(|h| *h == c));
+    }
+
+    pub fn is_hit(&self, seq: usize, col: usize) -> bool {
+        self.by_seq[seq].iter().any(|cols| cols.contains(&col))
+    }
+}
+
+// Converts one PROSITE pattern element (e.g. "x(2,4)", "[ST]", "{P}", "C") to a regex, or None if
+// it is not a valid element.
+fn prosite_element(element: &str) -> Option<String> {
+    let (body, repeat) = match element.find('(') {
+        Some(i) => {
+            let repeat = element[i..].strip_prefix('(')?.strip_suffix(')')?;
+            let valid = repeat
+                .split(',')
+                .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+            if !valid || repeat.split(',').count() > 2 {
+                return None;
+            }
+            (&element[..i], format!("{{{}}}", repeat))
+        }
+        None => (element, String::new()),
+    };
+    let is_residue = |c: char| c.is_ascii_uppercase();
+    let re = if body == "x" {
+        ".".to_string()
+    } else if body.len() == 1 && body.chars().all(is_residue) {
+        body.to_string()
+    } else if let Some(set) = body.strip_prefix('[').and_then(|b| b.strip_suffix(']')) {
+        // [G>] means G or the end of the sequence.
+        let (set, at_end) = match set.strip_suffix('>') {
+            Some(s) => (s, true),
+            None => (set, false),
+        };
+        if set.is_empty() || !set.chars().all(is_residue) {
+            return None;
+        }
+        if at_end {
+            format!("(?:[{}]|$)", set)
+        } else {
+            format!("[{}]", set)
+        }
+    } else if let Some(set) = body.strip_prefix('{').and_then(|b| b.strip_suffix('}')) {
+        if set.is_empty() || !set.chars().all(is_residue) {
+            return None;
+        }
+        format!("[^{}]", set)
+    } else {
+        return None;
+    };
+    Some(format!("{}{}", re, repeat))
+}
+
+// Converts a PROSITE pattern to a regex, or returns None if the pattern is not PROSITE syntax. A
+// pattern must have at least two elements (i.e., contain a '-') to count as PROSITE, otherwise
+// it is taken as a regex (on which a single element would mostly agree anyway).
+pub fn prosite_to_regex(pattern: &str) -> Option<String> {
+    let pattern = pattern.trim().strip_suffix('.').unwrap_or(pattern.trim());
+    if !pattern.contains('-') {
+        return None;
+    }
+    let elements: Vec<&str> = pattern.split('-').collect();
+    let last = elements.len() - 1;
+    let mut re = String::new();
+    for (i, element) in elements.iter().enumerate() {
+        let mut element = *element;
+        if i == 0 {
+            if let Some(rest) = element.strip_prefix('<') {
+                re.push('^');
+                element = rest;
+            }
+        }
+        let mut at_end = false;
+        if i == last {
+            if let Some(rest) = element.strip_suffix('>') {
+                element = rest;
+                at_end = true;
+            }
+        }
+        re.push_str(&prosite_element(element)?);
+        if at_end {
+            re.push('$');
+        }
+    }
+    Some(re)
+}
+
+// Compiles a pattern (PROSITE or regex); matching ignores case, so that insertions in A2M/A3M
+// alignments (in lowercase) are found too.
+pub fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    let re = prosite_to_regex(pattern).unwrap_or(pattern.to_string());
+    RegexBuilder::new(&re).case_insensitive(true).build()
+}
+
+// Hits are returned by sequence, then by column. Matches do not overlap (as with
+// Regex::find_iter()) and empty matches are ignored.
+pub fn find_hits(sequences: &[String], re: &Regex) -> Vec<Hit> {
+    let mut hits = Vec::new();
+    for (seq, sequence) in sequences.iter().enumerate() {
+        let mut ungapped = String::new();
+        // Alignment column of each byte of the ungapped sequence
+        let mut columns: Vec<usize> = Vec::new();
+        for (col, c) in sequence.chars().enumerate() {
+            if !is_gap(c) {
+                ungapped.push(c);
+                columns.extend(std::iter::repeat_n(col, c.len_utf8()));
+            }
+        }
+        for m in re.find_iter(&ungapped) {
+            if m.is_empty() {
+                continue;
+            }
+            hits.push(Hit {
+                seq,
+                cols: columns[m.start()]..columns[m.end() - 1] + 1,
+            });
+        }
+    }
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prosite_to_regex() {
+        assert_eq!(
+            prosite_to_regex("C-x(2,4)-C-x(3)-[LIVMFYWC]-x(8)-H-x(3,5)-H."),
+            Some("C.{2,4}C.{3}[LIVMFYWC].{8}H.{3,5}H".to_string())
+        );
+        assert_eq!(
+            prosite_to_regex("<M-{P}-[ST]>"),
+            Some("^M[^P][ST]$".to_string())
+        );
+        assert_eq!(
+            prosite_to_regex("A-[G>]"),
+            Some("A(?:[G]|$)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_not_prosite() {
+        assert_eq!(prosite_to_regex("GK[ST]"), None);
+        assert_eq!(prosite_to_regex("G.{2}K"), None);
+        assert_eq!(prosite_to_regex("[A-Z]K"), None);
+    }
+
+    #[test]
+    fn test_find_hits_maps_to_columns() {
+        let sequences = vec!["TT-GCCG-CGA".to_string(), "ttgccggcga".to_string()];
+        let re = compile_pattern("G-C-G").unwrap();
+        let hits = find_hits(&sequences, &re);
+        assert_eq!(
+            hits,
+            vec![
+                // G at col 6, gap at 7, C at 8, G at 9
+                Hit { seq: 0, cols: 6..10 },
+                Hit { seq: 1, cols: 6..9 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_hits_regex() {
+        let sequences = vec!["AC-GT-AC".to_string()];
+        let re = compile_pattern("ac").unwrap();
+        let hits = find_hits(&sequences, &re);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].cols, 6..8);
+        assert!(find_hits(&sequences, &compile_pattern("x*").unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Newick trees, e.g. "((A:0.1,B:0.2)95:0.05,C:0.3);". Only the first tree of the input is read.
+ * Names may be quoted ('...', in which '' stands for a quote); [comments] are skipped, as is
+ * whitespace outside of quotes. Inner nodes may have names (often support values) and any node a
+ * branch length, but neither is required. */
+
+use std::io::BufRead;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+use crate::errors::InputError;
+use crate::input::open_input;
+
+pub struct NewickNode {
+    pub name: String,
+    pub length: Option<f64>,
+    pub children: Vec<NewickNode>,
+}
+
+pub fn read_newick_file<P: AsRef<Path>>(path: P) -> Result<NewickNode, InputError> {
+    read_newick(open_input(path)?)
+}
+
+pub fn read_newick(mut reader: impl BufRead) -> Result<NewickNode, InputError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    parse_newick(&text)
+}
+
+pub fn parse_newick(text: &str) -> Result<NewickNode, InputError> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+        line: 1,
+    };
+    if parser.peek()?.is_none() {
+        return Err(InputError::syntax(None, "no tree found"));
+    }
+    let tree = parser.tree()?;
+    match parser.peek()? {
+        Some(';') => Ok(tree),
+        Some(c) => Err(parser.error(format!("expected ';', found '{}'", c))),
+        None => Err(parser.error("missing ';' at the end of the tree")),
+    }
+}
+
+// Characters that end an unquoted name or a branch length.
+const DELIMITERS: &str = "()[]':;,";
+
+// Trees are laid out and pruned recursively (see tree.rs): deeper ones are refused rather than
+// overflowing the stack. This is still enough for a caterpillar tree of 1000 leaves.
+pub const MAX_DEPTH: usize = 1000;
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: impl Into<String>) -> InputError {
+        InputError::syntax(Some(self.line), msg)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    // The next character that is neither whitespace nor part of a comment, without consuming it.
+    fn peek(&mut self) -> Result<Option<char>, InputError> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.next();
+                }
+                Some('[') => {
+                    let line = self.line;
+                    while self.next() != Some(']') {
+                        if self.chars.peek().is_none() {
+                            return Err(InputError::syntax(Some(line), "unterminated comment"));
+                        }
+                    }
+                }
+                c => return Ok(c.copied()),
+            }
+        }
+    }
+
+    // A leaf, or an inner node with its descendants, as in "(A,B)name:length". Parsed with an
+    // explicit stack of the inner nodes being read, so that deep trees can't overflow the call
+    // stack.
+    fn tree(&mut self) -> Result<NewickNode, InputError> {
+        // The children read so far of each open inner node, outermost first
+        let mut open: Vec<Vec<NewickNode>> = Vec::new();
+        loop {
+            while self.peek()? == Some('(') {
+                if open.len() == MAX_DEPTH {
+                    return Err(self.error(format!(
+                        "tree nested too deeply (more than {} levels)",
+                        MAX_DEPTH
+                    )));
+                }
+                self.next();
+                open.push(Vec::new());
+            }
+            let mut node = self.node(Vec::new())?;
+            // Close the inner nodes that end after this one.
+            loop {
+                let Some(children) = open.last_mut() else {
+                    return Ok(node);
+                };
+                children.push(node);
+                match self.peek()? {
+                    Some(',') => {
+                        self.next();
+                        break;
+                    }
+                    Some(')') => {
+                        self.next();
+                        let children = open.pop().unwrap();
+                        node = self.node(children)?;
+                    }
+                    Some(c) => {
+                        return Err(self.error(format!("expected ',' or ')', found '{}'", c)))
+                    }
+                    None => return Err(self.error("unexpected end of input (missing ')')")),
+                }
+            }
+        }
+    }
+
+    // The name and branch length that follow a node's children (if any).
+    fn node(&mut self, children: Vec<NewickNode>) -> Result<NewickNode, InputError> {
+        let name = self.name()?;
+        let length = if self.peek()? == Some(':') {
+            self.next();
+            Some(self.length()?)
+        } else {
+            None
+        };
+        Ok(NewickNode {
+            name,
+            length,
+            children,
+        })
+    }
+
+    // Possibly empty.
+    fn name(&mut self) -> Result<String, InputError> {
+        let mut name = String::new();
+        if self.peek()? == Some('\'') {
+            let line = self.line;
+            self.next();
+            loop {
+                match self.next() {
+                    Some('\'') if self.chars.peek() == Some(&'\'') => {
+                        self.next();
+                        name.push('\'');
+                    }
+                    Some('\'') => return Ok(name),
+                    Some(c) => name.push(c),
+                    None => return Err(InputError::syntax(Some(line), "unterminated quoted name")),
+                }
+            }
+        }
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || DELIMITERS.contains(c) {
+                break;
+            }
+            name.push(c);
+            self.next();
+        }
+        Ok(name)
+    }
+
+    fn length(&mut self) -> Result<f64, InputError> {
+        self.peek()?;
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || DELIMITERS.contains(c) {
+                break;
+            }
+            text.push(c);
+            self.next();
+        }
+        text.parse()
+            .map_err(|_| self.error(format!("bad branch length '{}'", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::newick::{parse_newick, read_newick_file, NewickNode, MAX_DEPTH};
+
+    fn leaf_names(node: &NewickNode) -> Vec<&str> {
+        if node.children.is_empty() {
+            return vec![&node.name];
+        }
+        node.children.iter().flat_map(leaf_names).collect()
+    }
+
+    #[test]
+    fn test_parse_newick() {
+        let tree = parse_newick("((A:0.1,B:0.2)95:0.05,'C d''e':0.3)root;").unwrap();
+        assert_eq!(tree.name, "root");
+        assert_eq!(tree.length, None);
+        assert_eq!(tree.children[0].name, "95");
+        assert_eq!(tree.children[0].length, Some(0.05));
+        assert_eq!(tree.children[0].children[1].length, Some(0.2));
+        assert_eq!(leaf_names(&tree), vec!["A", "B", "C d'e"]);
+    }
+
+    #[test]
+    fn test_parse_newick_comments_and_whitespace() {
+        let text = "[a comment]\n( A [another]: 1 ,\n  B,\n (C, D) ) ;\n(E, F);";
+        let tree = parse_newick(text).unwrap();
+        assert_eq!(leaf_names(&tree), vec!["A", "B", "C", "D"]);
+        assert_eq!(tree.children[0].length, Some(1.0));
+        assert_eq!(tree.children[1].length, None);
+    }
+
+    #[test]
+    fn test_parse_newick_errors() {
+        let msg = |text| parse_newick(text).err().unwrap().to_string();
+        assert_eq!(msg("(A,B"), "line 1: unexpected end of input (missing ')')");
+        assert_eq!(msg("(A,B)"), "line 1: missing ';' at the end of the tree");
+        assert_eq!(msg("(A,\nB:x);"), "line 2: bad branch length 'x'");
+        assert_eq!(msg("(A B);"), "line 1: expected ',' or ')', found 'B'");
+        assert_eq!(msg("('A,B);"), "line 1: unterminated quoted name");
+        assert_eq!(msg("  \n"), "no tree found");
+    }
+
+    // A caterpillar tree, e.g. "(((A,B),C),D);" for depth 3.
+    fn caterpillar(depth: usize) -> String {
+        let mut text = "(".repeat(depth);
+        text.push_str("A0");
+        for i in 1..=depth {
+            text.push_str(&format!(",A{})", i));
+        }
+        text + ";"
+    }
+
+    #[test]
+    fn test_parse_newick_depth() {
+        let tree = parse_newick(&caterpillar(MAX_DEPTH)).unwrap();
+        assert_eq!(leaf_names(&tree).len(), MAX_DEPTH + 1);
+        let msg = parse_newick(&caterpillar(5000)).err().unwrap().to_string();
+        assert_eq!(msg, "line 1: tree nested too deeply (more than 1000 levels)");
+    }
+
+    #[test]
+    fn test_read_newick_file() {
+        let tree = read_newick_file("data/test2.nwk").unwrap();
+        assert_eq!(leaf_names(&tree), vec!["seq3", "seq1", "seq2", "unknown"]);
+    }
+}
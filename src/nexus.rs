@@ -0,0 +1,480 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+use std::io::{BufRead, Write};
+
+use crate::alignment::SeqType;
+use crate::fasta::{seq_id, FastaFile, FastaRecord};
+use crate::errors::InputError;
+use crate::input::read_text;
+
+// In the alignment, gaps are always '-', and missing data is always '?' (both of which
+// Alignment::new() treats as gaps). Files that declare other symbols for these (e.g. GAP=. or
+// MISSING=N) are translated when read.
+const GAP: char = '-';
+const MISSING: char = '?';
+
+// The matrix is parsed after comments are stripped and commands are split, so line numbers are not
+// known.
+fn invalid_data(msg: String) -> InputError {
+    InputError::syntax(None, msg)
+}
+
+// Settings from the DIMENSIONS and FORMAT commands of the DATA (or CHARACTERS) block.
+struct MatrixFormat {
+    ntax: Option<usize>,
+    nchar: Option<usize>,
+    gap: char,
+    missing: char,
+    match_char: Option<char>,
+    interleave: bool,
+}
+
+impl Default for MatrixFormat {
+    fn default() -> Self {
+        MatrixFormat {
+            ntax: None,
+            nchar: None,
+            gap: GAP,
+            missing: MISSING,
+            match_char: None,
+            interleave: false,
+        }
+    }
+}
+
+#[cfg(test)]
+pub fn read_nexus_file<P: AsRef<std::path::Path>>(path: P) -> Result<FastaFile, InputError> {
+    read_nexus(crate::input::open_input(path)?)
+}
+
+// Reads the MATRIX of the first DATA or CHARACTERS block. Other blocks (TAXA, TREES, ASSUMPTIONS,
+// etc.) are skipped, except that NTAX is also taken from a TAXA block, as CHARACTERS blocks
+// usually do not repeat it.
+pub fn read_nexus<R: BufRead>(reader: R) -> Result<FastaFile, InputError> {
+    let text = read_text(reader)?;
+    let text = strip_comments(&text);
+
+    if !text.trim_start().to_ascii_uppercase().starts_with("#NEXUS") {
+        return Err(invalid_data("expected '#NEXUS' header".into()));
+    }
+
+    let mut format = MatrixFormat::default();
+    let mut in_data_block = false;
+    let mut matrix: Option<String> = None;
+
+    for command in split_commands(text.trim_start()[6..].trim_start()) {
+        let words = split_words(&command);
+        let Some(keyword) = words.first().map(|w| w.to_ascii_uppercase()) else {
+            continue;
+        };
+        match keyword.as_str() {
+            "BEGIN" => {
+                let block = words.get(1).map(|w| w.to_ascii_uppercase());
+                in_data_block = matches!(block.as_deref(), Some("DATA" | "CHARACTERS"));
+            }
+            "END" | "ENDBLOCK" => in_data_block = false,
+            "DIMENSIONS" => {
+                for (key, value) in key_values(&words[1..]) {
+                    match key.as_str() {
+                        "NTAX" => format.ntax = value.parse().ok(),
+                        "NCHAR" if in_data_block => format.nchar = value.parse().ok(),
+                        _ => {}
+                    }
+                }
+            }
+            "FORMAT" if in_data_block => {
+                for (key, value) in key_values(&words[1..]) {
+                    match key.as_str() {
+                        "GAP" => format.gap = single_char(&value)?,
+                        "MISSING" => format.missing = single_char(&value)?,
+                        "MATCHCHAR" => format.match_char = Some(single_char(&value)?),
+                        "INTERLEAVE" => {
+                            format.interleave = !value.eq_ignore_ascii_case("NO");
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            "MATRIX" if in_data_block => {
+                // Keep the newlines: in interleaved matrices, each line starts with a name.
+                let start = command.to_ascii_uppercase().find("MATRIX").unwrap() + 6;
+                matrix = Some(command[start..].to_string());
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let Some(matrix) = matrix else {
+        return Err(invalid_data("no MATRIX found in a DATA or CHARACTERS block".into()));
+    };
+    let (Some(ntax), Some(nchar)) = (format.ntax, format.nchar) else {
+        return Err(invalid_data("missing NTAX or NCHAR in DIMENSIONS".into()));
+    };
+
+    let mut records = if format.interleave {
+        read_interleaved_matrix(&matrix, ntax)?
+    } else {
+        read_sequential_matrix(&matrix, ntax, nchar)?
+    };
+
+    // Lengths are in bytes, as in the other readers, so non-ASCII characters (which cannot be
+    // residues) must be reported first. The other symbols are checked once normalized (see
+    // check_records()).
+    for record in records.iter() {
+        if let Some(residue) = record.sequence.chars().find(|c| !c.is_ascii()) {
+            return Err(InputError::InvalidResidue {
+                line: None,
+                id: record.header.clone(),
+                residue,
+            });
+        }
+        if record.sequence.len() != nchar {
+            return Err(InputError::UnequalLength {
+                line: None,
+                id: record.header.clone(),
+                expected: nchar,
+                found: record.sequence.len(),
+            });
+        }
+    }
+    normalize_symbols(&mut records, &format);
+
+    Ok(records)
+}
+
+// Removes [comments], which may appear anywhere (but not inside quoted names).
+fn strip_comments(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut depth = 0;
+    let mut in_quotes = false;
+    for c in text.chars() {
+        match c {
+            '\'' if depth == 0 => {
+                in_quotes = !in_quotes;
+                result.push(c);
+            }
+            '[' if !in_quotes => depth += 1,
+            ']' if !in_quotes && depth > 0 => depth -= 1,
+            _ if depth == 0 => result.push(c),
+            _ => {}
+        }
+    }
+    result
+}
+
+// Splits the text into ';'-terminated commands (again, except inside quotes).
+fn split_commands(text: &str) -> Vec<String> {
+    let mut commands: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in text.chars() {
+        match c {
+            '\'' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ';' if !in_quotes => commands.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() {
+        commands.push(current);
+    }
+    commands
+}
+
+// Splits a command (or a line) into words. Quoted words may contain whitespace, and '' stands for
+// a literal quote. '=' is always a word of its own, so that 'GAP=-' and 'GAP = -' are the same.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                while let Some(q) = chars.next() {
+                    if q == '\'' {
+                        if chars.peek() == Some(&'\'') {
+                            current.push('\'');
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    } else {
+                        current.push(q);
+                    }
+                }
+            }
+            '=' => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                words.push("=".into());
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+// Turns e.g. [NTAX, =, 3, INTERLEAVE] into [(NTAX, 3), (INTERLEAVE, YES)]. Keys are uppercased.
+fn key_values(words: &[String]) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let key = words[i].to_ascii_uppercase();
+        if words.get(i + 1).map(|w| w.as_str()) == Some("=") {
+            pairs.push((key, words.get(i + 2).cloned().unwrap_or_default()));
+            i += 3;
+        } else {
+            pairs.push((key, "YES".into()));
+            i += 1;
+        }
+    }
+    pairs
+}
+
+fn single_char(value: &str) -> Result<char, InputError> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(invalid_data(format!("expected a single symbol, got '{}'", value))),
+    }
+}
+
+// Concatenates sequence words. Sets of states, like {AG} or (AG), are ambiguous and are shown as
+// 'X'.
+fn push_sequence_words(sequence: &mut String, words: &[String]) {
+    let mut in_set = false;
+    for word in words {
+        for c in word.chars() {
+            match c {
+                '{' | '(' => {
+                    in_set = true;
+                    sequence.push('X');
+                }
+                '}' | ')' => in_set = false,
+                _ if in_set => {}
+                _ => sequence.push(c),
+            }
+        }
+    }
+}
+
+// In non-interleaved matrices, a sequence may span several lines, so we go by NCHAR.
+fn read_sequential_matrix(
+    matrix: &str,
+    ntax: usize,
+    nchar: usize,
+) -> Result<FastaFile, InputError> {
+    let words = split_words(matrix);
+    let mut records: FastaFile = Vec::new();
+    let mut words = words.iter();
+    while let Some(name) = words.next() {
+        let mut sequence = String::new();
+        while sequence.len() < nchar {
+            let Some(word) = words.next() else { break };
+            push_sequence_words(&mut sequence, std::slice::from_ref(word));
+        }
+        records.push(FastaRecord {
+            header: name.clone(),
+            sequence,
+        });
+    }
+    if records.len() != ntax {
+        return Err(invalid_data(format!(
+            "found {} sequences in MATRIX, but NTAX is {}",
+            records.len(),
+            ntax
+        )));
+    }
+    Ok(records)
+}
+
+// In interleaved matrices, each line has a name and a chunk of that sequence.
+fn read_interleaved_matrix(matrix: &str, ntax: usize) -> Result<FastaFile, InputError> {
+    let mut records: FastaFile = Vec::new();
+    for line in matrix.lines() {
+        let words = split_words(line);
+        let Some((name, chunks)) = words.split_first() else {
+            continue;
+        };
+        match records.iter_mut().find(|r| &r.header == name) {
+            Some(record) => push_sequence_words(&mut record.sequence, chunks),
+            None => {
+                let mut sequence = String::new();
+                push_sequence_words(&mut sequence, chunks);
+                records.push(FastaRecord {
+                    header: name.clone(),
+                    sequence,
+                });
+            }
+        }
+    }
+    if records.len() != ntax {
+        return Err(invalid_data(format!(
+            "found {} sequences in MATRIX, but NTAX is {}",
+            records.len(),
+            ntax
+        )));
+    }
+    Ok(records)
+}
+
+// Translates the file's gap and missing symbols into ours, and resolves match characters
+// (MATCHCHAR=. means "same as in the first sequence", once that one is translated).
+fn normalize_symbols(records: &mut FastaFile, format: &MatrixFormat) {
+    let symbol = |c: char| {
+        if c == format.gap {
+            GAP
+        } else if c == format.missing {
+            MISSING
+        } else {
+            c
+        }
+    };
+    let Some((first, others)) = records.split_first_mut() else {
+        return;
+    };
+    first.sequence = first.sequence.chars().map(symbol).collect();
+    let first: Vec<char> = first.sequence.chars().collect();
+    for record in others {
+        record.sequence = record
+            .sequence
+            .chars()
+            .enumerate()
+            .map(|(j, c)| {
+                if c != format.gap && c != format.missing && Some(c) == format.match_char {
+                    first[j]
+                } else {
+                    symbol(c)
+                }
+            })
+            .collect();
+    }
+}
+
+// Names with whitespace or NEXUS punctuation must be quoted, with single quotes doubled.
+fn quote_name(name: &str) -> String {
+    let needs_quotes = name.is_empty()
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || "()[]{}/\\,;:=*'\"`+-<>".contains(c));
+    if needs_quotes {
+        format!("'{}'", name.replace('\'', "''"))
+    } else {
+        name.to_string()
+    }
+}
+
+// Writes a DATA block with a non-interleaved MATRIX.
+pub fn write_nexus<W: Write>(
+    mut writer: W,
+    records: &FastaFile,
+    seq_type: SeqType,
+) -> std::io::Result<()> {
+    let nchar = records.first().map_or(0, |r| r.sequence.len());
+    let names: Vec<String> = records.iter().map(|r| quote_name(seq_id(&r.header))).collect();
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+    let datatype = match seq_type {
+        SeqType::Nucleic => "DNA",
+        SeqType::Protein => "PROTEIN",
+    };
+    writeln!(writer, "#NEXUS\n")?;
+    writeln!(writer, "BEGIN DATA;")?;
+    writeln!(writer, "  DIMENSIONS NTAX={} NCHAR={};", records.len(), nchar)?;
+    writeln!(writer, "  FORMAT DATATYPE={} GAP={} MISSING={};", datatype, GAP, MISSING)?;
+    writeln!(writer, "  MATRIX")?;
+    for (name, record) in names.iter().zip(records.iter()) {
+        writeln!(writer, "    {:width$} {}", name, record.sequence)?;
+    }
+    writeln!(writer, "  ;")?;
+    writeln!(writer, "END;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_nexus_file_1() {
+        let aln = read_nexus_file("data/test1.nex").expect("Test file not found");
+        assert_eq!(aln.len(), 3);
+        assert_eq!(aln[0].header, "Homo sapiens");
+        assert_eq!(aln[0].sequence, "ACGTACGTAC-GTACGTACGTTT");
+        assert_eq!(aln[1].header, "Pan");
+        assert_eq!(aln[1].sequence, "ACGTACGTACAGTACGTACGTTT");
+        assert_eq!(aln[2].header, "Gorilla");
+        assert_eq!(aln[2].sequence, "ACGTTCGTAC-GTACGTAC?TTT");
+    }
+
+    #[test]
+    fn test_read_nexus_interleaved_symbols() {
+        // Custom gap and missing symbols, match character, interleaved matrix, CHARACTERS block.
+        let aln = read_nexus_file("data/test2.nex").expect("Test file not found");
+        assert_eq!(aln.len(), 3);
+        assert_eq!(aln[0].header, "Homo");
+        assert_eq!(aln[0].sequence, "ACGTACGTAC-GTACGTACGTTT");
+        assert_eq!(aln[1].header, "Pan");
+        assert_eq!(aln[1].sequence, "ACGTACGTACAGTACGTACGTTT");
+        assert_eq!(aln[2].header, "Gorilla");
+        assert_eq!(aln[2].sequence, "ACGTTCGTAC-GTACGTAC?TTT");
+        // A match character under a gap stands for a gap, not for the file's gap symbol.
+        let text = "#NEXUS\nbegin data; dimensions ntax=2 nchar=4; format gap=~ matchchar=.;\n\
+                    matrix a AC~T b G..A; end;";
+        let aln = read_nexus(text.as_bytes()).unwrap();
+        assert_eq!(aln[1].sequence, "GC-A");
+    }
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            vec!["GAP", "=", "-", "MISSING", "=", "?"],
+            split_words("GAP=- MISSING = ?")
+        );
+        assert_eq!(vec!["it's", "ACGT"], split_words("'it''s' ACGT"));
+    }
+
+    #[test]
+    fn test_write_nexus() {
+        let records = vec![
+            FastaRecord {
+                header: "seq1".into(),
+                sequence: "AC-T".into(),
+            },
+            FastaRecord {
+                header: "it's".into(),
+                sequence: "ACG?".into(),
+            },
+        ];
+        let mut out: Vec<u8> = Vec::new();
+        write_nexus(&mut out, &records, SeqType::Nucleic).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("DATATYPE=DNA"));
+        assert!(text.contains("'it''s' ACG?"));
+        let aln = read_nexus(text.as_bytes()).unwrap();
+        assert_eq!(aln[1].header, "it's");
+        assert_eq!(aln[1].sequence, "ACG?");
+        assert_eq!(aln[0].sequence, "AC-T");
+    }
+
+    #[test]
+    fn test_read_nexus_wrong_length() {
+        let text = "#NEXUS\nbegin data; dimensions ntax=2 nchar=4; matrix a ACGT b ACG; end;";
+        let err = read_nexus(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, InputError::UnequalLength { expected: 4, found: 3, .. }));
+        // Same as in FastA files: the multi-byte character is reported, rather than the length.
+        let text = "#NEXUS\nbegin data; dimensions ntax=2 nchar=4; matrix a ACGT b ACGé; end;";
+        let err = read_nexus(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, InputError::InvalidResidue { residue: 'é', .. }));
+    }
+}
@@ -0,0 +1,20 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Opening of output files (exports, matrices, rendered frames). As with input (see input.rs), '-'
+ * stands for the standard stream. */
+
+use std::fs::File;
+use std::io::{stdout, BufWriter, Result, Write};
+
+// Passing this as the file name means writing to standard output.
+pub const STDOUT_PATH: &str = "-";
+
+// The writer is buffered, so it must be flushed (dropping it would ignore write errors).
+pub fn create_output(path: &str) -> Result<Box<dyn Write>> {
+    if path == STDOUT_PATH {
+        Ok(Box::new(BufWriter::new(stdout().lock())))
+    } else {
+        Ok(Box::new(BufWriter::new(File::create(path)?)))
+    }
+}
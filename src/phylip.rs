@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+use std::io::{BufRead, Write};
+
+use crate::fasta::{seq_id, FastaFile, FastaRecord};
+use crate::errors::InputError;
+use crate::input::numbered_lines;
+
+// Length of taxon names in "strict" PHYLIP, where the name is not necessarily followed by
+// whitespace.
+const STRICT_NAME_LEN: usize = 10;
+
+fn invalid_data(msg: String) -> InputError {
+    InputError::syntax(None, msg)
+}
+
+#[cfg(test)]
+pub fn read_phylip_file<P: AsRef<std::path::Path>>(path: P) -> Result<FastaFile, InputError> {
+    read_phylip(crate::input::open_input(path)?)
+}
+
+// Parses the "<ntax> <nchar>" header line.
+pub fn parse_phylip_header(line: &str) -> Option<(usize, usize)> {
+    let mut fields = line.split_whitespace();
+    let ntax = fields.next()?.parse::<usize>().ok()?;
+    let nchar = fields.next()?.parse::<usize>().ok()?;
+    Some((ntax, nchar))
+}
+
+// Splits the first line of a record into name and (the start of) the sequence. Relaxed PHYLIP
+// (as written by RAxML, IQ-TREE, etc.) separates them by whitespace; in strict PHYLIP the name
+// takes exactly 10 characters and may be glued to the sequence.
+fn split_name(line: &str) -> (String, String) {
+    let line = line.trim_start();
+    match line.split_once(char::is_whitespace) {
+        Some((name, rest)) => (name.to_string(), strip_whitespace(rest)),
+        None if line.len() > STRICT_NAME_LEN && line.is_char_boundary(STRICT_NAME_LEN) => (
+            line[..STRICT_NAME_LEN].trim_end().to_string(),
+            line[STRICT_NAME_LEN..].to_string(),
+        ),
+        None => (line.to_string(), String::new()),
+    }
+}
+
+// Sequences may contain spaces (e.g. every 10 residues), which we do not want.
+fn strip_whitespace(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+// Reads both the sequential and the interleaved variants. There is no way to tell them apart
+// from the header (some programs add an 'I' or 'S', but most don't), so we first try to read the
+// file as sequential, and fall back to interleaved if that does not yield ntax sequences of nchar
+// residues each.
+pub fn read_phylip<R: BufRead>(reader: R) -> Result<FastaFile, InputError> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut header_line_num = 0;
+    for line in numbered_lines(reader) {
+        let (line_num, l) = line?;
+        if !l.trim().is_empty() {
+            if lines.is_empty() {
+                header_line_num = line_num;
+            }
+            lines.push(l);
+        }
+    }
+    let Some(header) = lines.first() else {
+        return Err(InputError::Empty);
+    };
+    let Some((ntax, nchar)) = parse_phylip_header(header) else {
+        return Err(InputError::syntax(
+            Some(header_line_num),
+            "expected '<number of taxa> <number of characters>' header",
+        ));
+    };
+    let body = &lines[1..];
+
+    if let Some(records) = read_sequential(body, ntax, nchar) {
+        return Ok(records);
+    }
+    if let Some(records) = read_interleaved(body, ntax, nchar) {
+        return Ok(records);
+    }
+    Err(invalid_data(format!(
+        "could not read {} sequences of length {} (neither as sequential nor as interleaved PHYLIP)",
+        ntax, nchar
+    )))
+}
+
+fn read_sequential(body: &[String], ntax: usize, nchar: usize) -> Option<FastaFile> {
+    let mut records: FastaFile = Vec::new();
+    let mut lines = body.iter();
+    for _ in 0..ntax {
+        let (header, mut sequence) = split_name(lines.next()?);
+        while sequence.len() < nchar {
+            sequence.push_str(&strip_whitespace(lines.next()?));
+        }
+        if sequence.len() != nchar {
+            return None;
+        }
+        records.push(FastaRecord { header, sequence });
+    }
+    // Trailing lines mean that this was not a sequential file after all.
+    if lines.next().is_some() {
+        return None;
+    }
+    Some(records)
+}
+
+fn read_interleaved(body: &[String], ntax: usize, nchar: usize) -> Option<FastaFile> {
+    if ntax == 0 || body.len() < ntax || !body.len().is_multiple_of(ntax) {
+        return None;
+    }
+    let mut records: FastaFile = body[..ntax]
+        .iter()
+        .map(|l| {
+            let (header, sequence) = split_name(l);
+            FastaRecord { header, sequence }
+        })
+        .collect();
+    // Subsequent blocks have no names.
+    for (i, line) in body[ntax..].iter().enumerate() {
+        records[i % ntax].sequence.push_str(&strip_whitespace(line));
+    }
+    if records.iter().any(|r| r.sequence.len() != nchar) {
+        return None;
+    }
+    Some(records)
+}
+
+// Writes relaxed sequential PHYLIP, i.e. names are not truncated to 10 characters.
+pub fn write_phylip<W: Write>(mut writer: W, records: &FastaFile) -> std::io::Result<()> {
+    let nchar = records.first().map_or(0, |r| r.sequence.len());
+    let width = records.iter().map(|r| seq_id(&r.header).len()).max().unwrap_or(0);
+    writeln!(writer, "{} {}", records.len(), nchar)?;
+    for record in records {
+        writeln!(writer, "{:width$} {}", seq_id(&record.header), record.sequence)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_phylip_sequential() {
+        let aln = read_phylip_file("data/test-seq.phy").expect("Test file not found");
+        assert_eq!(aln.len(), 3);
+        assert_eq!(aln[0].header, "Homo");
+        assert_eq!(aln[0].sequence, "ACGTACGTAC-GTACGTACGTTT");
+        assert_eq!(aln[1].header, "Pan");
+        assert_eq!(aln[1].sequence, "ACGTACGTACAGTACGTACGTTT");
+        assert_eq!(aln[2].header, "Gorilla");
+        assert_eq!(aln[2].sequence, "ACGTTCGTAC-GTACGTAC?TTT");
+    }
+
+    #[test]
+    fn test_read_phylip_interleaved() {
+        let aln = read_phylip_file("data/test-int.phy").expect("Test file not found");
+        assert_eq!(aln.len(), 3);
+        assert_eq!(aln[0].header, "Homo");
+        assert_eq!(aln[0].sequence, "ACGTACGTAC-GTACGTACGTTT");
+        assert_eq!(aln[2].header, "Gorilla");
+        assert_eq!(aln[2].sequence, "ACGTTCGTAC-GTACGTAC?TTT");
+    }
+
+    #[test]
+    fn test_read_phylip_strict_names() {
+        let text = "2 6\nHomo_sapieACGTAC\nPan_troglo ACGTAA\n";
+        let aln = read_phylip(text.as_bytes()).unwrap();
+        assert_eq!(aln[0].header, "Homo_sapie");
+        assert_eq!(aln[0].sequence, "ACGTAC");
+        assert_eq!(aln[1].header, "Pan_troglo");
+        assert_eq!(aln[1].sequence, "ACGTAA");
+    }
+
+    #[test]
+    fn test_write_phylip() {
+        let aln = read_phylip_file("data/test-int.phy").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        write_phylip(&mut out, &aln).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("3 23\nHomo    ACGTACGTAC-GTACGTACGTTT\n"));
+        let aln2 = read_phylip(text.as_bytes()).unwrap();
+        assert_eq!(aln[2].header, aln2[2].header);
+        assert_eq!(aln[2].sequence, aln2[2].sequence);
+    }
+
+    #[test]
+    fn test_read_phylip_wrong_length() {
+        let text = "2 6\nHomo ACGTAC\nPan ACGTA\n";
+        let err = read_phylip(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, InputError::Syntax { line: None, .. }));
+    }
+}
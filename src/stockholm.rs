@@ -0,0 +1,361 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+use crate::fasta::{seq_id, FastaFile, FastaRecord};
+use crate::errors::InputError;
+use crate::input::numbered_lines;
+
+// A named annotation string that has one character per alignment column, such as a #=GC SS_cons
+// line, or a #=GR line (which is attached to a sequence). In interleaved files, the pieces from
+// each block are concatenated, just like the sequences.
+#[derive(Debug, Default, Clone)]
+pub struct Track {
+    pub name: String,
+    pub data: String,
+}
+
+// Everything a Stockholm file can say about an alignment besides the sequences themselves.
+// Free-text annotations (#=GF, #=GS) are kept as (feature, text) pairs in file order, and several
+// lines with the same feature (e.g. #=GF CC) are kept as separate pairs.
+#[derive(Debug, Default, Clone)]
+pub struct Annotations {
+    // #=GF <feature> <text>
+    pub file: Vec<(String, String)>,
+    // #=GS <seqname> <feature> <text>, keyed by sequence name
+    pub sequence: HashMap<String, Vec<(String, String)>>,
+    // #=GR <seqname> <feature> <per-residue annotation>, keyed by sequence name
+    pub residue: HashMap<String, Vec<Track>>,
+    // #=GC <feature> <per-column annotation>
+    pub column: Vec<Track>,
+}
+
+impl Annotations {
+    // The same annotations, restricted to a range of columns.
+    pub fn columns(&self, range: &std::ops::Range<usize>) -> Annotations {
+        let slice = |track: &Track| Track {
+            name: track.name.clone(),
+            data: track.data.chars().skip(range.start).take(range.len()).collect(),
+        };
+        Annotations {
+            file: self.file.clone(),
+            sequence: self.sequence.clone(),
+            residue: self
+                .residue
+                .iter()
+                .map(|(name, tracks)| (name.clone(), tracks.iter().map(slice).collect()))
+                .collect(),
+            column: self.column.iter().map(slice).collect(),
+        }
+    }
+}
+
+pub struct StockholmFile {
+    pub records: FastaFile,
+    pub annotations: Annotations,
+}
+
+fn invalid_data(line_num: usize, msg: &str) -> InputError {
+    InputError::syntax(Some(line_num), msg)
+}
+
+// Appends data to the track called name, creating it if needed.
+fn append_to_track(tracks: &mut Vec<Track>, name: &str, data: &str) {
+    match tracks.iter_mut().find(|t| t.name == name) {
+        Some(track) => track.data.push_str(data),
+        None => tracks.push(Track {
+            name: name.to_string(),
+            data: data.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+pub fn read_stockholm_file<P: AsRef<std::path::Path>>(path: P) -> Result<StockholmFile, InputError> {
+    read_stockholm(crate::input::open_input(path)?)
+}
+
+// Only the first alignment in the file is read (Pfam and Rfam flat files can hold several, each
+// terminated by a '//' line).
+pub fn read_stockholm<R: BufRead>(reader: R) -> Result<StockholmFile, InputError> {
+    let mut records: FastaFile = Vec::new();
+    // Sequence name -> index into records, so that interleaved blocks get appended to the right
+    // record.
+    let mut seq_index: HashMap<String, usize> = HashMap::new();
+    let mut annotations = Annotations::default();
+    // Label of each per-residue or per-column track (e.g. "#=GC SS_cons") -> its last line, so
+    // that lengths can be checked once all blocks are read.
+    let mut track_lines: HashMap<String, usize> = HashMap::new();
+    let mut seen_header = false;
+
+    for line in numbered_lines(reader) {
+        let (line_num, l) = line?;
+        let l = l.trim_end();
+        if l.trim().is_empty() {
+            continue;
+        }
+        if !seen_header {
+            if !l.starts_with("# STOCKHOLM") {
+                return Err(invalid_data(line_num, "expected '# STOCKHOLM' header"));
+            }
+            seen_header = true;
+            continue;
+        }
+        if l == "//" {
+            break;
+        }
+        if let Some(markup) = l.strip_prefix("#=") {
+            let (tag, rest) = markup.split_once(char::is_whitespace).unwrap_or((markup, ""));
+            let rest = rest.trim_start();
+            match tag {
+                "GF" => {
+                    let (feature, text) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                    annotations
+                        .file
+                        .push((feature.to_string(), text.trim().to_string()));
+                }
+                "GS" => {
+                    let mut fields = rest.splitn(3, char::is_whitespace);
+                    let (Some(seqname), Some(feature)) = (fields.next(), fields.next()) else {
+                        return Err(invalid_data(line_num, "malformed #=GS line"));
+                    };
+                    annotations
+                        .sequence
+                        .entry(seqname.to_string())
+                        .or_default()
+                        .push((feature.to_string(), fields.next().unwrap_or("").trim().to_string()));
+                }
+                "GR" => {
+                    let fields: Vec<&str> = rest.split_whitespace().collect();
+                    if fields.len() != 3 {
+                        return Err(invalid_data(line_num, "malformed #=GR line"));
+                    }
+                    let tracks = annotations.residue.entry(fields[0].to_string()).or_default();
+                    append_to_track(tracks, fields[1], fields[2]);
+                    track_lines.insert(format!("#=GR {} {}", fields[0], fields[1]), line_num);
+                }
+                "GC" => {
+                    let fields: Vec<&str> = rest.split_whitespace().collect();
+                    if fields.len() != 2 {
+                        return Err(invalid_data(line_num, "malformed #=GC line"));
+                    }
+                    append_to_track(&mut annotations.column, fields[0], fields[1]);
+                    track_lines.insert(format!("#=GC {}", fields[0]), line_num);
+                }
+                _ => return Err(invalid_data(line_num, &format!("unknown markup '#={}'", tag))),
+            }
+            continue;
+        }
+        if l.starts_with('#') {
+            // Plain comment
+            continue;
+        }
+
+        let fields: Vec<&str> = l.split_whitespace().collect();
+        if fields.len() != 2 {
+            return Err(invalid_data(line_num, "expected '<seqname> <aligned sequence>'"));
+        }
+        let (name, seq) = (fields[0], fields[1]);
+        match seq_index.get(name) {
+            Some(&ndx) => records[ndx].sequence.push_str(seq),
+            None => {
+                seq_index.insert(name.to_string(), records.len());
+                records.push(FastaRecord {
+                    header: name.to_string(),
+                    sequence: seq.to_string(),
+                });
+            }
+        }
+    }
+
+    if !seen_header {
+        return Err(InputError::Empty);
+    }
+    check_track_lengths(&records, &annotations, &track_lines)?;
+
+    Ok(StockholmFile {
+        records,
+        annotations,
+    })
+}
+
+// Tracks must be as long as the alignment (which is checked separately), or they would be shown
+// misaligned and exported truncated. The first offending track (by line) is reported.
+fn check_track_lengths(
+    records: &FastaFile,
+    annotations: &Annotations,
+    track_lines: &HashMap<String, usize>,
+) -> Result<(), InputError> {
+    let Some(first) = records.first() else {
+        return Ok(());
+    };
+    // Lengths are in bytes, as for the sequences (see fasta::check_lengths()).
+    let aln_len = first.sequence.len();
+    let residue_tracks = annotations.residue.iter().flat_map(|(name, tracks)| {
+        tracks.iter().map(move |t| (format!("#=GR {} {}", name, t.name), t))
+    });
+    let column_tracks = annotations
+        .column
+        .iter()
+        .map(|t| (format!("#=GC {}", t.name), t));
+    let mut bad_tracks: Vec<(usize, String, usize)> = residue_tracks
+        .chain(column_tracks)
+        .map(|(label, track)| (track_lines[&label], label, track.data.len()))
+        .filter(|&(_, _, len)| len != aln_len)
+        .collect();
+    bad_tracks.sort();
+    match bad_tracks.first() {
+        Some((line_num, label, len)) => Err(invalid_data(
+            *line_num,
+            &format!("{} has length {}, expected {}", label, len, aln_len),
+        )),
+        None => Ok(()),
+    }
+}
+
+// Writes a single-block (non-interleaved) Stockholm file. Sequence names are the IDs; the rest of
+// the FastA header, if any, goes into a #=GS DE line.
+pub fn write_stockholm<W: Write>(
+    mut writer: W,
+    records: &FastaFile,
+    annotations: &Annotations,
+) -> std::io::Result<()> {
+    let mut labels: Vec<String> = records.iter().map(|r| seq_id(&r.header).to_string()).collect();
+    for (name, tracks) in &annotations.residue {
+        labels.extend(tracks.iter().map(|t| format!("#=GR {} {}", name, t.name)));
+    }
+    labels.extend(annotations.column.iter().map(|t| format!("#=GC {}", t.name)));
+    let width = labels.iter().map(|l| l.len()).max().unwrap_or(0);
+
+    writeln!(writer, "# STOCKHOLM 1.0")?;
+    for (feature, text) in &annotations.file {
+        writeln!(writer, "#=GF {} {}", feature, text)?;
+    }
+    for record in records {
+        let name = seq_id(&record.header);
+        // After the first word, which may not start the header (see seq_id()).
+        let description = record
+            .header
+            .trim_start()
+            .split_once(char::is_whitespace)
+            .map_or("", |(_, description)| description.trim());
+        if !description.is_empty() {
+            writeln!(writer, "#=GS {} DE {}", name, description)?;
+        }
+        for (feature, text) in annotations.sequence.get(name).into_iter().flatten() {
+            writeln!(writer, "#=GS {} {} {}", name, feature, text)?;
+        }
+    }
+    for record in records {
+        let name = seq_id(&record.header);
+        writeln!(writer, "{:width$} {}", name, record.sequence)?;
+        for track in annotations.residue.get(name).into_iter().flatten() {
+            let label = format!("#=GR {} {}", name, track.name);
+            writeln!(writer, "{:width$} {}", label, track.data)?;
+        }
+    }
+    for track in &annotations.column {
+        let label = format!("#=GC {}", track.name);
+        writeln!(writer, "{:width$} {}", label, track.data)?;
+    }
+    writeln!(writer, "//")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_stockholm_file_1() {
+        let sto = read_stockholm_file("data/test1.sto").expect("Test file not found");
+        let records = sto.records;
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].header, "seq1/1-18");
+        assert_eq!(records[0].sequence, "ACDEF.GHIKLMNPQRSTVW");
+        assert_eq!(records[1].header, "seq2/3-19");
+        assert_eq!(records[1].sequence, "ACDEF.GHIKL-NPQRSTVW");
+        assert_eq!(records[2].header, "seq3");
+        assert_eq!(records[2].sequence, "ACEEFaGHIKLMNPQRS-VW");
+    }
+
+    #[test]
+    fn test_read_stockholm_file_annotations() {
+        let ann = read_stockholm_file("data/test1.sto")
+            .expect("Test file not found")
+            .annotations;
+        assert_eq!(ann.file[0], ("ID".to_string(), "test1".to_string()));
+        assert_eq!(ann.file[1].0, "DE");
+        assert_eq!(ann.file[1].1, "A small test alignment");
+        assert_eq!(
+            ann.sequence["seq2/3-19"][0],
+            ("AC".to_string(), "P00002".to_string())
+        );
+        assert_eq!(ann.residue["seq1/1-18"][0].name, "SS");
+        assert_eq!(ann.residue["seq1/1-18"][0].data, "HHHHH.HHHHCCCEEEEEEE");
+        assert_eq!(ann.column.len(), 2);
+        assert_eq!(ann.column[0].name, "SS_cons");
+        assert_eq!(ann.column[0].data, "HHHHH.HHHHCCCEEEEEEE");
+        assert_eq!(ann.column[1].name, "RF");
+        assert_eq!(ann.column[1].data, "xxxxx.xxxxxxxxxxxxxx");
+    }
+
+    #[test]
+    fn test_write_stockholm() {
+        let sto = read_stockholm_file("data/test1.sto").unwrap();
+        let mut out: Vec<u8> = Vec::new();
+        write_stockholm(&mut out, &sto.records, &sto.annotations).unwrap();
+        let sto2 = read_stockholm(out.as_slice()).unwrap();
+        for (r1, r2) in sto.records.iter().zip(sto2.records.iter()) {
+            assert_eq!(r1.header, r2.header);
+            assert_eq!(r1.sequence, r2.sequence);
+        }
+        assert_eq!(sto.annotations.file, sto2.annotations.file);
+        assert_eq!(sto.annotations.sequence, sto2.annotations.sequence);
+        assert_eq!(sto.annotations.column.len(), sto2.annotations.column.len());
+        assert_eq!(sto.annotations.column[0].data, sto2.annotations.column[0].data);
+        assert_eq!(
+            sto.annotations.residue["seq1/1-18"][0].data,
+            sto2.annotations.residue["seq1/1-18"][0].data
+        );
+    }
+
+    #[test]
+    fn test_write_stockholm_descriptions() {
+        let records = vec![
+            FastaRecord { header: " s1 desc".into(), sequence: "AC".into() },
+            FastaRecord { header: "\u{3000}s2 other desc ".into(), sequence: "AG".into() },
+            FastaRecord { header: "s3".into(), sequence: "AT".into() },
+        ];
+        let mut out: Vec<u8> = Vec::new();
+        write_stockholm(&mut out, &records, &Annotations::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("#=GS s1 DE desc\n"));
+        assert!(out.contains("#=GS s2 DE other desc\n"));
+        assert!(!out.contains("#=GS s3"));
+    }
+
+    #[test]
+    fn test_read_stockholm_track_length() {
+        let text = "# STOCKHOLM 1.0\nseq1 ACGTAC\nseq2 ACGAAC\n#=GC SS_cons <<>>\n//\n";
+        let err = read_stockholm(text.as_bytes()).err().unwrap();
+        assert_eq!(err.to_string(), "line 4: #=GC SS_cons has length 4, expected 6");
+        let text = "# STOCKHOLM 1.0\nseq1 ACGTAC\nseq2 ACGAAC\n#=GC SS_cons <<é>>>\n//\n";
+        let err = read_stockholm(text.as_bytes()).err().unwrap();
+        assert_eq!(err.to_string(), "line 4: #=GC SS_cons has length 7, expected 6");
+        // Tracks are checked once all blocks are read.
+        let text = "# STOCKHOLM 1.0\n\nseq1 ACG\n#=GR seq1 SS HHH\n\nseq1 TAC\n\
+                    #=GR seq1 SS HH\n//\n";
+        let err = read_stockholm(text.as_bytes()).err().unwrap();
+        assert_eq!(err.to_string(), "line 7: #=GR seq1 SS has length 5, expected 6");
+        let text = "# STOCKHOLM 1.0\n\nseq1 ACG\n#=GC RF xxx\n\nseq1 TAC\n#=GC RF xxx\n//\n";
+        assert!(read_stockholm(text.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_read_stockholm_no_header() {
+        let text = "seq1 ACGT\nseq2 ACGA\n//\n";
+        let err = read_stockholm(text.as_bytes()).err().unwrap();
+        assert!(matches!(err, InputError::Syntax { line: Some(1), .. }));
+    }
+}
@@ -0,0 +1,456 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Trees of the sequences: either read from a Newick file (see newick.rs), or guide trees computed
+ * from pairwise distances (see alignment/pairwise.rs) by neighbour joining (NJ) or UPGMA. Both are
+ * the textbook O(n^3) algorithms, which is fine for the few hundred sequences of a typical
+ * alignment. The NJ tree, which is unrooted, is rooted on its last join.
+ *
+ * Trees are used to order the sequences (by the order of their leaves, so that related sequences
+ * are next to each other), and are drawn as a dendrogram next to the labels. */
+
+use crate::newick::NewickNode;
+
+// Distances between the leaves, indexed like them (symmetric, with 0s on the diagonal).
+pub type DistanceMatrix = Vec<Vec<f64>>;
+
+struct Node {
+    // Children, with the lengths of the branches that lead to them. Leaves have none.
+    children: Vec<(usize, f64)>,
+    // Leaves only: the sequence (an index into the alignment's sequences)
+    leaf: Option<usize>,
+}
+
+pub struct Tree {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+impl Tree {
+    // Leaf i is sequence i, until renumbered.
+    fn with_leaves(n: usize) -> Tree {
+        let nodes = (0..n)
+            .map(|i| Node {
+                children: Vec::new(),
+                leaf: Some(i),
+            })
+            .collect();
+        Tree { nodes, root: 0 }
+    }
+
+    fn empty() -> Tree {
+        Tree {
+            nodes: Vec::new(),
+            root: 0,
+        }
+    }
+
+    fn add_leaf(&mut self, seq: usize) -> usize {
+        self.nodes.push(Node {
+            children: Vec::new(),
+            leaf: Some(seq),
+        });
+        self.nodes.len() - 1
+    }
+
+    fn join(&mut self, children: Vec<(usize, f64)>) -> usize {
+        self.nodes.push(Node {
+            children,
+            leaf: None,
+        });
+        self.nodes.len() - 1
+    }
+
+    // The tree of a Newick file, whose leaves are given sequences by `seq_of` (None for names that
+    // match no sequence). Leaves without a sequence are left out, and so are inner nodes left
+    // without leaves; those left with a single child are replaced by it. None if no leaf is left.
+    pub fn from_newick(
+        newick: &NewickNode,
+        mut seq_of: impl FnMut(&str) -> Option<usize>,
+    ) -> Option<Tree> {
+        let mut tree = Tree::empty();
+        tree.root = tree.add_newick(newick, &mut seq_of)?.0;
+        Some(tree)
+    }
+
+    // Returns the subtree's node and the length of the branch that leads to it.
+    fn add_newick(
+        &mut self,
+        newick: &NewickNode,
+        seq_of: &mut impl FnMut(&str) -> Option<usize>,
+    ) -> Option<(usize, f64)> {
+        let length = newick.length.unwrap_or(0.0);
+        if newick.children.is_empty() {
+            let seq = seq_of(&newick.name)?;
+            return Some((self.add_leaf(seq), length));
+        }
+        let children = newick
+            .children
+            .iter()
+            .filter_map(|child| self.add_newick(child, seq_of))
+            .collect();
+        self.add_inner(children, length)
+    }
+
+    // The same tree, restricted to the leaves whose sequences are kept (as in from_newick()).
+    pub fn pruned(&self, keep: impl Fn(usize) -> bool) -> Option<Tree> {
+        let mut tree = Tree::empty();
+        tree.root = tree.add_pruned(self, self.root, 0.0, &keep)?.0;
+        Some(tree)
+    }
+
+    fn add_pruned(
+        &mut self,
+        from: &Tree,
+        node: usize,
+        length: f64,
+        keep: &impl Fn(usize) -> bool,
+    ) -> Option<(usize, f64)> {
+        let Node { children, leaf } = &from.nodes[node];
+        if let Some(seq) = *leaf {
+            return keep(seq).then(|| (self.add_leaf(seq), length));
+        }
+        let children = children
+            .iter()
+            .filter_map(|&(child, child_length)| self.add_pruned(from, child, child_length, keep))
+            .collect();
+        self.add_inner(children, length)
+    }
+
+    // An inner node, unless there are fewer than two children: a single child takes its place
+    // (with both branches as one), and no children means no node.
+    fn add_inner(&mut self, children: Vec<(usize, f64)>, length: f64) -> Option<(usize, f64)> {
+        match children[..] {
+            [] => None,
+            [(child, child_length)] => Some((child, child_length + length)),
+            _ => Some((self.join(children), length)),
+        }
+    }
+
+    // The leaves' sequences, from top to bottom.
+    pub fn leaves(&self) -> Vec<usize> {
+        let mut leaves = Vec::new();
+        self.collect_leaves(self.root, &mut leaves);
+        leaves
+    }
+
+    fn collect_leaves(&self, node: usize, leaves: &mut Vec<usize>) {
+        if let Some(seq) = self.nodes[node].leaf {
+            leaves.push(seq);
+        }
+        for &(child, _) in &self.nodes[node].children {
+            self.collect_leaves(child, leaves);
+        }
+    }
+
+    // Makes leaf i stand for sequence seqs[i] (trees are computed on the shown sequences only).
+    pub fn renumber_leaves(&mut self, seqs: &[usize]) {
+        for node in &mut self.nodes {
+            if let Some(seq) = node.leaf.as_mut() {
+                *seq = seqs[*seq];
+            }
+        }
+    }
+
+    // The tree's topology, drawn with box characters, one line per leaf (in the order of
+    // leaves()). The root is at the left and the leaves at the right edge; inner nodes are two
+    // columns apart per level, or one if that does not fit, and those too deep to fit at all are
+    // drawn in the last column.
+    pub fn dendrogram(&self, width: usize) -> Vec<String> {
+        let num_leaves = self.leaves().len();
+        let mut layout = vec![(0, 0); self.nodes.len()]; // (depth, line)
+        let mut next_line = 0;
+        self.layout(self.root, 0, &mut layout, &mut next_line);
+        let max_depth = (0..self.nodes.len())
+            .filter(|&node| self.nodes[node].leaf.is_none())
+            .map(|node| layout[node].0)
+            .max()
+            .unwrap_or(0);
+        let step = if 2 * max_depth < width { 2 } else { 1 };
+        // Leaves are one past the right edge, so that their branches reach it.
+        let column = |node: usize| -> usize {
+            if self.nodes[node].leaf.is_some() {
+                width
+            } else {
+                (layout[node].0 * step).min(width.saturating_sub(1))
+            }
+        };
+
+        let mut grid = vec![vec![0u8; width]; num_leaves];
+        for (node, Node { children, .. }) in self.nodes.iter().enumerate() {
+            if children.is_empty() || width == 0 {
+                continue;
+            }
+            let x = column(node);
+            let lines = children.iter().map(|&(child, _)| layout[child].1);
+            let (top, bottom) = (lines.clone().min().unwrap(), lines.max().unwrap());
+            for (y, row) in grid.iter_mut().enumerate().take(bottom + 1).skip(top) {
+                if y > top {
+                    row[x] |= UP;
+                }
+                if y < bottom {
+                    row[x] |= DOWN;
+                }
+            }
+            for &(child, _) in children {
+                let row = &mut grid[layout[child].1];
+                row[x] |= RIGHT;
+                let child_x = column(child);
+                for cell in row.iter_mut().take(child_x).skip(x + 1) {
+                    *cell |= LEFT | RIGHT;
+                }
+                if child_x < width {
+                    row[child_x] |= LEFT;
+                }
+            }
+        }
+        grid.iter()
+            .map(|row| row.iter().map(|&cell| box_char(cell)).collect())
+            .collect()
+    }
+
+    // Depths and lines of the subtree's nodes: leaves take the next line, inner nodes are midway
+    // between their first and last children.
+    fn layout(
+        &self,
+        node: usize,
+        depth: usize,
+        layout: &mut [(usize, usize)],
+        next_line: &mut usize,
+    ) {
+        let children = &self.nodes[node].children;
+        if children.is_empty() {
+            layout[node] = (depth, *next_line);
+            *next_line += 1;
+            return;
+        }
+        for &(child, _) in children {
+            self.layout(child, depth + 1, layout, next_line);
+        }
+        let first = layout[children[0].0].1;
+        let last = layout[children[children.len() - 1].0].1;
+        layout[node] = (depth, (first + last) / 2);
+    }
+}
+
+// Directions in which a cell of a dendrogram connects.
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
+
+fn box_char(directions: u8) -> char {
+    match directions {
+        0 => ' ',
+        UP => '╵',
+        DOWN => '╷',
+        LEFT => '╴',
+        RIGHT => '╶',
+        0b0011 => '│',
+        0b1100 => '─',
+        0b1010 => '┌',
+        0b1001 => '└',
+        0b0110 => '┐',
+        0b0101 => '┘',
+        0b1011 => '├',
+        0b0111 => '┤',
+        0b1110 => '┬',
+        0b1101 => '┴',
+        _ => '┼',
+    }
+}
+
+// The pair (i < j) of active nodes that minimizes f(i, j); the first one in case of ties.
+fn closest_pair(n: usize, f: impl Fn(usize, usize) -> f64) -> (usize, usize) {
+    let mut best = (0, 1);
+    let mut min = f64::INFINITY;
+    for i in 0..n {
+        for j in i + 1..n {
+            let value = f(i, j);
+            if value < min {
+                min = value;
+                best = (i, j);
+            }
+        }
+    }
+    best
+}
+
+// Replaces nodes i and j (i < j) by `node`, whose distance to each other node k (an index into the
+// old matrix) is given by `new_dist`. The matrix is updated in place: the rows and columns of i and
+// j are removed, and those of the new node appended (reusing j's row).
+fn merge(
+    active: &mut Vec<usize>,
+    dist: &mut DistanceMatrix,
+    (i, j): (usize, usize),
+    node: usize,
+    new_dist: impl Fn(&DistanceMatrix, usize) -> f64,
+) {
+    let to_node: Vec<f64> = (0..active.len())
+        .filter(|&k| k != i && k != j)
+        .map(|k| new_dist(dist, k))
+        .collect();
+    let mut new_row = dist.remove(j);
+    dist.remove(i);
+    for (row, &d) in dist.iter_mut().zip(&to_node) {
+        row.remove(j);
+        row.remove(i);
+        row.push(d);
+    }
+    new_row.clear();
+    new_row.extend(to_node);
+    new_row.push(0.0);
+    dist.push(new_row);
+    active.remove(j);
+    active.remove(i);
+    active.push(node);
+}
+
+pub fn neighbour_joining(distances: &[Vec<f64>]) -> Tree {
+    let n = distances.len();
+    let mut tree = Tree::with_leaves(n);
+    let mut active: Vec<usize> = (0..n).collect();
+    let mut dist: DistanceMatrix = distances.to_vec();
+    while active.len() > 2 {
+        let r = active.len();
+        let sums: Vec<f64> = dist.iter().map(|row| row.iter().sum()).collect();
+        let (i, j) = closest_pair(r, |i, j| {
+            (r - 2) as f64 * dist[i][j] - sums[i] - sums[j]
+        });
+        let length_i = dist[i][j] / 2.0 + (sums[i] - sums[j]) / (2.0 * (r - 2) as f64);
+        let length_j = dist[i][j] - length_i;
+        // Negative lengths can occur with non-additive distances.
+        let node = tree.join(vec![
+            (active[i], length_i.max(0.0)),
+            (active[j], length_j.max(0.0)),
+        ]);
+        merge(&mut active, &mut dist, (i, j), node, |d, k| {
+            (d[i][k] + d[j][k] - d[i][j]) / 2.0
+        });
+    }
+    tree.root = match active[..] {
+        [a, b] => tree.join(vec![(a, dist[0][1] / 2.0), (b, dist[0][1] / 2.0)]),
+        _ => active[0],
+    };
+    tree
+}
+
+pub fn upgma(distances: &[Vec<f64>]) -> Tree {
+    let n = distances.len();
+    let mut tree = Tree::with_leaves(n);
+    let mut active: Vec<usize> = (0..n).collect();
+    let mut dist: DistanceMatrix = distances.to_vec();
+    // Indexed like the tree's nodes
+    let mut sizes = vec![1.0; n];
+    let mut heights = vec![0.0; n];
+    while active.len() > 1 {
+        let (i, j) = closest_pair(active.len(), |i, j| dist[i][j]);
+        let (a, b) = (active[i], active[j]);
+        let height = dist[i][j] / 2.0;
+        let node = tree.join(vec![
+            (a, (height - heights[a]).max(0.0)),
+            (b, (height - heights[b]).max(0.0)),
+        ]);
+        sizes.push(sizes[a] + sizes[b]);
+        heights.push(height);
+        merge(&mut active, &mut dist, (i, j), node, |d, k| {
+            (sizes[a] * d[i][k] + sizes[b] * d[j][k]) / (sizes[a] + sizes[b])
+        });
+    }
+    tree.root = active[0];
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::newick::{parse_newick, MAX_DEPTH};
+    use crate::tree::{neighbour_joining, upgma, Tree};
+
+    // The example of Saitou and Nei (1987), as given by Wikipedia's "Neighbor joining" article.
+    fn five_taxa() -> Vec<Vec<f64>> {
+        vec![
+            vec![0.0, 5.0, 9.0, 9.0, 8.0],
+            vec![5.0, 0.0, 10.0, 10.0, 9.0],
+            vec![9.0, 10.0, 0.0, 8.0, 7.0],
+            vec![9.0, 10.0, 8.0, 0.0, 3.0],
+            vec![8.0, 9.0, 7.0, 3.0, 0.0],
+        ]
+    }
+
+    #[test]
+    fn test_neighbour_joining() {
+        let tree = neighbour_joining(&five_taxa());
+        // a and b are joined first, with branches of length 2 and 3.
+        assert_eq!(tree.nodes[5].children, vec![(0, 2.0), (1, 3.0)]);
+        assert_eq!(tree.leaves(), vec![2, 0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_upgma() {
+        let mut tree = upgma(&five_taxa());
+        // d and e are the closest, then a and b.
+        assert_eq!(tree.nodes[5].children, vec![(3, 1.5), (4, 1.5)]);
+        assert_eq!(tree.nodes[6].children, vec![(0, 2.5), (1, 2.5)]);
+        assert_eq!(tree.leaves(), vec![0, 1, 2, 3, 4]);
+        tree.renumber_leaves(&[10, 11, 12, 13, 14]);
+        assert_eq!(tree.leaves(), vec![10, 11, 12, 13, 14]);
+    }
+
+    #[test]
+    fn test_dendrogram() {
+        let tree = upgma(&five_taxa());
+        // ((a,b),(c,(d,e))): inner nodes are midway between their first and last children (rounded
+        // up), two columns per level.
+        assert_eq!(
+            tree.dendrogram(8),
+            [
+                "┌─┬─────",
+                "│ └─────",
+                "└─┬─────",
+                "  └─┬───",
+                "    └───",
+            ]
+        );
+        // Levels are closer together when the width is too small.
+        assert_eq!(tree.dendrogram(4)[2], "└┬──");
+    }
+
+    #[test]
+    fn test_from_newick() {
+        let newick = parse_newick("((b:1,x:1):2,(c:1,(a:1,y:1):1):1);").unwrap();
+        let names = ["a", "b", "c"];
+        let tree = Tree::from_newick(&newick, |name| names.iter().position(|&n| n == name));
+        let tree = tree.unwrap();
+        assert_eq!(tree.leaves(), vec![1, 2, 0]);
+        // (b,x) and (a,y) are replaced by b and a, whose branches are extended.
+        let root = &tree.nodes[tree.root];
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.children[0].1, 3.0);
+        assert_eq!(tree.nodes[root.children[1].0].children[1].1, 2.0);
+        assert!(Tree::from_newick(&newick, |_| None).is_none());
+    }
+
+    #[test]
+    fn test_pruned() {
+        let tree = upgma(&five_taxa());
+        // ((a,b),(c,(d,e))) without b and e
+        let pruned = tree.pruned(|seq| seq != 1 && seq != 4).unwrap();
+        assert_eq!(pruned.leaves(), vec![0, 2, 3]);
+        assert_eq!(pruned.dendrogram(4), ["┌───", "└─┬─", "  └─"]);
+        assert!(tree.pruned(|_| false).is_none());
+    }
+
+    #[test]
+    fn test_deepest_tree() {
+        // A caterpillar tree as deep as Newick files may be, e.g. "(((0,1),2),3);" for depth 3
+        let mut text = "(".repeat(MAX_DEPTH) + "0";
+        for i in 1..=MAX_DEPTH {
+            text.push_str(&format!(",{})", i));
+        }
+        let newick = parse_newick(&(text + ";")).unwrap();
+        let tree = Tree::from_newick(&newick, |name| name.parse().ok()).unwrap();
+        let tree = tree.pruned(|seq| seq != 0).unwrap();
+        assert_eq!(tree.leaves(), (1..=MAX_DEPTH).collect::<Vec<_>>());
+        assert_eq!(tree.dendrogram(10).len(), MAX_DEPTH);
+    }
+}
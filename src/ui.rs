@@ -1,31 +1,50 @@
 // SPDX-License-Identifier: MIT
 // Copyright (c) 2025 Thomas Junier
 pub mod color_map;
-mod color_scheme;
+pub mod color_scheme;
 mod barchart;
 pub mod key_handling;
+pub mod keymap;
+pub mod mouse_handling;
 pub mod render;
+mod prompt;
+pub mod figure;
+pub mod ansi;
+pub mod background;
+mod selection;
 
 use std::{
     cmp::min,
     fmt,
+    io::{stdout, Write},
+    ops::Range,
 }; 
 
 use log::debug;
 
 use bitflags::bitflags;
 
-use ratatui::layout::Size;
+use ratatui::layout::{Rect, Size};
 use ratatui::style::{
     Color,
     Style,
 };
 
 use crate::{
+    config::Settings,
+    fasta::seq_id,
+    file_format::FileFormat,
+    goto::{parse_seq_ref, parse_target, residue_column, resolve_seq_ref, SeqRef, Target},
+    output::create_output,
+    ui::color_map::ColorMap,
     ui::color_scheme::{
         ColorScheme,
         Theme,
         },
+    ui::figure::{write_figure, FigureFormat},
+    ui::keymap::Keymap,
+    ui::prompt::{Prompt, PromptAction},
+    ui::selection::{osc52, Selection, SelectionKind},
     App,
 };
 
@@ -36,14 +55,38 @@ pub enum ZoomLevel {
     ZoomedOutAR,
 }
 
-#[derive(Debug)]
-enum BottomPanePosition {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BottomPanePosition {
     Adjacent,
     ScreenBottom,
 }
 
+// Residues that are identical to the reference's (in the same column) are shown as the match
+// character, so that only the differences stand out.
+#[derive(Clone, Copy, PartialEq)]
+enum Reference {
+    Consensus,
+    Sequence(usize), // index into the alignment's sequences
+}
+
+// A drag of the zoom box with the mouse: where it started (screen column and row), and where the
+// zoom box was then.
+#[derive(Clone, Copy)]
+struct ZoomboxDrag {
+    from: (u16, u16),
+    top_line: u16,
+    leftmost_col: u16,
+}
+
+// What n and N step through: motif hits, or sequences whose label matches.
 #[derive(Clone, Copy, PartialEq)]
-enum VideoMode {
+enum SearchKind {
+    Motif,
+    Label,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VideoMode {
     Direct,
     Inverse,
 }
@@ -78,6 +121,7 @@ pub struct UI<'a> {
     color_schemes: Vec<ColorScheme>,
     current_color_scheme_index: usize, 
     zoom_level: ZoomLevel,
+    initial_zoom_level: Option<ZoomLevel>, // From the settings, set on the first draw
     show_zoombox: bool,
     //zoombox_color: Style,
     show_zb_guides: bool,
@@ -90,20 +134,59 @@ pub struct UI<'a> {
     bottom_pane_height: u16,
     previous_bottom_pane_height: u16,
     bottom_pane_position: BottomPanePosition,
+    show_logo: bool,
+    show_identity_map: bool, // Instead of the alignment
+    show_tree: bool, // When the sequences are ordered by a tree
     // These cannot be known when the structure is initialized, so they are Options -- but it is
     // possible that they need not be stored at all, as they can in principle be computed when the
     // layout is known.
     aln_pane_size: Option<Size>,
     frame_size: Option<Size>, // whole app
+    // Where the label and alignment panes are on screen, for the mouse (also set when drawing).
+    label_pane_area: Option<Rect>,
+    aln_pane_area: Option<Rect>,
+    zoombox_drag: Option<ZoomboxDrag>,
+    focused_seq: Option<usize>, // index into the alignment's sequences
+    selection: Option<Selection>,
+    // Only in the TUI: elsewhere (e.g. --render-once), stdout is not the terminal, and the OSC 52
+    // sequence would end up in the output.
+    clipboard_enabled: bool,
+    keymap: Keymap,
     show_help: bool,
     full_screen: bool,
     message: String, // Simple, 1-line message (possibly just "", no need for Option IMHO)
+    prompt: Option<Prompt>,
+    last_search: SearchKind,
+    label_search: Option<String>,
+    label_row: Option<usize>, // Row (in the current ordering) of the current label match
+    reference: Option<Reference>,
+    match_char: char,
+    reference_numbering: bool, // Number columns after the reference's residues
     video_mode: VideoMode,
 }
 
+// Regex errors span several lines; the last one says what is wrong.
+fn bad_pattern_message(pattern: &str, error: regex::Error) -> String {
+    let reason = error.to_string();
+    let reason = reason.lines().last().unwrap_or("").trim().to_string();
+    format!(" Bad pattern '{}': {} ", pattern, reason)
+}
+
+// Rows of the sequence logo, when shown (see render::logo_lines()).
+const LOGO_HEIGHT: u16 = 4;
+
+// Height of the bottom pane when shown: position, consensus and conservation (plus the tick marks
+// and bottom border), as well as one line per column annotation (e.g. Stockholm's #=GC lines) and
+// the sequence logo, if shown.
+fn full_bottom_pane_height(app: &App, show_logo: bool) -> u16 {
+    let logo_height = if show_logo { LOGO_HEIGHT } else { 0 };
+    5 + app.alignment.annotations.column.len() as u16 + logo_height
+}
+
 impl<'a> UI<'a> {
     pub fn new(app: &'a mut App) -> Self {
         let macromolecule_type = app.alignment.macromolecule_type();
+        let bottom_pane_height = full_bottom_pane_height(app, false);
         UI {
             app,
             color_schemes: vec![
@@ -113,6 +196,7 @@ impl<'a> UI<'a> {
             ],
             current_color_scheme_index: 0,
             zoom_level: ZoomLevel::ZoomedIn,
+            initial_zoom_level: None,
             show_zoombox: true,
             show_zb_guides: true,
             show_scrollbars: true,
@@ -121,14 +205,31 @@ impl<'a> UI<'a> {
             leftmost_col: 0,
             label_pane_width: 18, // Reasonable default, I'd say...
             previous_label_pane_width: 0,
-            bottom_pane_height: 5,
+            bottom_pane_height,
             previous_bottom_pane_height: 0,
             bottom_pane_position: BottomPanePosition::Adjacent,
+            show_logo: false,
+            show_identity_map: false,
+            show_tree: true,
             aln_pane_size: None,
             frame_size: None,
+            label_pane_area: None,
+            aln_pane_area: None,
+            zoombox_drag: None,
+            focused_seq: None,
+            selection: None,
+            clipboard_enabled: false,
+            keymap: Keymap::default(),
             show_help: false,
             full_screen: false,
             message: " Press '?' for help ".into(),
+            prompt: None,
+            last_search: SearchKind::Motif,
+            label_search: None,
+            label_row: None,
+            reference: None,
+            match_char: '.',
+            reference_numbering: false,
             video_mode: VideoMode::Inverse,
         }
     }
@@ -143,26 +244,18 @@ impl<'a> UI<'a> {
 
     fn max_nb_seq_shown(&self) -> u16 {
         let height = self.aln_pane_size.unwrap().height;
-        if height >= 2 {
-            // border, should later be a constant or a field of UI
-            height - 2
-        } else {
-            // Set to null (prevents display) if not enough room
-            // NOTE: this causes v_ratio() to return 0, which in turn causes the number of retained
-            // sequences to be 0, causing render::every_nth() to crash. Maybe the minimum should be
-            // 2, not 0. TODO: prepare more tests (esp. w/ small sets), change to 2, and check.
-            // Then do the same for max_nb_col_shown().
-            0
-        }
+        // 2: borders, should later be a constant or a field of UI. Saturates to null (prevents
+        // display) if not enough room.
+        // NOTE: this causes v_ratio() to return 0, which in turn causes the number of retained
+        // sequences to be 0, causing render::every_nth() to crash. Maybe the minimum should be
+        // 2, not 0. TODO: prepare more tests (esp. w/ small sets), change to 2, and check.
+        // Then do the same for max_nb_col_shown().
+        height.saturating_sub(2)
     }
 
     fn max_nb_col_shown(&self) -> u16 {
         let width = self.aln_pane_size.unwrap().width;
-        if width >= 2 {
-            width - 2
-        } else {
-            0
-        }
+        width.saturating_sub(2)
     }
 
     // Resizing (as when the user resizes the terminal window where Termal runs) affects
@@ -226,11 +319,7 @@ impl<'a> UI<'a> {
 
     pub fn reduce_label_pane(&mut self, amount: u16) {
         // TODO: heed the border width (not sure if we'll keep them)
-        self.label_pane_width = if self.label_pane_width > amount {
-            self.label_pane_width - amount
-        } else {
-            0
-        }
+        self.label_pane_width = self.label_pane_width.saturating_sub(amount);
     }
 
     // Bottom pane dimensions
@@ -245,7 +334,24 @@ impl<'a> UI<'a> {
     }
 
     pub fn show_bottom_pane(&mut self) {
-        self.bottom_pane_height = 5;
+        self.bottom_pane_height = full_bottom_pane_height(self.app, self.show_logo);
+    }
+
+    // The tree pane is only there while the sequences are ordered by a tree (see 'o').
+    pub fn toggle_tree_pane(&mut self) {
+        if self.app.tree().is_none() {
+            self.message = " No tree: order the sequences by one first (press 'd' or 'o') ".into();
+            return;
+        }
+        self.show_tree = !self.show_tree;
+    }
+
+    // The bottom pane grows or shrinks accordingly, if it is shown.
+    pub fn toggle_logo(&mut self) {
+        self.show_logo = !self.show_logo;
+        if self.bottom_pane_height > 0 {
+            self.show_bottom_pane();
+        }
     }
 
     // ****************************************************************
@@ -285,6 +391,16 @@ impl<'a> UI<'a> {
         }
     }
 
+    // Whether the alignment fits is only known once the layout is (see render_ui()). As with
+    // cycle_zoom(), an alignment that fits is not zoomed out.
+    fn set_zoom_level(&mut self, zoom_level: ZoomLevel) {
+        self.zoom_level = if self.aln_wrt_seq_pane() == AlnWRTSeqPane::Fits {
+            ZoomLevel::ZoomedIn
+        } else {
+            zoom_level
+        };
+    }
+
     pub fn h_ratio(&self) -> f64 {
         self.max_nb_col_shown() as f64 / self.app.aln_len() as f64
     }
@@ -471,6 +587,45 @@ impl<'a> UI<'a> {
         self.current_color_scheme_index = self.color_schemes.len() - 1;
     }
 
+    pub fn set_theme(&mut self, theme: Theme) {
+        if let Some(index) = self.color_schemes.iter().position(|cs| cs.theme == theme) {
+            self.current_color_scheme_index = index;
+        }
+    }
+
+    // Custom maps are added to all but the monochrome color scheme (which is last), if they suit
+    // the alignment's sequences.
+    pub fn add_colormap(&mut self, colormap: ColorMap) {
+        if !colormap.applies_to(self.app.alignment.macromolecule_type()) {
+            return;
+        }
+        let n = self.color_schemes.len() - 1;
+        for cs in &mut self.color_schemes[..n] {
+            cs.residue_colormaps.push(colormap.clone());
+        }
+    }
+
+    // Makes the named colormap the current one in the color schemes that have it.
+    pub fn set_colormap(&mut self, name: &str) -> Result<(), String> {
+        let mut found = false;
+        for cs in &mut self.color_schemes {
+            let index = cs
+                .residue_colormaps
+                .iter()
+                .position(|cm| cm.name.eq_ignore_ascii_case(name));
+            if let Some(index) = index {
+                cs.residue_colormap_index = index;
+                found = true;
+            }
+        }
+        if found {
+            Ok(())
+        } else {
+            let seq_type = format!("{:?}", self.app.alignment.macromolecule_type()).to_lowercase();
+            Err(format!("no colormap '{}' for {} sequences", name, seq_type))
+        }
+    }
+
     // FIXME: this method is in the singular, but the one it delegates to is in the plural. Call it
     // next_... instead of cycle_... Also change other cycle*, replace with next_ and prev_.
 
@@ -513,6 +668,261 @@ impl<'a> UI<'a> {
         self.app.cycle_ordering_criterion();
     }
 
+    // Guide trees can take a while: they are refused (with a message) for large alignments.
+    pub fn cycle_guide_tree(&mut self) {
+        if let Err(e) = self.app.cycle_guide_tree() {
+            self.message = format!(" No guide tree: {} ", e);
+        }
+    }
+
+    pub fn prompt_for_similarity(&mut self) {
+        self.prompt = Some(Prompt::new(
+            "Order by similarity to (#number or ID): ",
+            PromptAction::Similarity,
+        ));
+    }
+
+    // The chosen sequence comes first, so the view goes back to the top.
+    fn order_by_similarity(&mut self, input: &str) -> Result<String, String> {
+        let seq = self.seq_from_input(input)?;
+        self.app.order_by_similarity(seq);
+        self.top_line = 0;
+        Ok(format!(
+            " Ordered by identity to {} ({}) ",
+            seq_id(&self.app.alignment.headers[seq]),
+            self.app.gap_handling()
+        ))
+    }
+
+    // ****************************************************************
+    // Identity map: the pairwise identities of the shown sequences, instead of the alignment (see
+    // render::render_identity_map()).
+
+    pub fn toggle_identity_map(&mut self) {
+        self.show_identity_map = !self.show_identity_map;
+        self.message = if self.show_identity_map {
+            " Pairwise identity: click a cell for its value ".into()
+        } else {
+            String::new()
+        };
+    }
+
+    // ****************************************************************
+    // Insert columns (A2M/A3M)
+
+    pub fn toggle_insert_columns(&mut self) {
+        if !self.app.has_insert_columns() {
+            self.message = " No insert columns in this alignment ".into();
+            return;
+        }
+        self.app.toggle_insert_columns();
+        self.message = if self.app.insert_columns_shown() {
+            " Insert columns shown ".into()
+        } else {
+            " Insert columns hidden ".into()
+        };
+    }
+
+    // ****************************************************************
+    // Prompt
+
+    // The alignment columns that are visible when zoomed in, i.e. those in the zoom box when zoomed
+    // out.
+    pub fn visible_columns(&self) -> Range<usize> {
+        let left = self.leftmost_col as usize;
+        let right = min(
+            self.leftmost_col + self.max_nb_col_shown(),
+            self.app.aln_len(),
+        ) as usize;
+        left..right
+    }
+
+    // Likewise for sequences (in the current order).
+    pub fn visible_rows(&self) -> Range<usize> {
+        let top = self.top_line as usize;
+        let bottom = min(self.top_line + self.max_nb_seq_shown(), self.app.num_seq()) as usize;
+        top..bottom
+    }
+
+    pub fn prompt_for_export(&mut self, visible_columns_only: bool) {
+        let (label, columns) = if visible_columns_only {
+            let columns = self.visible_columns();
+            let label = format!(
+                "Export columns {}-{} to: ",
+                columns.start + 1,
+                columns.end
+            );
+            (label, Some(columns))
+        } else {
+            ("Export to: ".to_string(), None)
+        };
+        self.prompt = Some(Prompt::new(&label, PromptAction::Export(columns)));
+    }
+
+    pub fn prompt_for_figure(&mut self, visible_region_only: bool) {
+        let (rows, cols) = if visible_region_only {
+            (self.visible_rows(), self.visible_columns())
+        } else {
+            (0..self.app.num_seq() as usize, 0..self.app.aln_len() as usize)
+        };
+        let label = if visible_region_only {
+            "Save zoom box picture (.svg, .html) to: "
+        } else {
+            "Save picture (.svg, .html) to: "
+        };
+        self.prompt = Some(Prompt::new(label, PromptAction::Figure(rows, cols)));
+    }
+
+    // The format is given by the file's extension (see FigureFormat::from_extension()).
+    pub fn write_figure_to_file(
+        &self,
+        path: &str,
+        rows: Range<usize>,
+        cols: Range<usize>,
+    ) -> std::io::Result<FigureFormat> {
+        let format = FigureFormat::from_extension(path);
+        let mut writer = create_output(path)?;
+        write_figure(self, &mut writer, format, rows, cols)?;
+        writer.flush()?;
+        Ok(format)
+    }
+
+    pub fn prompt_for_search(&mut self) {
+        self.prompt = Some(Prompt::new("/", PromptAction::Search));
+    }
+
+    pub fn prompt_for_label_search(&mut self) {
+        self.prompt = Some(Prompt::new("Find label: ", PromptAction::LabelSearch));
+    }
+
+    pub fn prompt_for_go_to(&mut self) {
+        self.prompt = Some(Prompt::new(":", PromptAction::GoTo));
+    }
+
+    pub fn prompt_for_reference(&mut self) {
+        self.prompt = Some(Prompt::new(
+            "Reference (#number, ID or 'consensus'): ",
+            PromptAction::Reference,
+        ));
+    }
+
+    pub fn prompt_for_label_filter(&mut self) {
+        self.prompt = Some(Prompt::new("Show labels matching: ", PromptAction::LabelFilter));
+    }
+
+    pub fn cancel_prompt(&mut self) {
+        self.prompt = None;
+    }
+
+    // Runs the prompt's action on the input, and reports the outcome in the message line.
+    pub fn submit_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        let input = prompt.input.trim();
+        // An empty file name is taken as a change of mind (an empty pattern, however, clears the
+        // search or filter).
+        let needs_input = matches!(
+            prompt.action,
+            PromptAction::Export(_) | PromptAction::ExportSelection(..) | PromptAction::Figure(..)
+        );
+        if input.is_empty() && needs_input {
+            return;
+        }
+        match prompt.action {
+            PromptAction::Export(columns) => {
+                self.message = match self.app.export_to_file(input, None, columns) {
+                    Ok(format) => format!(
+                        " Wrote {} sequences to {} ({}) ",
+                        self.app.num_seq(),
+                        input,
+                        format
+                    ),
+                    Err(e) => format!(" Could not write {}: {} ", input, e),
+                };
+            }
+            PromptAction::Search => {
+                self.last_search = SearchKind::Motif;
+                if input.is_empty() {
+                    self.app.clear_search();
+                    self.message = " Search cleared ".into();
+                    return;
+                }
+                match self.app.search(input) {
+                    Ok(0) => self.message = format!(" No hits for '{}' ", input),
+                    Ok(_) => self.jump_to_hit(true),
+                    Err(e) => self.message = bad_pattern_message(input, e),
+                }
+            }
+            PromptAction::LabelSearch => {
+                if input.is_empty() {
+                    return;
+                }
+                self.last_search = SearchKind::Label;
+                self.label_search = Some(input.to_string());
+                self.label_row = None;
+                self.jump_to_label(true);
+            }
+            PromptAction::Reference => {
+                self.message = match self.set_reference(input) {
+                    Ok(msg) => msg,
+                    Err(e) => format!(" Reference '{}': {} ", input, e),
+                };
+            }
+            PromptAction::Similarity => {
+                if input.is_empty() {
+                    return;
+                }
+                self.message = match self.order_by_similarity(input) {
+                    Ok(msg) => msg,
+                    Err(e) => format!(" Order by similarity to '{}': {} ", input, e),
+                };
+            }
+            PromptAction::GoTo => {
+                self.message = match self.go_to(input) {
+                    Ok(msg) => msg,
+                    Err(e) => format!(" Go to '{}': {} ", input, e),
+                };
+            }
+            PromptAction::LabelFilter => {
+                if input.is_empty() {
+                    self.app.clear_label_filter();
+                    self.message = " Showing all sequences ".into();
+                    return;
+                }
+                match self.app.set_label_filter(input) {
+                    Ok(0) => self.message = format!(" No labels match '{}' ", input),
+                    Ok(n) => {
+                        self.top_line = 0;
+                        self.message = format!(
+                            " Showing {}/{} sequences ",
+                            n,
+                            self.app.alignment.num_seq()
+                        );
+                    }
+                    Err(e) => self.message = bad_pattern_message(input, e),
+                }
+            }
+            PromptAction::ExportSelection(seqs, columns) => {
+                self.message = match self.app.export_to_file(input, Some(&seqs), Some(columns)) {
+                    Ok(format) => format!(
+                        " Wrote {} selected sequences to {} ({}) ",
+                        seqs.len(),
+                        input,
+                        format
+                    ),
+                    Err(e) => format!(" Could not write {}: {} ", input, e),
+                };
+            }
+            PromptAction::Figure(rows, cols) => {
+                self.message = match self.write_figure_to_file(input, rows, cols) {
+                    Ok(format) => format!(" Wrote {} picture to {} ", format, input),
+                    Err(e) => format!(" Could not write {}: {} ", input, e),
+                };
+            }
+        }
+    }
+
     // ****************************************************************
 
     pub fn disable_scrollbars(&mut self) {
@@ -625,6 +1035,425 @@ impl<'a> UI<'a> {
         self.leftmost_col = self.max_leftmost_col()
     }
 
+    // The following scroll (as little as possible) so that the given sequence (as a position in
+    // the current ordering) or columns are visible. When zoomed out, they move the zoom box.
+
+    fn show_row(&mut self, row: usize) {
+        let row = row as u16;
+        if row < self.top_line || row >= self.top_line + self.max_nb_seq_shown() {
+            self.top_line = min(row, self.max_top_line());
+        }
+    }
+
+    fn show_cols(&mut self, cols: Range<usize>) {
+        let (start, end) = (cols.start as u16, cols.end as u16);
+        if start < self.leftmost_col || end > self.leftmost_col + self.max_nb_col_shown() {
+            self.leftmost_col = min(start, self.max_leftmost_col());
+        }
+    }
+
+    // n and N
+    pub fn jump_to_match(&mut self, forward: bool) {
+        match self.last_search {
+            SearchKind::Motif => self.jump_to_hit(forward),
+            SearchKind::Label => self.jump_to_label(forward),
+        }
+    }
+
+    // Motif search hits
+
+    pub fn jump_to_hit(&mut self, forward: bool) {
+        let Some(pattern) = self.app.search_pattern().map(str::to_string) else {
+            self.message = " No search - press '/' ".into();
+            return;
+        };
+        let num_hits = self.app.num_hits();
+        let Some((i, hit)) = self.app.step_hit(forward) else {
+            self.message = format!(" No hits for '{}' ", pattern);
+            return;
+        };
+        let (seq, cols) = (hit.seq, hit.cols.clone());
+        let row = self.app.ordering.iter().position(|&s| s == seq).unwrap();
+        self.message = format!(
+            " Hit {}/{} for '{}': {}, col. {}-{} ",
+            i + 1,
+            num_hits,
+            pattern,
+            self.app.alignment.headers[seq],
+            cols.start + 1,
+            cols.end
+        );
+        self.show_row(row);
+        self.show_cols(cols);
+    }
+
+    // Go-to command. Returns a message that describes where we went.
+
+    fn go_to(&mut self, input: &str) -> Result<String, String> {
+        // Position of a sequence in the current ordering
+        let row_of = |ui: &UI, seq: usize| {
+            ui.app
+                .ordering
+                .iter()
+                .position(|&s| s == seq)
+                .ok_or_else(|| format!("sequence #{} is filtered out", seq + 1))
+        };
+        match parse_target(input)? {
+            Target::Column(col) => {
+                if col >= self.app.aln_len() as usize {
+                    return Err(format!("there are only {} columns", self.app.aln_len()));
+                }
+                self.show_cols(col..col + 1);
+                Ok(format!(" Column {} ", col + 1))
+            }
+            Target::Sequence(seq_ref) => {
+                let seq = resolve_seq_ref(&self.app.alignment, &seq_ref)?;
+                let row = row_of(self, seq)?;
+                self.show_row(row);
+                Ok(format!(
+                    " Sequence #{}: {} ",
+                    seq + 1,
+                    self.app.alignment.headers[seq]
+                ))
+            }
+            Target::Residue(seq_ref, pos) => {
+                let seq = resolve_seq_ref(&self.app.alignment, &seq_ref)?;
+                let sequence = &self.app.alignment.sequences[seq];
+                let id = seq_id(&self.app.alignment.headers[seq]).to_string();
+                let col = residue_column(sequence, pos)
+                    .ok_or_else(|| format!("{} has fewer than {} residues", id, pos + 1))?;
+                let residue = sequence.as_bytes()[col] as char;
+                let row = row_of(self, seq)?;
+                self.show_row(row);
+                self.show_cols(col..col + 1);
+                Ok(format!(
+                    " Residue {} of {} ({}) is in column {} ",
+                    pos + 1,
+                    id,
+                    residue,
+                    col + 1
+                ))
+            }
+        }
+    }
+
+    // ****************************************************************
+    // Settings (from the configuration file)
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    // Replaces the initial help hint, e.g. to report how a tree given on the command line matched.
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+
+    // Colormaps are only set for the alignment's kind of sequences; their names are not case
+    // sensitive.
+    pub fn apply_settings(&mut self, settings: &Settings) -> Result<(), String> {
+        self.initial_zoom_level = settings.zoom_level;
+        if let Some(video_mode) = settings.video_mode {
+            self.video_mode = video_mode;
+        }
+        if let Some(width) = settings.label_pane_width {
+            self.set_label_pane_width(width);
+        }
+        if let Some(position) = settings.bottom_pane_position {
+            self.bottom_pane_position = position;
+        }
+        let seq_type = self.app.alignment.macromolecule_type();
+        for (_, name) in settings.colormaps.iter().filter(|(t, _)| *t == seq_type) {
+            self.set_colormap(name)?;
+        }
+        Ok(())
+    }
+
+    // Reference sequence
+
+    pub fn set_match_char(&mut self, c: char) {
+        self.match_char = c;
+    }
+
+    fn set_reference(&mut self, input: &str) -> Result<String, String> {
+        if input.is_empty() {
+            self.reference = None;
+            return Ok(" No reference ".into());
+        }
+        if "consensus".starts_with(&input.to_lowercase()) {
+            self.reference = Some(Reference::Consensus);
+            return Ok(" Reference: consensus ".into());
+        }
+        let seq = self.seq_from_input(input)?;
+        self.reference = Some(Reference::Sequence(seq));
+        Ok(format!(" Reference: {} ", self.app.alignment.headers[seq]))
+    }
+
+    // A sequence, as given in a prompt: #12, an ID, or (as there is no column to go to) a plain
+    // number.
+    fn seq_from_input(&self, input: &str) -> Result<usize, String> {
+        let seq_ref = match input.parse::<usize>() {
+            Ok(n) if n > 0 => SeqRef::Number(n - 1),
+            _ => parse_seq_ref(input)?,
+        };
+        resolve_seq_ref(&self.app.alignment, &seq_ref)
+    }
+
+    // The reference's residues, one per column.
+    fn reference_residues(&self) -> Option<&str> {
+        match self.reference? {
+            Reference::Consensus => Some(self.app.consensus()),
+            Reference::Sequence(seq) => Some(&self.app.alignment.sequences[seq]),
+        }
+    }
+
+    // The reference's sequence index, if it is a sequence (which is then shown as is).
+    fn reference_seq(&self) -> Option<usize> {
+        match self.reference? {
+            Reference::Consensus => None,
+            Reference::Sequence(seq) => Some(seq),
+        }
+    }
+
+    fn reference_name(&self) -> Option<String> {
+        match self.reference? {
+            Reference::Consensus => Some("consensus".to_string()),
+            Reference::Sequence(seq) => Some(seq_id(&self.app.alignment.headers[seq]).to_string()),
+        }
+    }
+
+    pub fn toggle_reference_numbering(&mut self) {
+        self.reference_numbering = !self.reference_numbering;
+        self.message = match (self.reference_numbering, self.reference_name()) {
+            (true, Some(name)) => format!(" Positions: residues of {} ", name),
+            (true, None) => " Positions: residues of the reference (none set - press 'R') ".into(),
+            (false, _) => " Positions: alignment columns ".into(),
+        };
+    }
+
+    // Label search
+
+    pub fn jump_to_label(&mut self, forward: bool) {
+        let Some(pattern) = self.label_search.clone() else {
+            return;
+        };
+        let rows = match self.app.find_labels(&pattern) {
+            Ok(rows) => rows,
+            Err(e) => {
+                self.message = bad_pattern_message(&pattern, e);
+                return;
+            }
+        };
+        // Without a current match, the search starts at the top line (inclusive).
+        let target = if forward {
+            let after = self.label_row.map_or(self.top_line as isize - 1, |r| r as isize);
+            rows.iter()
+                .position(|&r| r as isize > after)
+                .or((!rows.is_empty()).then_some(0))
+        } else {
+            let before = self.label_row.unwrap_or(self.top_line as usize);
+            rows.iter()
+                .rposition(|&r| r < before)
+                .or(rows.len().checked_sub(1))
+        };
+        let Some(i) = target else {
+            self.message = format!(" No labels match '{}' ", pattern);
+            return;
+        };
+        let row = rows[i];
+        self.label_row = Some(row);
+        self.message = format!(
+            " Label {}/{} for '{}': {} ",
+            i + 1,
+            rows.len(),
+            pattern,
+            self.app.alignment.headers[self.app.ordering[row]]
+        );
+        self.show_row(row);
+    }
+
+    pub fn toggle_stats_on_subset(&mut self) {
+        self.app.toggle_stats_on_subset();
+        let all_shown = self.app.label_filter().is_none() && self.app.num_hidden() == 0;
+        self.message = match (self.app.stats_are_on_subset(), all_shown) {
+            (true, _) => " Statistics of the shown sequences ".into(),
+            (false, false) => " Statistics of the whole alignment ".into(),
+            (false, true) => " Statistics of the whole alignment (all shown) ".into(),
+        };
+    }
+
+    // Selection (see selection.rs)
+
+    // Starts a selection at the focused sequence if it is on screen, else at the top line. The key
+    // that started the selection ends it, and the other one switches its kind.
+    pub fn toggle_selection(&mut self, kind: SelectionKind) {
+        if let Some(selection) = self.selection.as_mut() {
+            if selection.kind == kind {
+                self.end_selection();
+            } else {
+                selection.kind = kind;
+                self.message = self.selection_message();
+            }
+            return;
+        }
+        let focused_row = self
+            .focused_seq
+            .and_then(|seq| self.app.ordering.iter().position(|&s| s == seq))
+            .filter(|row| self.visible_rows().contains(row));
+        let row = focused_row.unwrap_or(self.top_line as usize);
+        self.selection = Some(Selection::new(kind, row, self.leftmost_col as usize));
+        self.message = self.selection_message();
+    }
+
+    pub fn end_selection(&mut self) {
+        self.selection = None;
+        self.message = String::new();
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.selection.is_some()
+    }
+
+    fn selection_message(&self) -> String {
+        let Some(selection) = self.selection else {
+            return String::new();
+        };
+        let rows = selection.rows();
+        let cols = selection.cols(self.app.aln_len() as usize);
+        match selection.kind {
+            SelectionKind::Rows => format!(" -- VISUAL LINE -- {} sequences ", rows.len()),
+            SelectionKind::Block => format!(
+                " -- VISUAL BLOCK -- {} sequences x {} columns ({}-{}) ",
+                rows.len(),
+                cols.len(),
+                cols.start + 1,
+                cols.end
+            ),
+        }
+    }
+
+    // Moves the selection's cursor (clamped to the alignment), scrolling so that it is visible.
+    pub fn move_selection_cursor(&mut self, rows: isize, cols: isize) {
+        // Readers reject empty alignments, but there is nothing to clamp to if one got through.
+        let (Some(max_row), Some(max_col)) = (
+            (self.app.num_seq() as usize).checked_sub(1),
+            (self.app.aln_len() as usize).checked_sub(1),
+        ) else {
+            return;
+        };
+        let Some(selection) = self.selection.as_mut() else {
+            return;
+        };
+        let (row, col) = selection.cursor;
+        let row = row.saturating_add_signed(rows).min(max_row);
+        let col = col.saturating_add_signed(cols).min(max_col);
+        selection.cursor = (row, col);
+        self.show_row(row);
+        self.show_cols(col..col + 1);
+        self.message = self.selection_message();
+    }
+
+    // The selected sequences (as indices into the alignment), in display order, and columns.
+    fn selected(&self) -> Option<(Vec<usize>, Range<usize>)> {
+        let selection = self.selection?;
+        // The number of shown sequences may have changed since the selection was made.
+        let rows = selection.rows();
+        let rows = rows.start.min(self.app.ordering.len())..rows.end.min(self.app.ordering.len());
+        let seqs = self.app.ordering[rows].to_vec();
+        Some((seqs, selection.cols(self.app.aln_len() as usize)))
+    }
+
+    // Copies the selection, as FastA, to the clipboard of the terminal (see osc52()).
+    pub fn enable_clipboard(&mut self) {
+        self.clipboard_enabled = true;
+    }
+
+    pub fn copy_selection(&mut self) {
+        let Some((seqs, cols)) = self.selected() else {
+            return;
+        };
+        if !self.clipboard_enabled {
+            self.message = " Copying only works in the TUI ".into();
+            return;
+        }
+        let mut fasta: Vec<u8> = Vec::new();
+        let copied = self
+            .app
+            .export_seqs(&mut fasta, FileFormat::FastA, &seqs, Some(cols))
+            .and_then(|_| {
+                let mut out = stdout();
+                out.write_all(osc52(&String::from_utf8_lossy(&fasta)).as_bytes())?;
+                out.flush()
+            });
+        self.message = match copied {
+            Ok(_) => format!(" Copied {} sequences to the clipboard ", seqs.len()),
+            Err(e) => format!(" Could not copy: {} ", e),
+        };
+    }
+
+    pub fn prompt_for_selection_export(&mut self) {
+        let Some((seqs, cols)) = self.selected() else {
+            return;
+        };
+        self.prompt = Some(Prompt::new(
+            "Export selection to: ",
+            PromptAction::ExportSelection(seqs, cols),
+        ));
+    }
+
+    pub fn show_selection_stats(&mut self) {
+        let Some((seqs, cols)) = self.selected() else {
+            return;
+        };
+        if seqs.is_empty() {
+            return;
+        }
+        let stats = self.app.block_stats(&seqs, cols.clone());
+        // The consensus is only shown if it fits on the message line.
+        let consensus = if stats.consensus.len() <= 20 {
+            format!(" | consensus {}", stats.consensus)
+        } else {
+            String::new()
+        };
+        self.message = format!(
+            " {} sequences x {} columns | {:.1}% id to consensus | {:.1}% gaps{} ",
+            seqs.len(),
+            cols.len(),
+            100.0 * stats.mean_identity,
+            100.0 * stats.gap_fraction,
+            consensus
+        );
+    }
+
+    // Hides the selected sequences (or, if `others`, all the other shown ones), which ends the
+    // selection.
+    pub fn hide_selection(&mut self, others: bool) {
+        let Some((seqs, _)) = self.selected() else {
+            return;
+        };
+        let num_hidden = if others {
+            self.app.show_only(&seqs)
+        } else {
+            self.app.hide_seqs(&seqs)
+        };
+        self.selection = None;
+        self.message = if num_hidden == 0 && !others {
+            " Cannot hide all the sequences ".into()
+        } else {
+            format!(
+                " Hid {} sequences ({}/{} shown) - 'X' shows them all ",
+                num_hidden,
+                self.app.num_seq(),
+                self.app.alignment.num_seq()
+            )
+        };
+    }
+
+    pub fn unhide_all(&mut self) {
+        let num_hidden = self.app.unhide_all();
+        self.message = format!(" Showing {} hidden sequences again ", num_hidden);
+    }
+
     // Debugging
 
     pub fn assert_invariants(&self) {
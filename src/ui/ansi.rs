@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Renders a frame buffer (e.g. from ratatui's TestBackend) as text with ANSI escape sequences, so
+ * that a snapshot of the UI can be viewed with `cat` or `less -R`, or embedded in other documents.
+ * Each line ends with a reset, so lines can be taken separately. */
+
+use ratatui::{
+    buffer::Buffer,
+    style::{Color, Modifier, Style},
+};
+
+const RESET: &str = "\x1b[0m";
+
+// SGR parameters for a foreground color; background ones are the same plus 10.
+fn color_params(color: Color) -> String {
+    match color {
+        Color::Reset => "39".to_string(),
+        Color::Black => "30".to_string(),
+        Color::Red => "31".to_string(),
+        Color::Green => "32".to_string(),
+        Color::Yellow => "33".to_string(),
+        Color::Blue => "34".to_string(),
+        Color::Magenta => "35".to_string(),
+        Color::Cyan => "36".to_string(),
+        Color::Gray => "37".to_string(),
+        Color::DarkGray => "90".to_string(),
+        Color::LightRed => "91".to_string(),
+        Color::LightGreen => "92".to_string(),
+        Color::LightYellow => "93".to_string(),
+        Color::LightBlue => "94".to_string(),
+        Color::LightMagenta => "95".to_string(),
+        Color::LightCyan => "96".to_string(),
+        Color::White => "97".to_string(),
+        Color::Indexed(i) => format!("38;5;{}", i),
+        Color::Rgb(r, g, b) => format!("38;2;{};{};{}", r, g, b),
+    }
+}
+
+fn bg_params(color: Color) -> String {
+    let fg = color_params(color);
+    match fg.split_once(';') {
+        // 38;5;... -> 48;5;...
+        Some((_, rest)) => format!("48;{}", rest),
+        None => (fg.parse::<u8>().unwrap() + 10).to_string(),
+    }
+}
+
+const MODIFIER_PARAMS: [(Modifier, &str); 6] = [
+    (Modifier::BOLD, "1"),
+    (Modifier::DIM, "2"),
+    (Modifier::ITALIC, "3"),
+    (Modifier::UNDERLINED, "4"),
+    (Modifier::REVERSED, "7"),
+    (Modifier::CROSSED_OUT, "9"),
+];
+
+// A complete SGR sequence (starting from a reset), so that it does not depend on the previous one.
+fn sgr(style: Style) -> String {
+    let mut params = vec!["0".to_string()];
+    let modifier = style.add_modifier;
+    for (m, p) in MODIFIER_PARAMS {
+        if modifier.contains(m) {
+            params.push(p.to_string());
+        }
+    }
+    params.push(color_params(style.fg.unwrap_or(Color::Reset)));
+    params.push(bg_params(style.bg.unwrap_or(Color::Reset)));
+    format!("\x1b[{}m", params.join(";"))
+}
+
+pub fn buffer_to_ansi(buffer: &Buffer) -> String {
+    let area = buffer.area;
+    let mut out = String::new();
+    for y in area.top()..area.bottom() {
+        let mut current: Option<Style> = None;
+        for x in area.left()..area.right() {
+            let cell = &buffer[(x, y)];
+            if cell.skip {
+                continue;
+            }
+            let style = cell.style();
+            if current != Some(style) {
+                out.push_str(&sgr(style));
+                current = Some(style);
+            }
+            out.push_str(cell.symbol());
+        }
+        out.push_str(RESET);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Rect;
+
+    #[test]
+    fn test_bg_params() {
+        assert_eq!(bg_params(Color::Red), "41");
+        assert_eq!(bg_params(Color::White), "107");
+        assert_eq!(bg_params(Color::Indexed(200)), "48;5;200");
+        assert_eq!(bg_params(Color::Rgb(1, 2, 3)), "48;2;1;2;3");
+    }
+
+    #[test]
+    fn test_buffer_to_ansi() {
+        let mut buffer = Buffer::empty(Rect::new(0, 0, 4, 2));
+        buffer.set_string(0, 0, "ab", Style::default().fg(Color::Red));
+        buffer.set_string(
+            2,
+            1,
+            "c",
+            Style::default().bg(Color::Blue).add_modifier(Modifier::BOLD),
+        );
+        assert_eq!(
+            buffer_to_ansi(&buffer),
+            "\x1b[0;31;49mab\x1b[0;39;49m  \x1b[0m\n\
+             \x1b[0;39;49m  \x1b[0;1;39;44mc\x1b[0;39;49m \x1b[0m\n"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Guessing the theme from the terminal's background color. The terminal is asked for it (OSC 11);
+ * terminals that do not answer are asked for their attributes (DA1) right after, which all of them
+ * answer, so that we need not wait for the timeout. If there is no answer, the COLORFGBG variable
+ * (set by some terminals, e.g. rxvt and Konsole) may give the background's color index. */
+
+use std::env;
+
+use crate::ui::color_scheme::Theme;
+
+// The terminal must be in raw mode, otherwise the answer would be echoed (and only sent on Enter).
+pub fn detect_theme() -> Option<Theme> {
+    query_background()
+        .map(theme_from_rgb)
+        .or_else(|| theme_from_colorfgbg(&env::var("COLORFGBG").ok()?))
+}
+
+fn theme_from_rgb((r, g, b): (f64, f64, f64)) -> Theme {
+    // Relative luminance (ITU-R BT.709)
+    if 0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5 {
+        Theme::Light
+    } else {
+        Theme::Dark
+    }
+}
+
+// E.g. "15;0" (white on black) or "0;default;15": the background comes last; 7 (light gray) and
+// the bright colors, except 8 (dark gray), are light.
+fn theme_from_colorfgbg(value: &str) -> Option<Theme> {
+    let bg: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(if bg == 7 || bg > 8 { Theme::Light } else { Theme::Dark })
+}
+
+// The answer is like "ESC ] 11 ; rgb:ffff/ffff/dddd" followed by BEL or ESC \; each component has
+// 1 to 4 hex digits. Returns the components, scaled to [0, 1].
+fn parse_osc11(answer: &[u8]) -> Option<(f64, f64, f64)> {
+    let answer = String::from_utf8_lossy(answer);
+    let start = answer.find("]11;rgb:")? + "]11;rgb:".len();
+    let end = answer[start..].find(['\x07', '\x1b'])? + start;
+    let components: Vec<f64> = answer[start..end]
+        .split('/')
+        .map(|hex| {
+            let value = u32::from_str_radix(hex, 16).ok()?;
+            let max = 16u32.checked_pow(hex.len() as u32)? - 1;
+            (1..=4).contains(&hex.len()).then(|| value as f64 / max as f64)
+        })
+        .collect::<Option<_>>()?;
+    match components[..] {
+        [r, g, b] => Some((r, g, b)),
+        _ => None,
+    }
+}
+
+// Whether the answer to DA1 ("ESC [ ? ... c") has arrived, i.e. there is nothing more to read.
+fn has_da1_answer(answer: &[u8]) -> bool {
+    answer
+        .windows(3)
+        .position(|w| w == b"\x1b[?")
+        .is_some_and(|start| answer[start..].contains(&b'c'))
+}
+
+#[cfg(unix)]
+fn query_background() -> Option<(f64, f64, f64)> {
+    use std::fs::OpenOptions;
+    use std::io::{Read, Write};
+    use std::os::fd::AsRawFd;
+    use std::time::{Duration, Instant};
+
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty").ok()?;
+    tty.write_all(b"\x1b]11;?\x1b\\\x1b[c").ok()?;
+    tty.flush().ok()?;
+
+    let deadline = Instant::now() + Duration::from_millis(200);
+    let mut answer = Vec::new();
+    while !has_da1_answer(&answer) {
+        let timeout = deadline.saturating_duration_since(Instant::now()).as_millis() as i32;
+        let mut fds = libc::pollfd {
+            fd: tty.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: fds is a valid pollfd, and its count is 1.
+        if timeout == 0 || unsafe { libc::poll(&mut fds, 1, timeout) } <= 0 {
+            break;
+        }
+        let mut buf = [0; 64];
+        match tty.read(&mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => answer.extend_from_slice(&buf[..n]),
+        }
+    }
+    parse_osc11(&answer)
+}
+
+#[cfg(not(unix))]
+fn query_background() -> Option<(f64, f64, f64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_osc11() {
+        let answer = b"\x1b]11;rgb:ffff/ffff/dddd\x1b\\\x1b[?62;22c";
+        assert_eq!(parse_osc11(answer), Some((1.0, 1.0, 0xdddd as f64 / 0xffff as f64)));
+        assert_eq!(parse_osc11(b"\x1b]11;rgb:0/0/0\x07"), Some((0.0, 0.0, 0.0)));
+        assert_eq!(parse_osc11(b"\x1b[?62;22c"), None);
+        assert_eq!(parse_osc11(b"\x1b]11;rgb:ff/ff\x07"), None);
+        assert!(has_da1_answer(answer));
+        assert!(!has_da1_answer(b"\x1b]11;rgb:ffff/ffff/dddd\x1b\\\x1b[?62"));
+    }
+
+    #[test]
+    fn test_guessed_theme() {
+        assert_eq!(theme_from_rgb((1.0, 1.0, 0.87)), Theme::Light);
+        assert_eq!(theme_from_rgb((0.1, 0.1, 0.2)), Theme::Dark);
+        assert_eq!(theme_from_colorfgbg("15;0"), Some(Theme::Dark));
+        assert_eq!(theme_from_colorfgbg("0;default;15"), Some(Theme::Light));
+        assert_eq!(theme_from_colorfgbg("0;7"), Some(Theme::Light));
+        assert_eq!(theme_from_colorfgbg("15;8"), Some(Theme::Dark));
+        assert_eq!(theme_from_colorfgbg(""), None);
+    }
+}
@@ -3,8 +3,8 @@
 use std::{
     collections::HashMap,
     fmt,
-    fs::File,
-    io::BufReader
+    fs,
+    path::{Path, PathBuf},
 };
 
 use hex_color::HexColor;
@@ -14,6 +14,7 @@ use ratatui::prelude::Color;
 
 use serde_json::Value::Object;
 
+use crate::alignment::SeqType::{self, Nucleic, Protein};
 use crate::ui::color_scheme::{
     CLUSTALX_BLUE, CLUSTALX_CYAN, CLUSTALX_GREEN, CLUSTALX_MAGENTA, CLUSTALX_ORANGE, CLUSTALX_PINK,
     CLUSTALX_RED, CLUSTALX_YELLOW, JALVIEW_NUCLEOTIDE_A, JALVIEW_NUCLEOTIDE_B,
@@ -23,15 +24,25 @@ use crate::ui::color_scheme::{
     JALVIEW_NUCLEOTIDE_V, JALVIEW_NUCLEOTIDE_W, JALVIEW_NUCLEOTIDE_X, JALVIEW_NUCLEOTIDE_Y, ORANGE,
 };
 
+#[derive(Clone)]
 pub struct ColorMap {
-    #[allow(dead_code)]
     pub name: String,
+    // The kinds of sequences the map is meant for; color schemes only offer suitable maps.
+    pub seq_types: Vec<SeqType>,
     map: HashMap<char, Color>,
 }
 
 impl ColorMap {
-    pub fn new(name: String, map: HashMap<char, Color>) -> ColorMap {
-        ColorMap { name, map }
+    pub fn new(name: String, seq_types: &[SeqType], map: HashMap<char, Color>) -> ColorMap {
+        ColorMap {
+            name,
+            seq_types: seq_types.to_vec(),
+            map,
+        }
+    }
+
+    pub fn applies_to(&self, seq_type: SeqType) -> bool {
+        self.seq_types.contains(&seq_type)
     }
 
     pub fn get(&self, residue: char) -> Color {
@@ -54,9 +65,10 @@ impl fmt::Display for ColorMap {
     }
 }
 
+// The first suitable map is the default.
 pub fn builtin_polychrome_colormaps() -> Vec<ColorMap> {
     vec![
-        color_map_jalview_nt(), // Keep the nucleotide map at index 0 (see
+        color_map_jalview_nt(),
         color_map_clustalx(),
         color_map_lesk(),
     ]
@@ -80,6 +92,7 @@ pub fn monochrome_colormap() -> Vec<ColorMap> {
 pub fn color_map_monochrome() -> ColorMap {
     ColorMap::new(
         "White".into(),
+        &[Nucleic, Protein],
         HashMap::from([
             ('G', Color::White),
             ('A', Color::White),
@@ -131,6 +144,7 @@ pub fn color_map_monochrome() -> ColorMap {
 pub fn color_map_lesk() -> ColorMap {
     ColorMap::new(
         "Lesk".into(),
+        &[Protein],
         HashMap::from([
             ('G', ORANGE),
             ('A', ORANGE),
@@ -182,6 +196,7 @@ pub fn color_map_lesk() -> ColorMap {
 pub fn color_map_clustalx() -> ColorMap {
     ColorMap::new(
         "ClustalX".into(),
+        &[Protein],
         HashMap::from([
             ('G', CLUSTALX_ORANGE),
             ('A', CLUSTALX_BLUE),
@@ -233,6 +248,7 @@ pub fn color_map_clustalx() -> ColorMap {
 pub fn color_map_jalview_nt() -> ColorMap {
     ColorMap::new(
         "JalView-nt".into(),
+        &[Nucleic],
         HashMap::from([
             ('A', JALVIEW_NUCLEOTIDE_A),
             ('C', JALVIEW_NUCLEOTIDE_C),
@@ -275,28 +291,91 @@ pub fn color_map_jalview_nt() -> ColorMap {
     )
 }
 
-pub fn colormap_gecos(path: String) -> ColorMap {
-    let file = File::open(path).unwrap();
-    let reader = BufReader::new(file);
-    let cm: serde_json::Value = serde_json::from_reader(reader).unwrap();
+// Gecos (https://gecos.biotite-python.org) color maps are JSON objects whose "colors" member maps
+// residues to hex colors, e.g. "A": "#71564e". The map is named after the file (without the
+// extension), since Gecos names them all "scheme".
+pub fn colormap_gecos(path: &Path) -> Result<ColorMap, String> {
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "custom".into());
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse_gecos(name, &text)
+}
 
-    //println!("{:#?}", cm["colors"]);
+fn parse_gecos(name: String, text: &str) -> Result<ColorMap, String> {
+    let cm: serde_json::Value = serde_json::from_str(text).map_err(|e| format!("bad JSON: {}", e))?;
+    let Some(Object(map)) = cm.get("colors") else {
+        return Err("no \"colors\" object".into());
+    };
     let mut color_map: HashMap<char, Color> = HashMap::new();
-    let orig_map = &cm["colors"];
-    if let Object(map) = orig_map {
-        //println!("Found map: {:#?}", map);
-        for (k, v) in map {
-            let color_str = serde_json::from_value::<String>(v.clone()).unwrap();
-            let hex_color = HexColor::parse_rgb(&color_str).unwrap();
-            let color = Color::Rgb(hex_color.r, hex_color.g, hex_color.b);
-            let residue = k.chars().collect::<Vec<char>>()[0];
-            let residue_lc = residue.to_ascii_lowercase();
-            color_map.insert(residue, color);
-            color_map.insert(residue_lc, color);
-            //println!("{} -> {}", k.chars().collect::<Vec<char>>()[0], color);
-        }
-        color_map.insert('-', Color::Gray);
+    for (k, v) in map {
+        let mut chars = k.chars();
+        let residue = match (chars.next(), chars.next()) {
+            (Some(residue), None) => residue,
+            _ => return Err(format!("bad residue '{}' (should be one character)", k)),
+        };
+        let hex_color = v
+            .as_str()
+            .and_then(|color_str| HexColor::parse_rgb(color_str).ok())
+            .ok_or_else(|| format!("bad color for '{}': {} (should be like \"#71564e\")", k, v))?;
+        let color = Color::Rgb(hex_color.r, hex_color.g, hex_color.b);
+        color_map.insert(residue, color);
+        color_map.insert(residue.to_ascii_lowercase(), color);
+    }
+    color_map.insert('-', Color::Gray);
+
+    Ok(ColorMap::new(name, &[gecos_seq_type(map.keys())], color_map))
+}
+
+// Gecos maps do not say what they are for, but nucleotide maps only have nucleotides (including
+// IUPAC ambiguity codes).
+fn gecos_seq_type<'a>(residues: impl Iterator<Item = &'a String>) -> SeqType {
+    const NUCLEOTIDES: &str = "ACGTUIRYSWKMBDHVNX-";
+    let mut residues = residues.peekable();
+    if residues.peek().is_some()
+        && residues.all(|r| r.chars().all(|c| NUCLEOTIDES.contains(c.to_ascii_uppercase())))
+    {
+        Nucleic
+    } else {
+        Protein
     }
+}
+
+// All the Gecos maps (*.json) in a directory, by file name. Errors are prefixed with the file.
+pub fn gecos_colormaps(dir: &Path) -> Result<Vec<ColorMap>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|path| colormap_gecos(path).map_err(|e| format!("{}: {}", path.display(), e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    ColorMap::new("custom".into(), color_map)
+    #[test]
+    fn test_gecos_colormaps() {
+        let maps = gecos_colormaps(Path::new("data/colormaps")).unwrap();
+        assert!(maps.iter().any(|cm| cm.name == "gecos_default"));
+        let cm = parse_gecos("t".into(), r##"{"colors": {"A": "#ff0000"}}"##).unwrap();
+        assert_eq!(cm.get('A'), Color::Rgb(255, 0, 0));
+        assert_eq!(cm.get('a'), Color::Rgb(255, 0, 0));
+        assert!(cm.applies_to(Nucleic) && !cm.applies_to(Protein));
+        let default = maps.iter().find(|cm| cm.name == "gecos_default").unwrap();
+        assert_eq!(default.seq_types, vec![Protein]);
+
+        let error = |text: &str| parse_gecos("t".into(), text).err().unwrap();
+        assert!(error("{\"colors\": ").starts_with("bad JSON"));
+        assert_eq!(error("{}"), "no \"colors\" object");
+        assert!(error(r##"{"colors": {"A": "#ff00"}}"##).starts_with("bad color for 'A'"));
+        assert!(error(r##"{"colors": {"A": 3}}"##).starts_with("bad color for 'A'"));
+        assert!(error(r##"{"colors": {"AB": "#ff0000"}}"##).starts_with("bad residue"));
+    }
 }
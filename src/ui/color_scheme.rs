@@ -13,7 +13,6 @@ use crate::{
             builtin_polychrome_colormaps,
             monochrome_colormap,
         },
-        color_scheme::SeqType::Protein,
     }
 };
 
@@ -53,13 +52,17 @@ pub const JALVIEW_NUCLEOTIDE_D: Color = Color::from_u32(0x00483D8B);
 pub const JALVIEW_NUCLEOTIDE_V: Color = Color::from_u32(0x00b8860b);
 pub const JALVIEW_NUCLEOTIDE_N: Color = Color::from_u32(0x002f4f4f);
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Theme {
     Light,
     Dark,
     Monochrome,
 }
 
+// As in --theme and the configuration file.
+pub const THEMES: &[(&str, Theme)] =
+    &[("dark", Theme::Dark), ("light", Theme::Light), ("mono", Theme::Monochrome)];
+
 impl fmt::Display for Theme {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let s = match self {
@@ -71,6 +74,43 @@ impl fmt::Display for Theme {
     }
 }
 
+impl Theme {
+    // Background and foreground of figures (see figure.rs), which stand in for the terminal's
+    // default colors. Monochrome figures are black on white, as they are most likely to be printed.
+    pub fn figure_colors(&self) -> (&'static str, &'static str) {
+        match self {
+            Theme::Dark => ("#000000", "#ffffff"),
+            Theme::Light | Theme::Monochrome => ("#ffffff", "#000000"),
+        }
+    }
+}
+
+// The CSS equivalent of a terminal color, for figures. Named colors get their usual xterm values;
+// Reset (the terminal's default) and indexed colors have no fixed value, hence None.
+pub fn css_color(color: Color) -> Option<String> {
+    let (r, g, b) = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 0, 0),
+        Color::Green => (0, 205, 0),
+        Color::Yellow => (205, 205, 0),
+        Color::Blue => (0, 0, 238),
+        Color::Magenta => (205, 0, 205),
+        Color::Cyan => (0, 205, 205),
+        Color::Gray => (229, 229, 229),
+        Color::DarkGray => (127, 127, 127),
+        Color::LightRed => (255, 0, 0),
+        Color::LightGreen => (0, 255, 0),
+        Color::LightYellow => (255, 255, 0),
+        Color::LightBlue => (92, 92, 255),
+        Color::LightMagenta => (255, 0, 255),
+        Color::LightCyan => (0, 255, 255),
+        Color::White => (255, 255, 255),
+        Color::Reset | Color::Indexed(_) => return None,
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
 // TODO: make these private and use getters.
 pub struct ColorScheme {
     pub theme: Theme,
@@ -84,32 +124,34 @@ pub struct ColorScheme {
     pub conservation_color: Color,
 }
 
+// Only protein maps are offered for amino acids, and only nucleotide maps for DNA/RNA.
+fn suitable_colormaps(macromolecule_type: SeqType) -> Vec<ColorMap> {
+    builtin_polychrome_colormaps()
+        .into_iter()
+        .filter(|cm| cm.applies_to(macromolecule_type))
+        .collect()
+}
+
 impl ColorScheme {
-    // TODO: the Vec of colormaps should depend on the macromolecule, i.e. only protein maps for aa,
-    // and only nt maps for nt.
     pub fn color_scheme_dark(macromolecule_type: SeqType) -> Self {
-        // These are indices into the Vec of built-in color maps, see color_maps.rs
-        let index = if macromolecule_type == Protein { 1 } else { 0 };
         ColorScheme {
             theme: Theme::Dark,
             label_num_color: Color::LightGreen,
             seq_metric_color: Color::LightBlue,
-            residue_colormaps: builtin_polychrome_colormaps(),
-            residue_colormap_index: index,
+            residue_colormaps: suitable_colormaps(macromolecule_type),
+            residue_colormap_index: 0,
             zoombox_color: Color::Cyan,
             conservation_color: SALMON,
         }
     }
 
     pub fn color_scheme_light(macromolecule_type: SeqType) -> Self {
-        // These are indices into the Vec of built-in color maps, see color_maps.rs
-        let index = if macromolecule_type == Protein { 1 } else { 0 };
         ColorScheme {
             theme: Theme::Light,
             label_num_color: Color::from_u32(0x00008000), 
             seq_metric_color: Color::Rgb(25, 127, 229),
-            residue_colormaps: builtin_polychrome_colormaps(),
-            residue_colormap_index: index,
+            residue_colormaps: suitable_colormaps(macromolecule_type),
+            residue_colormap_index: 0,
             zoombox_color: Color::Cyan,
             conservation_color: SALMON,
         }
@@ -143,3 +185,17 @@ impl fmt::Display for ColorScheme {
         write!(f, "{} {}", self.theme, self.current_residue_colormap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suitable_colormaps() {
+        let names = |cs: ColorScheme| -> Vec<String> {
+            cs.residue_colormaps.iter().map(|cm| cm.name.clone()).collect()
+        };
+        assert_eq!(names(ColorScheme::color_scheme_dark(SeqType::Nucleic)), ["JalView-nt"]);
+        assert_eq!(names(ColorScheme::color_scheme_light(SeqType::Protein)), ["ClustalX", "Lesk"]);
+    }
+}
@@ -0,0 +1,348 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Standalone pictures (SVG or HTML) of a region of the alignment, as shown by termal: labels,
+ * residues colored according to the current color scheme, map and video mode, then the consensus
+ * and the conservation bars. The terminal's default colors, which have no fixed value, are
+ * replaced by the theme's figure colors. */
+
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Write as _},
+    io::Write,
+    ops::Range,
+    path::Path,
+};
+
+use crate::{
+    ui::{
+        barchart::values_barchart,
+        color_scheme::{css_color, Theme},
+        VideoMode,
+    },
+    UI,
+};
+
+// SVG geometry, in pixels.
+const CELL_WIDTH: usize = 10;
+const CELL_HEIGHT: usize = 14;
+const CHAR_WIDTH: f64 = 7.2; // for a 12px monospace font
+const FONT_SIZE: usize = 12;
+const MARGIN: usize = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FigureFormat {
+    Svg,
+    Html,
+}
+
+impl FigureFormat {
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> FigureFormat {
+        let ext = path.as_ref().extension().and_then(|e| e.to_str());
+        match ext.map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("html") | Some("htm") => FigureFormat::Html,
+            _ => FigureFormat::Svg,
+        }
+    }
+}
+
+impl fmt::Display for FigureFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FigureFormat::Svg => write!(f, "SVG"),
+            FigureFormat::Html => write!(f, "HTML"),
+        }
+    }
+}
+
+// Background and text color of a residue (None meaning the figure's background).
+struct CellColors {
+    bg: Option<String>,
+    fg: String,
+}
+
+fn residue_colors(ui: &UI, residue: char) -> CellColors {
+    let theme = ui.theme();
+    let (page_bg, page_fg) = theme.figure_colors();
+    let color = match theme {
+        Theme::Dark | Theme::Light => {
+            css_color(ui.color_scheme().current_residue_colormap().get(residue))
+        }
+        Theme::Monochrome => None,
+    }
+    .unwrap_or(page_fg.to_string());
+    match ui.video_mode {
+        VideoMode::Direct => CellColors { bg: None, fg: color },
+        VideoMode::Inverse => CellColors {
+            bg: Some(color),
+            // In inverse video, the dark and light themes both show residues in black.
+            fg: match theme {
+                Theme::Dark | Theme::Light => "#000000".to_string(),
+                Theme::Monochrome => page_bg.to_string(),
+            },
+        },
+    }
+}
+
+fn conservation_color(ui: &UI) -> String {
+    let (_, page_fg) = ui.theme().figure_colors();
+    match ui.theme() {
+        Theme::Dark | Theme::Light => css_color(ui.color_scheme().conservation_color),
+        Theme::Monochrome => None,
+    }
+    .unwrap_or(page_fg.to_string())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn column_chars(s: &str, cols: &Range<usize>) -> Vec<char> {
+    s.chars().skip(cols.start).take(cols.len()).collect()
+}
+
+// `rows` are positions in the current ordering (i.e. as shown on screen), `cols` are alignment
+// columns.
+pub fn write_figure<W: Write>(
+    ui: &UI,
+    mut writer: W,
+    format: FigureFormat,
+    rows: Range<usize>,
+    cols: Range<usize>,
+) -> std::io::Result<()> {
+    let text = match format {
+        FigureFormat::Svg => svg(ui, &rows, &cols),
+        FigureFormat::Html => html(ui, &rows, &cols),
+    };
+    writer.write_all(text.as_bytes())
+}
+
+fn svg(ui: &UI, rows: &Range<usize>, cols: &Range<usize>) -> String {
+    let aln = &ui.app.alignment;
+    let (page_bg, page_fg) = ui.theme().figure_colors();
+    let labels: Vec<&str> = ui.app.ordering[rows.clone()]
+        .iter()
+        .map(|&i| aln.headers[i].as_str())
+        .chain(["Consensus", "Conservation"])
+        .collect();
+    let label_width = (labels.iter().map(|l| l.chars().count()).max().unwrap_or(0) as f64
+        * CHAR_WIDTH)
+        .ceil() as usize
+        + 2 * MARGIN;
+    let width = label_width + cols.len() * CELL_WIDTH + MARGIN;
+    // Sequences, a blank line, consensus, conservation.
+    let num_lines = rows.len() + 3;
+    let height = num_lines * CELL_HEIGHT + 2 * MARGIN;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="monospace" font-size="{fs}">"#,
+        w = width,
+        h = height,
+        fs = FONT_SIZE
+    );
+    let _ = writeln!(out, r#"<rect width="100%" height="100%" fill="{}"/>"#, page_bg);
+
+    let cell_row = |out: &mut String, line: usize, label: &str, residues: &[char]| {
+        let y = MARGIN + line * CELL_HEIGHT;
+        let baseline = y + CELL_HEIGHT - 3;
+        let _ = writeln!(
+            out,
+            r#"<text x="{}" y="{}" fill="{}">{}</text>"#,
+            MARGIN,
+            baseline,
+            page_fg,
+            escape(label)
+        );
+        for (j, &c) in residues.iter().enumerate() {
+            let x = label_width + j * CELL_WIDTH;
+            let colors = residue_colors(ui, c);
+            if let Some(bg) = colors.bg {
+                let _ = writeln!(
+                    out,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                    x, y, CELL_WIDTH, CELL_HEIGHT, bg
+                );
+            }
+            let _ = writeln!(
+                out,
+                r#"<text x="{}" y="{}" fill="{}" text-anchor="middle">{}</text>"#,
+                x + CELL_WIDTH / 2,
+                baseline,
+                colors.fg,
+                escape(&c.to_string())
+            );
+        }
+    };
+
+    for (line, &i) in ui.app.ordering[rows.clone()].iter().enumerate() {
+        cell_row(
+            &mut out,
+            line,
+            &aln.headers[i],
+            &column_chars(&aln.sequences[i], cols),
+        );
+    }
+    cell_row(
+        &mut out,
+        rows.len() + 1,
+        "Consensus",
+        &column_chars(ui.app.consensus(), cols),
+    );
+
+    let cons_line = rows.len() + 2;
+    let y = MARGIN + cons_line * CELL_HEIGHT;
+    let _ = writeln!(
+        out,
+        r#"<text x="{}" y="{}" fill="{}">Conservation</text>"#,
+        MARGIN,
+        y + CELL_HEIGHT - 3,
+        page_fg
+    );
+    let bar_color = conservation_color(ui);
+    for (j, v) in ui.app.conservation()[cols.clone()].iter().enumerate() {
+        let bar_height = v.clamp(0.0, 1.0) * CELL_HEIGHT as f64;
+        let _ = writeln!(
+            out,
+            r#"<rect x="{}" y="{:.1}" width="{}" height="{:.1}" fill="{}"/>"#,
+            label_width + j * CELL_WIDTH,
+            (y + CELL_HEIGHT) as f64 - bar_height,
+            CELL_WIDTH,
+            bar_height,
+            bar_color
+        );
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+// Each residue gets a CSS class, rather than repeating its colors in every cell.
+fn residue_class(c: char) -> String {
+    format!("r{}", c as u32)
+}
+
+fn html(ui: &UI, rows: &Range<usize>, cols: &Range<usize>) -> String {
+    let aln = &ui.app.alignment;
+    let (page_bg, page_fg) = ui.theme().figure_colors();
+    let seqs: Vec<(&str, Vec<char>)> = ui.app.ordering[rows.clone()]
+        .iter()
+        .map(|&i| (aln.headers[i].as_str(), column_chars(&aln.sequences[i], cols)))
+        .collect();
+    let consensus = column_chars(ui.app.consensus(), cols);
+    let label_width = seqs
+        .iter()
+        .map(|(h, _)| h.chars().count())
+        .chain(["Conservation".len()])
+        .max()
+        .unwrap_or(0);
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>{}</title>", escape(&ui.app.filename));
+    out.push_str("<style>\n");
+    let _ = writeln!(
+        out,
+        "body {{ background: {}; color: {}; }}\npre {{ font-family: monospace; line-height: 1.2; }}",
+        page_bg, page_fg
+    );
+    let residues: BTreeSet<char> = seqs
+        .iter()
+        .flat_map(|(_, s)| s.iter().copied())
+        .chain(consensus.iter().copied())
+        .collect();
+    for c in residues {
+        let colors = residue_colors(ui, c);
+        let _ = write!(out, ".{} {{ color: {};", residue_class(c), colors.fg);
+        if let Some(bg) = colors.bg {
+            let _ = write!(out, " background: {};", bg);
+        }
+        out.push_str(" }\n");
+    }
+    let _ = writeln!(out, ".cons {{ color: {}; }}", conservation_color(ui));
+    out.push_str("</style>\n</head>\n<body>\n<pre>\n");
+
+    let cell_row = |out: &mut String, label: &str, residues: &[char]| {
+        // Padding is computed before escaping, which may lengthen the label.
+        let padding = label_width - label.chars().count();
+        let _ = write!(out, "{}{} ", escape(label), " ".repeat(padding));
+        for &c in residues {
+            let _ = write!(
+                out,
+                "<span class=\"{}\">{}</span>",
+                residue_class(c),
+                escape(&c.to_string())
+            );
+        }
+        out.push('\n');
+    };
+    for (header, residues) in &seqs {
+        cell_row(&mut out, header, residues);
+    }
+    out.push('\n');
+    cell_row(&mut out, "Consensus", &consensus);
+    let _ = writeln!(
+        out,
+        "{:width$} <span class=\"cons\">{}</span>",
+        "Conservation",
+        values_barchart(&ui.app.conservation()[cols.clone()].to_vec()),
+        width = label_width
+    );
+    out.push_str("</pre>\n</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+
+    fn figure(format: FigureFormat, rows: Range<usize>, cols: Range<usize>) -> String {
+        let mut app = App::new("data/test2.fas").unwrap();
+        let ui = UI::new(&mut app);
+        let mut out: Vec<u8> = Vec::new();
+        write_figure(&ui, &mut out, format, rows, cols).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(FigureFormat::from_extension("aln.svg"), FigureFormat::Svg);
+        assert_eq!(FigureFormat::from_extension("aln.HTML"), FigureFormat::Html);
+        assert_eq!(FigureFormat::from_extension("aln.htm"), FigureFormat::Html);
+        assert_eq!(FigureFormat::from_extension("aln"), FigureFormat::Svg);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a<b & \"c\">"), "a&lt;b &amp; &quot;c&quot;&gt;");
+    }
+
+    #[test]
+    fn test_svg_region() {
+        let svg = figure(FigureFormat::Svg, 1..3, 2..6);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(!svg.contains(">seq1<"));
+        assert!(svg.contains(">seq2<"));
+        assert!(svg.contains(">seq3<"));
+        // 2 sequences + consensus, 4 columns each
+        assert_eq!(svg.matches("text-anchor=\"middle\"").count(), 12);
+        // The background, one per cell (inverse video is the default), one bar per column.
+        assert_eq!(svg.matches("<rect ").count(), 1 + 12 + 4);
+    }
+
+    #[test]
+    fn test_html_whole_alignment() {
+        let html = figure(FigureFormat::Html, 0..3, 0..10);
+        assert!(html.contains("<title>data/test2.fas</title>"));
+        // seq1 is TTGCCG-CGA; T is U+0054
+        // Labels are padded to the width of "Conservation".
+        assert!(html.contains("seq1         <span class=\"r84\">T</span><span class=\"r84\">T</span>"));
+        assert!(html.contains(".r45 {"));
+        assert!(html.contains("<span class=\"cons\">"));
+    }
+}
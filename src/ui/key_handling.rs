@@ -4,216 +4,321 @@ use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use log::debug;
 
-use crate::{ZoomLevel, UI};
+use crate::{
+    ui::{keymap::Action, selection::SelectionKind},
+    ZoomLevel, UI,
+};
 
-pub fn handle_key_press(ui: &mut UI, key_event: KeyEvent) -> bool {
-    let mut done = false;
-
-    if ui.show_help {
-        ui.show_help = false;
-    } else {
-        // debug!("key event: {:#?}", key_event.code);
-        match key_event.code {
-            // Help
-            KeyCode::Char('?') => {
-                ui.show_help = true;
+// While a prompt is active, keys edit its input rather than trigger commands.
+fn handle_prompt_key(ui: &mut UI, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Esc => ui.cancel_prompt(),
+        KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+            ui.cancel_prompt()
+        }
+        KeyCode::Enter => ui.submit_prompt(),
+        KeyCode::Backspace => {
+            if let Some(prompt) = ui.prompt.as_mut() {
+                prompt.input.pop();
+            }
+        }
+        KeyCode::Char(c) => {
+            if let Some(prompt) = ui.prompt.as_mut() {
+                prompt.input.push(c);
             }
+        }
+        _ => {}
+    }
+}
 
-            // ----- Hide/Show panes -----
+// While a selection is active, motion actions move its cursor (columns only matter in block
+// selections, otherwise horizontal motion just scrolls). Returns false for other actions, which
+// keep their usual meaning.
+fn move_selection_cursor(ui: &mut UI, action: Action) -> bool {
+    let block = ui.selection.is_some_and(|s| s.kind == SelectionKind::Block);
+    let screen_height = ui.max_nb_seq_shown() as isize;
+    let screen_width = ui.max_nb_col_shown() as isize;
+    let (num_seq, aln_len) = (ui.app.num_seq() as isize, ui.app.aln_len() as isize);
+    let (rows, cols) = match action {
+        Action::LineDown => (1, 0),
+        Action::LineUp => (-1, 0),
+        Action::ScreenDown => (screen_height, 0),
+        Action::ScreenUp => (-screen_height, 0),
+        Action::Bottom => (num_seq, 0),
+        Action::Top => (-num_seq, 0),
+        Action::ColumnRight if block => (0, 1),
+        Action::ColumnLeft if block => (0, -1),
+        Action::ScreenRight if block => (0, screen_width),
+        Action::ScreenLeft if block => (0, -screen_width),
+        Action::FullRight if block => (0, aln_len),
+        Action::FullLeft if block => (0, -aln_len),
+        _ => return false,
+    };
+    ui.move_selection_cursor(rows, cols);
+    true
+}
 
-            // Left pane
-            KeyCode::Char('a') => {
-                if ui.label_pane_width == 0 {
-                    ui.show_label_pane();
-                } else {
-                    ui.hide_label_pane();
-                }
-            }
+// Returns true IFF the user quits.
+fn run_action(ui: &mut UI, action: Action) -> bool {
+    match action {
+        // ----- Motion -----
+        Action::LineDown => match ui.zoom_level() {
+            ZoomLevel::ZoomedIn => ui.scroll_one_line_down(),
+            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_line_down(),
+        },
+        Action::LineUp => match ui.zoom_level() {
+            ZoomLevel::ZoomedIn => ui.scroll_one_line_up(),
+            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_line_up(),
+        },
+        Action::ColumnRight => match ui.zoom_level() {
+            ZoomLevel::ZoomedIn => ui.scroll_one_col_right(),
+            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_col_right(),
+        },
+        Action::ColumnLeft => match ui.zoom_level() {
+            ZoomLevel::ZoomedIn => ui.scroll_one_col_left(),
+            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_col_left(),
+        },
+        Action::ScreenDown => ui.scroll_one_screen_down(),
+        Action::ScreenUp => ui.scroll_one_screen_up(),
+        Action::ScreenRight => ui.scroll_one_screen_right(),
+        Action::ScreenLeft => ui.scroll_one_screen_left(),
+        Action::Bottom => ui.jump_to_bottom(),
+        Action::Top => ui.jump_to_top(),
+        Action::FullRight => ui.jump_to_end(),
+        Action::FullLeft => ui.jump_to_begin(),
+        Action::GoTo => ui.prompt_for_go_to(),
 
-            // Bottom pane
-            // Exception: Ctrl-C quits
-            KeyCode::Char('c') if !key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                if key_event.modifiers == KeyModifiers::CONTROL {
-                    done = true;
-                }
-                if ui.bottom_pane_height == 0 {
-                    ui.show_bottom_pane();
-                } else {
-                    ui.hide_bottom_pane();
-                }
-            }
+        // ----- Search -----
+        Action::Search => ui.prompt_for_search(),
+        Action::NextMatch => ui.jump_to_match(true),
+        Action::PreviousMatch => ui.jump_to_match(false),
+        Action::FindLabel => ui.prompt_for_label_search(),
+        Action::FilterLabels => ui.prompt_for_label_filter(),
+        Action::ToggleSubsetStats => ui.toggle_stats_on_subset(),
 
-            // Both panes
-            KeyCode::Char('f') => {
-                if ui.full_screen {
-                    ui.show_label_pane();
-                    ui.show_bottom_pane();
-                    ui.full_screen = false;
-                } else {
-                    ui.hide_label_pane();
-                    ui.hide_bottom_pane();
-                    ui.full_screen = true;
-                }
-            }
+        // ----- Selection -----
+        Action::SelectRows => ui.toggle_selection(SelectionKind::Rows),
+        Action::SelectBlock => ui.toggle_selection(SelectionKind::Block),
+        Action::ShowHidden => ui.unhide_all(),
+        Action::EndSelection => ui.end_selection(),
+        Action::CopySelection => ui.copy_selection(),
+        Action::WriteSelection => ui.prompt_for_selection_export(),
+        Action::SelectionStats => ui.show_selection_stats(),
+        Action::HideSelection => ui.hide_selection(false),
+        Action::HideOthers => ui.hide_selection(true),
 
-            // ----- Motion -----
-
-            // Arrows - late introduction, but might be friendlier to new users.
-            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right => {
-                // Non-shifted arrow keys
-                if !key_event.modifiers.contains(KeyModifiers::SHIFT) {
-                    match key_event.code {
-                        KeyCode::Down => match ui.zoom_level() {
-                            ZoomLevel::ZoomedIn => ui.scroll_one_line_down(),
-                            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => {
-                                ui.scroll_zoombox_one_line_down()
-                            }
-                        },
-                        KeyCode::Up => match ui.zoom_level() {
-                            ZoomLevel::ZoomedIn => ui.scroll_one_line_up(),
-                            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => {
-                                ui.scroll_zoombox_one_line_up()
-                            }
-                        },
-                        KeyCode::Right => match ui.zoom_level() {
-                            ZoomLevel::ZoomedIn => ui.scroll_one_col_right(),
-                            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => {
-                                ui.scroll_zoombox_one_col_right()
-                            }
-                        },
-                        KeyCode::Left => match ui.zoom_level() {
-                            ZoomLevel::ZoomedIn => ui.scroll_one_col_left(),
-                            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => {
-                                ui.scroll_zoombox_one_col_left()
-                            }
-                        },
-
-                        _ => panic!("Expected only arrow keycodes"),
-                    }
-                } else {
-                    // Shifted arrow keys
-                    match key_event.code {
-                        KeyCode::Down => ui.scroll_one_screen_down(),
-                        KeyCode::Up => ui.scroll_one_screen_up(),
-                        KeyCode::Right => ui.scroll_one_screen_right(),
-                        KeyCode::Left => ui.scroll_one_screen_left(),
-
-                        _ => panic!("Expected only arrow keycodes"),
-                    }
-                }
-            }
+        // ----- Reference sequence -----
+        Action::SetReference => ui.prompt_for_reference(),
+        Action::ToggleReferenceNumbering => ui.toggle_reference_numbering(),
+
+        // ----- Zooming -----
+        Action::CycleZoom => ui.cycle_zoom(),
+        // Since there are 3 zoom levels, cycling twice amounts to cycling backwards.
+        Action::CycleZoomBack => {
+            ui.cycle_zoom();
+            ui.cycle_zoom();
+        }
+        Action::ToggleZoombox => ui.toggle_zoombox(),
+        Action::ToggleZoomboxGuides => ui.set_zoombox_guides(!ui.show_zb_guides),
+        // Mark consensus positions that are retained in the zoom box
+        Action::HighlightRetainedColumns => ui.toggle_hl_retained_cols(),
+
+        // ----- Hide/Show panes -----
 
-            // Down
-            KeyCode::Char('j') => match ui.zoom_level() {
-                ZoomLevel::ZoomedIn => ui.scroll_one_line_down(),
-                ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_line_down(),
-            },
-            KeyCode::Char('J') | KeyCode::Char(' ') => ui.scroll_one_screen_down(),
-            KeyCode::Char('G') => ui.jump_to_bottom(),
-
-            // Up
-            KeyCode::Char('k') => match ui.zoom_level() {
-                ZoomLevel::ZoomedIn => ui.scroll_one_line_up(),
-                ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_line_up(),
-            },
-            KeyCode::Char('K') => ui.scroll_one_screen_up(),
-            KeyCode::Char('g') => ui.jump_to_top(),
-
-            // Right
-            KeyCode::Char('l') => match ui.zoom_level() {
-                ZoomLevel::ZoomedIn => ui.scroll_one_col_right(),
-                ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_col_right(),
-            },
-            KeyCode::Char('L') => ui.scroll_one_screen_right(),
-            KeyCode::Char('$') => ui.jump_to_end(),
-
-            // Left
-            KeyCode::Char('h') => match ui.zoom_level() {
-                ZoomLevel::ZoomedIn => ui.scroll_one_col_left(),
-                ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_col_left(),
-            },
-            KeyCode::Char('H') => ui.scroll_one_screen_left(),
-            KeyCode::Char('^') => ui.jump_to_begin(),
-
-            // Label Pane width
-            // NOTE: for these methods I'm using a more general approach than for
-            // motion: pass the argument instead of having separate functions for
-            // each increment.
-            KeyCode::Char('>') => ui.widen_label_pane(1),
-            KeyCode::Char('<') => ui.reduce_label_pane(1),
-
-            // Zoom
-            KeyCode::Char('z') => ui.cycle_zoom(),
-            // Since there are 3 zoom levels, cycling twice amounts to cycling
-            // backwards.
-            KeyCode::Char('Z') => {
-                ui.cycle_zoom();
-                ui.cycle_zoom();
+        // NOTE: for these methods I'm using a more general approach than for motion: pass the
+        // argument instead of having separate functions for each increment.
+        Action::WidenLabelPane => ui.widen_label_pane(1),
+        Action::NarrowLabelPane => ui.reduce_label_pane(1),
+        Action::ToggleLabelPane => {
+            if ui.label_pane_width == 0 {
+                ui.show_label_pane();
+            } else {
+                ui.hide_label_pane();
             }
-            // Toggle zoom box guides
-            KeyCode::Char('v') => {
-                ui.set_zoombox_guides(!ui.show_zb_guides);
+        }
+        Action::ToggleBottomPane => {
+            if ui.bottom_pane_height == 0 {
+                ui.show_bottom_pane();
+            } else {
+                ui.hide_bottom_pane();
             }
-            // Toggle zoom box visibility
-            KeyCode::Char('B') => {
-                ui.toggle_zoombox();
+        }
+        Action::ToggleLogo => ui.toggle_logo(),
+        Action::ToggleTreePane => ui.toggle_tree_pane(),
+        // Both panes
+        Action::ToggleFullScreen => {
+            if ui.full_screen {
+                ui.show_label_pane();
+                ui.show_bottom_pane();
+                ui.full_screen = false;
+            } else {
+                ui.hide_label_pane();
+                ui.hide_bottom_pane();
+                ui.full_screen = true;
             }
+        }
+        // Bottom pane position (i.e., bottom of screen or stuck to the alignment - when both are
+        // possible).
+        Action::CycleBottomPanePosition => {
+            ui.cycle_bottom_pane_position();
+            debug!(
+                "-- Toggling bottom pane position - now {:?}  --",
+                ui.bottom_pane_position
+            );
+        }
+        // Show/hide insert columns (A2M/A3M only)
+        Action::ToggleInsertColumns => ui.toggle_insert_columns(),
 
-            // Bottom pane position (i.e., bottom of screen or stuck to the alignment - when both
-            // are possible).
-            KeyCode::Char('b') => {
-                ui.cycle_bottom_pane_position();
-                debug!(
-                    "-- Toggling bottom pane position - now {:?}  --",
-                    ui.bottom_pane_position
-                );
-            }
+        // ---- Export ----
 
-            // ---- Visuals ----
+        // All columns, or only those in the zoom box (i.e., visible when zoomed in)
+        Action::Export => ui.prompt_for_export(false),
+        Action::ExportZoombox => ui.prompt_for_export(true),
+        // Pictures (SVG or HTML): whole alignment, or zoom box
+        Action::Figure => ui.prompt_for_figure(false),
+        Action::FigureZoombox => ui.prompt_for_figure(true),
 
-            // Mark consensus positions that are retained in the zoom box
-            KeyCode::Char('r') => ui.toggle_hl_retained_cols(),
+        // ---- Visuals ----
+        Action::NextColorScheme => ui.next_color_scheme(),
+        Action::NextColormap => ui.cycle_colormap(),
+        Action::ToggleVideoMode => ui.toggle_video_mode(),
 
-            // Inverse video
-            KeyCode::Char('i') => {
-                ui.toggle_video_mode();
-            }
+        // Sequence Order and metric
+        Action::NextOrdering => ui.cycle_ordering_criterion(),
+        Action::NextGuideTree => ui.cycle_guide_tree(),
+        // TODO: this directly calls the method in App, while the above call a method in UI
+        // (which is just a wrapper around an App counterpart). Make up your mind, dude...
+        Action::NextMetric => ui.app.cycle_metric(),
+        Action::OrderBySimilarity => ui.prompt_for_similarity(),
+        Action::ToggleIdentityMap => ui.toggle_identity_map(),
 
-            KeyCode::Char('s') => ui.next_color_scheme(),
+        Action::Help => ui.show_help = true,
+        Action::Quit => return true,
+    }
+    false
+}
 
-            // Cycle through colormaps
-            KeyCode::Char('m') => ui.cycle_colormap(),
+pub fn handle_key_press(ui: &mut UI, key_event: KeyEvent) -> bool {
+    if ui.prompt.is_some() {
+        handle_prompt_key(ui, key_event);
+        return false;
+    }
+    if ui.show_help {
+        ui.show_help = false;
+        return false;
+    }
+    // While there is a selection, the keys of selection actions take precedence.
+    if ui.has_selection() {
+        if let Some(action) = ui.keymap.selection_action(key_event) {
+            return run_action(ui, action);
+        }
+    }
+    // Keys that are not bound do nothing (there is no message to that effect, as it would have to
+    // be cleared after every other key).
+    let Some(action) = ui.keymap.action(key_event) else {
+        return false;
+    };
+    if ui.has_selection() && move_selection_cursor(ui, action) {
+        return false;
+    }
+    run_action(ui, action)
+}
 
-            // Sequence Order
-            KeyCode::Char('o') => ui.cycle_ordering_criterion(),
+// Turns a key sequence such as "zzjj<Right>" into key events, for --keys. Keys without a
+// character are given in angle brackets (vim-style), optionally with an S- (Shift) or C- (Control)
+// prefix, e.g. "<S-Down>" or "<C-c>"; "<lt>" stands for '<' itself.
+pub fn parse_keys(keys: &str) -> Result<Vec<KeyEvent>, String> {
+    let mut events = Vec::new();
+    let mut chars = keys.chars();
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            events.push(KeyEvent::from(KeyCode::Char(c)));
+            continue;
+        }
+        let rest = chars.as_str();
+        let Some(end) = rest.find('>') else {
+            return Err(format!("missing '>' after '<{}'", rest));
+        };
+        let name = rest[..end].to_string();
+        chars = rest[end + 1..].chars();
+        let (modifiers, base) = if let Some(base) = name.strip_prefix("S-") {
+            (KeyModifiers::SHIFT, base)
+        } else if let Some(base) = name.strip_prefix("C-") {
+            (KeyModifiers::CONTROL, base)
+        } else {
+            (KeyModifiers::NONE, name.as_str())
+        };
+        let code = match base.to_ascii_lowercase().as_str() {
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "enter" | "cr" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "bs" => KeyCode::Backspace,
+            "space" => KeyCode::Char(' '),
+            "lt" => KeyCode::Char('<'),
+            _ if base.chars().count() == 1 => KeyCode::Char(base.chars().next().unwrap()),
+            _ => return Err(format!("unknown key '<{}>'", name)),
+        };
+        events.push(KeyEvent::new(code, modifiers));
+    }
+    Ok(events)
+}
 
-            // Metric
-            // TODO: this directl< calls the method in App, while the above call a method in UI
-            // (which is just a wrapper around an App counterpart). Make up your mind, dude...
-            KeyCode::Char('t') => {
-                ui.app.cycle_metric();
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            // ----  Exit ----
-            KeyCode::Char('q') | KeyCode::Char('Q') => done = true,
-            KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                done = true
-            }
+    #[test]
+    fn test_parse_keys() {
+        let keys = parse_keys("zj<lt><S-Down><C-c><Enter>").unwrap();
+        assert_eq!(
+            keys,
+            vec![
+                KeyEvent::from(KeyCode::Char('z')),
+                KeyEvent::from(KeyCode::Char('j')),
+                KeyEvent::from(KeyCode::Char('<')),
+                KeyEvent::new(KeyCode::Down, KeyModifiers::SHIFT),
+                KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+                KeyEvent::from(KeyCode::Enter),
+            ]
+        );
+    }
 
-            _ => {
-                // let the user know this key is not bound
-                //
-                // TODO: there are pros and cons about this - first, the user can probably guess
-                // that if nothing happens then the key isn't bound. Second, the message should be
-                // disabled after the user presses a bound key, which would force us to either add
-                // code to that effect for _every single_ key binding, or do a first match on every
-                // valid key (to disable the message) and then match on each individual key to
-                // launch the desired action. Not sure it's worth it, frankly.
-                //
-                // ui.message = format!("Key '{:#?}' is not bound.", key_event.code);
-            }
-        }
+    #[test]
+    fn test_parse_keys_unknown() {
+        assert!(parse_keys("j<PgDn>").is_err());
+        assert!(parse_keys("j<Enter").is_err());
     }
 
-    done
+    #[test]
+    fn test_selection_keys() {
+        use crate::app::App;
+        use crate::ui::render::render_ui;
+        use ratatui::{backend::TestBackend, Terminal};
+
+        let mut app = App::new("data/test2.fas").unwrap();
+        let mut ui = UI::new(&mut app);
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        terminal.draw(|f| render_ui(f, &mut ui)).unwrap();
+        for key in parse_keys("<C-v>jlll").unwrap() {
+            handle_key_press(&mut ui, key);
+        }
+        assert_eq!(
+            ui.message,
+            " -- VISUAL BLOCK -- 2 sequences x 4 columns (1-4) "
+        );
+        // 'x' hides the selected sequences and ends the selection.
+        handle_key_press(&mut ui, KeyEvent::from(KeyCode::Char('x')));
+        assert!(!ui.has_selection());
+        assert_eq!(ui.app.ordering, vec![2]);
+        handle_key_press(&mut ui, KeyEvent::from(KeyCode::Char('X')));
+        assert_eq!(ui.app.ordering, vec![0, 1, 2]);
+        // Outside the TUI, copying must not write the OSC 52 sequence to stdout.
+        for key in parse_keys("Vy").unwrap() {
+            handle_key_press(&mut ui, key);
+        }
+        assert_eq!(ui.message, " Copying only works in the TUI ");
+    }
 }
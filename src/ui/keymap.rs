@@ -0,0 +1,491 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Key bindings. Keys trigger named actions, which can be remapped in the [keys] table of the
+ * configuration file (see config.rs), e.g. for emacs-like motion:
+ *
+ *   [keys]
+ *   line-down = ["<C-n>", "<Down>"]
+ *   line-up = ["<C-p>", "<Up>"]
+ *
+ * An action listed there loses its default keys, and a key given to an action is taken away from
+ * any other action it was bound to. The help screen (and --show-bindings) is generated from the
+ * keymap in use, so that it always matches the actual bindings. */
+
+use std::fmt::Write;
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::ui::key_handling::parse_keys;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    // Motion
+    LineDown,
+    LineUp,
+    ColumnRight,
+    ColumnLeft,
+    ScreenDown,
+    ScreenUp,
+    ScreenRight,
+    ScreenLeft,
+    Bottom,
+    Top,
+    FullRight,
+    FullLeft,
+    GoTo,
+    // Search
+    Search,
+    NextMatch,
+    PreviousMatch,
+    FindLabel,
+    FilterLabels,
+    ToggleSubsetStats,
+    // Selection
+    SelectRows,
+    SelectBlock,
+    ShowHidden,
+    EndSelection,
+    CopySelection,
+    WriteSelection,
+    SelectionStats,
+    HideSelection,
+    HideOthers,
+    // Reference
+    SetReference,
+    ToggleReferenceNumbering,
+    // Zooming
+    CycleZoom,
+    CycleZoomBack,
+    ToggleZoombox,
+    ToggleZoomboxGuides,
+    HighlightRetainedColumns,
+    // Panes
+    WidenLabelPane,
+    NarrowLabelPane,
+    ToggleLabelPane,
+    ToggleBottomPane,
+    ToggleLogo,
+    ToggleTreePane,
+    ToggleFullScreen,
+    CycleBottomPanePosition,
+    ToggleInsertColumns,
+    // Export
+    Export,
+    ExportZoombox,
+    Figure,
+    FigureZoombox,
+    // Video
+    NextColorScheme,
+    NextColormap,
+    ToggleVideoMode,
+    // Metrics and orderings
+    NextOrdering,
+    NextGuideTree,
+    NextMetric,
+    OrderBySimilarity,
+    ToggleIdentityMap,
+    // Other
+    Help,
+    Quit,
+}
+
+// Actions that only apply while there is a selection; their keys take precedence then.
+#[derive(Clone, Copy, PartialEq)]
+enum Context {
+    Normal,
+    Selection,
+}
+
+struct ActionInfo {
+    action: Action,
+    name: &'static str, // as in the configuration file
+    context: Context,
+    section: &'static str,
+    description: &'static str,
+    default_keys: &'static [&'static str],
+}
+
+const fn info(
+    action: Action,
+    name: &'static str,
+    section: &'static str,
+    description: &'static str,
+    default_keys: &'static [&'static str],
+) -> ActionInfo {
+    ActionInfo {
+        action,
+        name,
+        context: Context::Normal,
+        section,
+        description,
+        default_keys,
+    }
+}
+
+const fn selection_info(
+    action: Action,
+    name: &'static str,
+    description: &'static str,
+    default_keys: &'static [&'static str],
+) -> ActionInfo {
+    ActionInfo {
+        action,
+        name,
+        context: Context::Selection,
+        section: SELECTION,
+        description,
+        default_keys,
+    }
+}
+
+const MOTION: &str = "Motion";
+const SEARCH: &str = "Search";
+const SELECTION: &str = "Selection";
+const REFERENCE: &str = "Reference";
+const ZOOMING: &str = "Zooming";
+const PANES: &str = "Adjusting the Panes";
+const EXPORT: &str = "Export";
+const VIDEO: &str = "Video";
+const METRICS: &str = "Metrics and Orderings";
+const OTHER: &str = "Other";
+
+// In the order of the help screen.
+const ACTIONS: &[ActionInfo] = &[
+    info(Action::LineDown, "line-down", MOTION,
+        "scroll down 1 line (zoomed out: move the zoom box)", &["j", "<Down>"]),
+    info(Action::LineUp, "line-up", MOTION, "scroll up 1 line", &["k", "<Up>"]),
+    info(Action::ColumnRight, "column-right", MOTION, "scroll right 1 column", &["l", "<Right>"]),
+    info(Action::ColumnLeft, "column-left", MOTION, "scroll left 1 column", &["h", "<Left>"]),
+    info(Action::ScreenDown, "screen-down", MOTION, "scroll down 1 screenful",
+        &["J", "<Space>", "<S-Down>"]),
+    info(Action::ScreenUp, "screen-up", MOTION, "scroll up 1 screenful", &["K", "<S-Up>"]),
+    info(Action::ScreenRight, "screen-right", MOTION, "scroll right 1 screenful",
+        &["L", "<S-Right>"]),
+    info(Action::ScreenLeft, "screen-left", MOTION, "scroll left 1 screenful", &["H", "<S-Left>"]),
+    info(Action::Bottom, "bottom", MOTION, "go to the bottom", &["G"]),
+    info(Action::Top, "top", MOTION, "go to the top", &["g"]),
+    info(Action::FullRight, "full-right", MOTION, "go to the last column", &["$"]),
+    info(Action::FullLeft, "full-left", MOTION, "go to the first column", &["^"]),
+    info(Action::GoTo, "go-to", MOTION,
+        "go to column (1830), sequence (#12), or residue (P12345:245, #12:245)", &[":"]),
+    info(Action::Search, "search", SEARCH,
+        "search for a motif (regex or PROSITE pattern, e.g. C-x(2)-[ST])", &["/"]),
+    info(Action::NextMatch, "next-match", SEARCH, "go to the next hit or matching label", &["n"]),
+    info(Action::PreviousMatch, "previous-match", SEARCH,
+        "go to the previous hit or matching label", &["N"]),
+    info(Action::FindLabel, "find-label", SEARCH, "find a sequence by label", &["F"]),
+    info(Action::FilterLabels, "filter-labels", SEARCH,
+        "only show sequences whose label matches (empty: show all)", &["&"]),
+    info(Action::ToggleSubsetStats, "toggle-subset-stats", SEARCH,
+        "toggle statistics of shown sequences only / whole alignment", &["S"]),
+    info(Action::SelectRows, "select-rows", SELECTION, "select sequences (again: stop)", &["V"]),
+    info(Action::SelectBlock, "select-block", SELECTION,
+        "select a block of sequences and columns (again: stop)", &["<C-v>"]),
+    info(Action::ShowHidden, "show-hidden", SELECTION, "show hidden sequences again", &["X"]),
+    selection_info(Action::EndSelection, "end-selection", "stop selecting", &["<Esc>"]),
+    selection_info(Action::CopySelection, "copy-selection", "copy (FastA) to the clipboard",
+        &["y"]),
+    selection_info(Action::WriteSelection, "write-selection", "write to a file", &["w"]),
+    selection_info(Action::SelectionStats, "selection-stats", "statistics of the selection",
+        &["S"]),
+    selection_info(Action::HideSelection, "hide-selection", "hide the selected sequences", &["x"]),
+    selection_info(Action::HideOthers, "hide-others", "hide all the other sequences", &["X"]),
+    info(Action::SetReference, "set-reference", REFERENCE,
+        "set the reference (#12, an ID, or 'consensus'; empty: none)", &["R"]),
+    info(Action::ToggleReferenceNumbering, "toggle-reference-numbering", REFERENCE,
+        "number positions after the reference's residues / the columns", &["u"]),
+    info(Action::CycleZoom, "cycle-zoom", ZOOMING, "cycle through zoom modes", &["z"]),
+    info(Action::CycleZoomBack, "cycle-zoom-back", ZOOMING, "cycle back through zoom modes",
+        &["Z"]),
+    info(Action::ToggleZoombox, "toggle-zoom-box", ZOOMING, "hide/show the zoom box", &["B"]),
+    info(Action::ToggleZoomboxGuides, "toggle-zoom-box-guides", ZOOMING,
+        "hide/show the zoom box guides", &["v"]),
+    info(Action::HighlightRetainedColumns, "highlight-retained-columns", ZOOMING,
+        "highlight zoom box residues in consensus", &["r"]),
+    info(Action::WidenLabelPane, "widen-label-pane", PANES, "widen label pane", &[">"]),
+    info(Action::NarrowLabelPane, "narrow-label-pane", PANES, "narrow label pane", &["<"]),
+    info(Action::ToggleLabelPane, "toggle-label-pane", PANES, "hide/show label pane", &["a"]),
+    info(Action::ToggleBottomPane, "toggle-bottom-pane", PANES, "hide/show consensus pane",
+        &["c"]),
+    info(Action::ToggleLogo, "toggle-logo", PANES, "hide/show the sequence logo (bottom pane)",
+        &["O"]),
+    info(Action::ToggleTreePane, "toggle-tree-pane", PANES,
+        "hide/show the tree (when ordered by a tree, see 'o' and 'd')", &["T"]),
+    info(Action::ToggleFullScreen, "toggle-full-screen", PANES,
+        "toggle fullscreen alignment pane", &["f"]),
+    info(Action::CycleBottomPanePosition, "cycle-bottom-pane-position", PANES,
+        "bottom pane at the bottom of the screen / under the alignment", &["b"]),
+    info(Action::ToggleInsertColumns, "toggle-insert-columns", PANES,
+        "hide/show insert columns (A2M/A3M)", &["I"]),
+    info(Action::Export, "export", EXPORT, "write sequences (in current order) to a file",
+        &["w"]),
+    info(Action::ExportZoombox, "export-zoom-box", EXPORT,
+        "same, but only the zoom box's columns", &["W"]),
+    info(Action::Figure, "figure", EXPORT,
+        "save a picture (SVG, or HTML if .html) of the whole alignment", &["p"]),
+    info(Action::FigureZoombox, "figure-zoom-box", EXPORT, "same, but only the zoom box", &["P"]),
+    info(Action::NextColorScheme, "next-color-scheme", VIDEO,
+        "next theme (dark, light, monochrome)", &["s"]),
+    info(Action::NextColormap, "next-colormap", VIDEO, "next color map", &["m"]),
+    info(Action::ToggleVideoMode, "toggle-video-mode", VIDEO, "toggle inverse/direct video",
+        &["i"]),
+    info(Action::NextOrdering, "next-ordering", METRICS,
+        "next ordering (file, metric up/down, tree from --tree)", &["o"]),
+    info(Action::NextGuideTree, "next-guide-tree", METRICS,
+        "order by a guide tree (NJ, again: UPGMA)", &["d"]),
+    info(Action::NextMetric, "next-metric", METRICS, "next metric", &["t"]),
+    info(Action::OrderBySimilarity, "order-by-similarity", METRICS,
+        "order by identity to a sequence (#12 or an ID)", &["%"]),
+    info(Action::ToggleIdentityMap, "toggle-identity-map", METRICS,
+        "show pairwise identities instead of the alignment (again: hide)", &["M"]),
+    info(Action::Help, "help", OTHER, "show this help", &["?"]),
+    info(Action::Quit, "quit", OTHER, "quit", &["q", "Q", "<C-c>"]),
+];
+
+// Extra lines shown at the end of some sections of the help screen.
+const NOTES: &[(&str, &str)] = &[
+    (MOTION, "Mouse: the wheel scrolls (with Shift: sideways); clicking a label focuses the\n\
+              sequence, clicking a residue shows where it is; zoomed out, drag the zoom box."),
+    (SELECTION, "While selecting, the motion keys extend the selection, and the keys below act\n\
+                 on it:"),
+    (EXPORT, "(format from extension: .sto .aln .phy .nex .a2m .a3m, else FastA)"),
+    (VIDEO, "Try dark/inverse for best results (this is the default)."),
+];
+
+// A key, as bound: letters carry their case, so Shift only counts for other keys (e.g. <S-Down>).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Key {
+        let mut modifiers = event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+        if let KeyCode::Char(_) = event.code {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Key {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+// Keys are written as for --keys (see parse_keys()), except that '<' may be written as is.
+fn parse_key(name: &str) -> Result<Key, String> {
+    if name == "<" {
+        return Ok(Key::from(KeyEvent::from(KeyCode::Char('<'))));
+    }
+    match parse_keys(name)?.as_slice() {
+        [event] => Ok(Key::from(*event)),
+        _ => Err(format!("'{}' is not a single key", name)),
+    }
+}
+
+fn key_name(key: &Key) -> String {
+    let base = match key.code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) if key.modifiers.is_empty() => return c.to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Enter => "Enter".to_string(),
+        KeyCode::Esc => "Esc".to_string(),
+        KeyCode::Backspace => "BS".to_string(),
+        other => format!("{:?}", other),
+    };
+    let prefix = if key.modifiers.contains(KeyModifiers::CONTROL) {
+        "C-"
+    } else if key.modifiers.contains(KeyModifiers::SHIFT) {
+        "S-"
+    } else {
+        ""
+    };
+    format!("<{}{}>", prefix, base)
+}
+
+pub struct Keymap {
+    // The keys of each action, in the order of ACTIONS.
+    keys: Vec<Vec<Key>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let keys = ACTIONS
+            .iter()
+            .map(|info| {
+                info.default_keys
+                    .iter()
+                    .map(|k| parse_key(k).expect("bad default key"))
+                    .collect()
+            })
+            .collect();
+        Keymap { keys }
+    }
+}
+
+impl Keymap {
+    // The default keymap, modified by the [keys] table of the configuration file (if any). Each
+    // entry maps an action's name to a key or a list of keys.
+    pub fn from_config(keys: Option<&toml::Table>) -> Result<Keymap, String> {
+        let mut keymap = Keymap::default();
+        let Some(keys) = keys else {
+            return Ok(keymap);
+        };
+        for (name, value) in keys {
+            let Some(i) = ACTIONS.iter().position(|info| info.name == name) else {
+                return Err(format!("unknown action '{}'", name));
+            };
+            let names: Vec<&str> = match value {
+                toml::Value::String(key) => vec![key.as_str()],
+                toml::Value::Array(keys) => keys
+                    .iter()
+                    .map(|k| k.as_str().ok_or(format!("{}: keys must be strings", name)))
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(format!("{}: expected a key or a list of keys", name)),
+            };
+            let new_keys = names
+                .iter()
+                .map(|k| parse_key(k).map_err(|e| format!("{}: {}", name, e)))
+                .collect::<Result<Vec<Key>, String>>()?;
+            keymap.bind(i, new_keys);
+        }
+        Ok(keymap)
+    }
+
+    // Gives these keys to the i-th action (only), in its context.
+    fn bind(&mut self, i: usize, new_keys: Vec<Key>) {
+        let context = ACTIONS[i].context;
+        for (j, keys) in self.keys.iter_mut().enumerate() {
+            if ACTIONS[j].context == context {
+                keys.retain(|k| !new_keys.contains(k));
+            }
+        }
+        self.keys[i] = new_keys;
+    }
+
+    fn lookup(&self, key: Key, context: Context) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .zip(&self.keys)
+            .find(|(info, keys)| info.context == context && keys.contains(&key))
+            .map(|(info, _)| info.action)
+    }
+
+    pub fn action(&self, event: KeyEvent) -> Option<Action> {
+        self.lookup(Key::from(event), Context::Normal)
+    }
+
+    // Actions that only apply to a selection.
+    pub fn selection_action(&self, event: KeyEvent) -> Option<Action> {
+        self.lookup(Key::from(event), Context::Selection)
+    }
+
+    // With `names`, each line ends with the action's name in the configuration file, e.g.
+    // "[line-down]" (as in `termal -b`).
+    pub fn help_text(&self, names: bool) -> String {
+        let entries: Vec<(String, &ActionInfo)> = ACTIONS
+            .iter()
+            .zip(&self.keys)
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(info, keys)| {
+                let names: Vec<String> = keys.iter().map(key_name).collect();
+                (names.join(","), info)
+            })
+            .collect();
+        let width = entries.iter().map(|(keys, _)| keys.chars().count()).max().unwrap_or(0);
+
+        let mut text = String::from("# Key Bindings\n\n");
+        text += "Run `termal -b` to see this message if it doesn't fit on screen. Keys can be\n";
+        text += "remapped in the configuration file (see the manual).\n";
+        let mut sections: Vec<&str> = ACTIONS.iter().map(|info| info.section).collect();
+        sections.dedup();
+        for section in sections {
+            let _ = write!(text, "\n## {}\n\n", section);
+            let note = NOTES.iter().find(|(s, _)| *s == section).map(|(_, note)| *note);
+            // In the Selection section, the note introduces the selection-only keys; elsewhere,
+            // it comes last.
+            for context in [Context::Normal, Context::Selection] {
+                if context == Context::Selection {
+                    if let Some(note) = note {
+                        let _ = writeln!(text, "\n{}\n", note);
+                    }
+                }
+                for (keys, info) in &entries {
+                    if info.section == section && info.context == context {
+                        let _ = write!(text, "{:<width$} : {}", keys, info.description);
+                        if names {
+                            let _ = write!(text, " [{}]", info.name);
+                        }
+                        text.push('\n');
+                    }
+                }
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+        let none = KeyModifiers::NONE;
+        assert_eq!(keymap.action(key(KeyCode::Char('j'), none)), Some(Action::LineDown));
+        // Terminals report capitals with Shift.
+        let shift = KeyModifiers::SHIFT;
+        assert_eq!(keymap.action(key(KeyCode::Char('J'), shift)), Some(Action::ScreenDown));
+        assert_eq!(keymap.action(key(KeyCode::Down, shift)), Some(Action::ScreenDown));
+        let control = KeyModifiers::CONTROL;
+        assert_eq!(keymap.action(key(KeyCode::Char('c'), none)), Some(Action::ToggleBottomPane));
+        assert_eq!(keymap.action(key(KeyCode::Char('c'), control)), Some(Action::Quit));
+        assert_eq!(keymap.action(key(KeyCode::Char('x'), none)), None);
+        assert_eq!(
+            keymap.selection_action(key(KeyCode::Char('x'), none)),
+            Some(Action::HideSelection)
+        );
+    }
+
+    #[test]
+    fn test_remapping() {
+        let keys: toml::Table = "line-down = [\"<C-n>\", \"<Down>\"]\nquit = \"j\""
+            .parse()
+            .unwrap();
+        let keymap = Keymap::from_config(Some(&keys)).unwrap();
+        let control = KeyModifiers::CONTROL;
+        assert_eq!(keymap.action(key(KeyCode::Char('n'), control)), Some(Action::LineDown));
+        // 'j' went to quit, and 'q' is no longer bound to it.
+        assert_eq!(keymap.action(KeyEvent::from(KeyCode::Char('j'))), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyEvent::from(KeyCode::Char('q'))), None);
+        let help = keymap.help_text(false);
+        let entry = |keys: &str, description: &str| {
+            help.lines().any(|l| {
+                l.split_once(" : ")
+                    .is_some_and(|(k, d)| k.trim_end() == keys && d == description)
+            })
+        };
+        assert!(entry("<C-n>,<Down>", "scroll down 1 line (zoomed out: move the zoom box)"));
+        assert!(entry("j", "quit"));
+        assert!(keymap.help_text(true).lines().any(|l| l.ends_with(": quit [quit]")));
+    }
+
+    #[test]
+    fn test_bad_config() {
+        let bad = |text: &str| Keymap::from_config(Some(&text.parse().unwrap())).is_err();
+        assert!(bad("fly = \"f\""));
+        assert!(bad("quit = \"<PgDn>\""));
+        assert!(bad("quit = \"qq\""));
+        assert!(bad("quit = 3"));
+    }
+}
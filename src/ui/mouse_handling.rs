@@ -0,0 +1,282 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Mouse events: the wheel scrolls (like the arrow keys; with Shift, sideways), clicking a label
+ * focuses that sequence, clicking a residue tells which one it is, and (when zoomed out) dragging
+ * the zoom box moves the view. Screen positions are mapped back to sequences and columns using
+ * the pane areas stored by render_ui(). */
+
+use crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::{Position, Rect};
+
+use crate::{
+    alignment::is_gap,
+    fasta::seq_id,
+    ui::render::{retained_col_ndx, retained_seq_ndx, shown_rows},
+    ZoomLevel, UI,
+};
+
+use super::ZoomboxDrag;
+
+pub fn handle_mouse_event(ui: &mut UI, event: MouseEvent) {
+    if ui.prompt.is_some() {
+        return;
+    }
+    if ui.show_help {
+        if let MouseEventKind::Down(_) = event.kind {
+            ui.show_help = false;
+        }
+        return;
+    }
+    let sideways = event.modifiers.contains(KeyModifiers::SHIFT);
+    match event.kind {
+        MouseEventKind::ScrollDown if sideways => scroll_right(ui),
+        MouseEventKind::ScrollUp if sideways => scroll_left(ui),
+        MouseEventKind::ScrollDown => scroll_down(ui),
+        MouseEventKind::ScrollUp => scroll_up(ui),
+        MouseEventKind::ScrollRight => scroll_right(ui),
+        MouseEventKind::ScrollLeft => scroll_left(ui),
+        MouseEventKind::Down(MouseButton::Left) => click(ui, event.column, event.row),
+        MouseEventKind::Drag(MouseButton::Left) => drag_zoombox(ui, event.column, event.row),
+        MouseEventKind::Up(MouseButton::Left) => ui.zoombox_drag = None,
+        _ => {}
+    }
+}
+
+fn scroll_down(ui: &mut UI) {
+    match ui.zoom_level() {
+        ZoomLevel::ZoomedIn => ui.scroll_one_line_down(),
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_line_down(),
+    }
+}
+
+fn scroll_up(ui: &mut UI) {
+    match ui.zoom_level() {
+        ZoomLevel::ZoomedIn => ui.scroll_one_line_up(),
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_line_up(),
+    }
+}
+
+fn scroll_right(ui: &mut UI) {
+    match ui.zoom_level() {
+        ZoomLevel::ZoomedIn => ui.scroll_one_col_right(),
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_col_right(),
+    }
+}
+
+fn scroll_left(ui: &mut UI) {
+    match ui.zoom_level() {
+        ZoomLevel::ZoomedIn => ui.scroll_one_col_left(),
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => ui.scroll_zoombox_one_col_left(),
+    }
+}
+
+// The panes have a top border, so the first sequence is on the second line. Returns the row (in
+// the current ordering) shown on screen line y, if any.
+fn row_at(ui: &UI, area: Rect, y: u16) -> Option<usize> {
+    if y <= area.y || y + 1 >= area.bottom() {
+        return None;
+    }
+    let line = (y - area.y - 1) as usize;
+    match ui.zoom_level {
+        ZoomLevel::ZoomedIn => {
+            let row = ui.top_line as usize + line;
+            (row < ui.app.num_seq() as usize).then_some(row)
+        }
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => retained_seq_ndx(ui).get(line).copied(),
+    }
+}
+
+// Same as row_at(), for the alignment column shown on screen column x (the alignment pane also
+// has a left border).
+fn col_at(ui: &UI, area: Rect, x: u16) -> Option<usize> {
+    if x <= area.x || x + 1 >= area.right() {
+        return None;
+    }
+    let n = (x - area.x - 1) as usize;
+    match ui.zoom_level {
+        ZoomLevel::ZoomedIn => {
+            let col = ui.leftmost_col as usize + n;
+            (col < ui.app.aln_len() as usize).then_some(col)
+        }
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => retained_col_ndx(ui).get(n).copied(),
+    }
+}
+
+fn click(ui: &mut UI, x: u16, y: u16) {
+    let position = Position::new(x, y);
+    if let Some(area) = ui.label_pane_area.filter(|a| a.contains(position)) {
+        if let Some(row) = row_at(ui, area, y) {
+            focus_seq(ui, ui.app.ordering[row]);
+        }
+    } else if let Some(area) = ui.aln_pane_area.filter(|a| a.contains(position)) {
+        if ui.show_identity_map {
+            if let Some(info) = identity_info(ui, area, x, y) {
+                ui.message = info;
+            }
+            return;
+        }
+        if let (Some(row), Some(col)) = (row_at(ui, area, y), col_at(ui, area, x)) {
+            ui.message = residue_info(ui, ui.app.ordering[row], col);
+        }
+        if ui.zoom_level != ZoomLevel::ZoomedIn && in_zoombox(ui, area, x, y) {
+            ui.zoombox_drag = Some(ZoomboxDrag {
+                from: (x, y),
+                top_line: ui.top_line,
+                leftmost_col: ui.leftmost_col,
+            });
+        }
+    }
+}
+
+// The identity map's cell at (x, y), e.g. " P12345 / Q67890: 87.5% identity (p-distance 0.125) ".
+// Its columns are the shown sequences, like its lines.
+fn identity_info(ui: &UI, area: Rect, x: u16, y: u16) -> Option<String> {
+    if x <= area.x || x + 1 >= area.right() {
+        return None;
+    }
+    let row1 = row_at(ui, area, y)?;
+    let row2 = *shown_rows(ui).get((x - area.x - 1) as usize)?;
+    let (seq1, seq2) = (ui.app.ordering[row1], ui.app.ordering[row2]);
+    let identity = ui.app.pairwise_identity(seq1, seq2)?;
+    Some(format!(
+        " {} / {}: {:.1}% identity (p-distance {:.3}) ",
+        seq_id(&ui.app.alignment.headers[seq1]),
+        seq_id(&ui.app.alignment.headers[seq2]),
+        identity * 100.0,
+        1.0 - identity
+    ))
+}
+
+// Clicking the focused sequence's label again removes the focus.
+fn focus_seq(ui: &mut UI, seq: usize) {
+    if ui.focused_seq == Some(seq) {
+        ui.focused_seq = None;
+        ui.message = String::new();
+    } else {
+        ui.focused_seq = Some(seq);
+        ui.message = format!(" #{} {} ", seq + 1, ui.app.alignment.headers[seq]);
+    }
+}
+
+// E.g. " P12345 | column 245 | K, residue 120 ", with 1-based numbers; residues are numbered as
+// in the ungapped sequence.
+fn residue_info(ui: &UI, seq: usize, col: usize) -> String {
+    let id = seq_id(&ui.app.alignment.headers[seq]);
+    let sequence = &ui.app.alignment.sequences[seq];
+    let residue = sequence.chars().nth(col).unwrap_or('-');
+    if is_gap(residue) {
+        format!(" {} | column {} | gap ", id, col + 1)
+    } else {
+        let pos = sequence.chars().take(col + 1).filter(|c| !is_gap(*c)).count();
+        format!(" {} | column {} | {}, residue {} ", id, col + 1, residue, pos)
+    }
+}
+
+fn in_zoombox(ui: &UI, area: Rect, x: u16, y: u16) -> bool {
+    if x <= area.x || y <= area.y {
+        return false;
+    }
+    let (col, line) = ((x - area.x - 1) as usize, (y - area.y - 1) as usize);
+    let top = ui.zoombox_top();
+    let bottom = ui.zoombox_bottom(retained_seq_ndx(ui).len()).max(top + 1);
+    let left = ui.zoombox_left();
+    let right = ui.zoombox_right(retained_col_ndx(ui).len()).max(left + 1);
+    (top..bottom).contains(&line) && (left..right).contains(&col)
+}
+
+// Moving the zoom box by one cell moves the view by about 1/ratio sequences or columns.
+fn drag_zoombox(ui: &mut UI, x: u16, y: u16) {
+    let Some(drag) = ui.zoombox_drag else {
+        return;
+    };
+    let (h_ratio, v_ratio) = match ui.zoom_level {
+        ZoomLevel::ZoomedIn => return,
+        ZoomLevel::ZoomedOut => (ui.h_ratio(), ui.v_ratio()),
+        ZoomLevel::ZoomedOutAR => (ui.common_ratio(), ui.common_ratio()),
+    };
+    let moved = |start: u16, from: u16, to: u16, ratio: f64, max: u16| {
+        let shift = (to as f64 - from as f64) / ratio;
+        (start as f64 + shift).round().clamp(0.0, max as f64) as u16
+    };
+    ui.leftmost_col = moved(drag.leftmost_col, drag.from.0, x, h_ratio, ui.max_leftmost_col());
+    ui.top_line = moved(drag.top_line, drag.from.1, y, v_ratio, ui.max_top_line());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::App;
+    use crate::ui::render::render_ui;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    fn draw(ui: &mut UI, width: u16, height: u16) {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|f| render_ui(f, ui)).unwrap();
+    }
+
+    fn mouse(kind: MouseEventKind, column: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    #[test]
+    fn test_click_residue_and_label() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        let mut ui = UI::new(&mut app);
+        draw(&mut ui, 80, 24);
+        let aln = ui.aln_pane_area.unwrap();
+        let left_click = MouseEventKind::Down(MouseButton::Left);
+        // seq1 is TTGCCG-CGA
+        handle_mouse_event(&mut ui, mouse(left_click, aln.x + 3, aln.y + 1));
+        assert_eq!(ui.message, " seq1 | column 3 | G, residue 3 ");
+        handle_mouse_event(&mut ui, mouse(left_click, aln.x + 7, aln.y + 1));
+        assert_eq!(ui.message, " seq1 | column 7 | gap ");
+        handle_mouse_event(&mut ui, mouse(left_click, aln.x + 10, aln.y + 1));
+        assert_eq!(ui.message, " seq1 | column 10 | A, residue 9 ");
+
+        let labels = ui.label_pane_area.unwrap();
+        handle_mouse_event(&mut ui, mouse(left_click, labels.x + 5, labels.y + 2));
+        assert_eq!(ui.focused_seq, Some(1));
+        handle_mouse_event(&mut ui, mouse(left_click, labels.x + 5, labels.y + 2));
+        assert_eq!(ui.focused_seq, None);
+    }
+
+    #[test]
+    fn test_wheel_scrolls() {
+        let mut app = App::new("data/example-1.msa").unwrap();
+        let mut ui = UI::new(&mut app);
+        draw(&mut ui, 80, 16);
+        handle_mouse_event(&mut ui, mouse(MouseEventKind::ScrollDown, 30, 5));
+        assert_eq!(ui.top_line, 1);
+        let mut shift_wheel = mouse(MouseEventKind::ScrollDown, 30, 5);
+        shift_wheel.modifiers = KeyModifiers::SHIFT;
+        handle_mouse_event(&mut ui, shift_wheel);
+        assert_eq!(ui.leftmost_col, 1);
+    }
+
+    #[test]
+    fn test_drag_zoombox() {
+        let mut app = App::new("data/example-1.msa").unwrap();
+        let mut ui = UI::new(&mut app);
+        draw(&mut ui, 80, 24);
+        ui.cycle_zoom();
+        draw(&mut ui, 80, 24);
+        let aln = ui.aln_pane_area.unwrap();
+        let (x, y) = (aln.x + 1 + ui.zoombox_left() as u16, aln.y + 1 + ui.zoombox_top() as u16);
+        handle_mouse_event(&mut ui, mouse(MouseEventKind::Down(MouseButton::Left), x, y));
+        handle_mouse_event(&mut ui, mouse(MouseEventKind::Drag(MouseButton::Left), x + 10, y));
+        let expected = (10.0 / ui.h_ratio()).round() as u16;
+        assert_eq!(ui.leftmost_col, expected);
+        handle_mouse_event(&mut ui, mouse(MouseEventKind::Up(MouseButton::Left), x + 10, y));
+        assert!(ui.zoombox_drag.is_none());
+        // Dragging back past the start stops at the first column.
+        handle_mouse_event(&mut ui, mouse(MouseEventKind::Down(MouseButton::Left), x + 10, y));
+        handle_mouse_event(&mut ui, mouse(MouseEventKind::Drag(MouseButton::Left), 0, y));
+        assert_eq!(ui.leftmost_col, 0);
+    }
+}
@@ -0,0 +1,51 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* A one-line text input, shown on the last line of the screen, for commands that need an argument
+ * (such as a file name). While a prompt is active, key presses go to it rather than to the usual
+ * bindings (see key_handling.rs). */
+
+use std::ops::Range;
+
+// What to do with the input once the user presses Enter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PromptAction {
+    // Write the sequences (in the current order) to a file, optionally only the given columns.
+    Export(Option<Range<usize>>),
+    // Write the selected sequences (indices into the alignment) and columns to a file.
+    ExportSelection(Vec<usize>, Range<usize>),
+    // Write a picture of the given rows (in the current order) and columns (see figure.rs).
+    Figure(Range<usize>, Range<usize>),
+    // Search for a motif (regex or PROSITE pattern); an empty pattern clears the search.
+    Search,
+    // Go to the first sequence whose header matches (a regex), at or after the top line.
+    LabelSearch,
+    // Only show sequences whose header matches; an empty pattern shows them all again.
+    LabelFilter,
+    // Go to a column, a sequence, or a residue of a sequence (see goto.rs).
+    GoTo,
+    // Set the reference sequence (a sequence, or the consensus); empty input unsets it.
+    Reference,
+    // Order the sequences by identity to a sequence (see App::order_by_similarity()).
+    Similarity,
+}
+
+pub struct Prompt {
+    pub label: String,
+    pub input: String,
+    pub action: PromptAction,
+}
+
+impl Prompt {
+    pub fn new(label: &str, action: PromptAction) -> Prompt {
+        Prompt {
+            label: label.to_string(),
+            input: String::new(),
+            action,
+        }
+    }
+
+    pub fn text(&self) -> String {
+        format!("{}{}", self.label, self.input)
+    }
+}
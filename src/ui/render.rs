@@ -10,12 +10,14 @@ use ratatui::{
 use log::debug;
 
 use crate::{
+    alignment::is_gap,
     ui::{
         barchart::{value_to_hbar, values_barchart},
+        color_map::ColorMap,
         color_scheme::Theme,
-        AlnWRTSeqPane, BottomPanePosition, VideoMode, 
+        prompt::Prompt,
+        AlnWRTSeqPane, BottomPanePosition, VideoMode, LOGO_HEIGHT,
     },
-    vec_f64_aux::{normalize, ones_complement, product},
     ZoomLevel, UI,
 };
 
@@ -25,7 +27,7 @@ use crate::{
  * for all zoom levels
 *****************************************************************/
 
-fn retained_col_ndx(ui: &UI) -> Vec<usize> {
+pub(super) fn retained_col_ndx(ui: &UI) -> Vec<usize> {
     match ui.zoom_level {
         ZoomLevel::ZoomedIn => {
             panic!("should not be called in zoomed-in mode")
@@ -40,7 +42,7 @@ fn retained_col_ndx(ui: &UI) -> Vec<usize> {
     }
 }
 
-fn retained_seq_ndx(ui: &UI) -> Vec<usize> {
+pub(super) fn retained_seq_ndx(ui: &UI) -> Vec<usize> {
     match ui.zoom_level {
         ZoomLevel::ZoomedIn => {
             panic!("should not be called in zoomed-in mode")
@@ -67,8 +69,21 @@ fn retained_seq_ndx(ui: &UI) -> Vec<usize> {
     }
 }
 
+// Rows (in the current ordering) of the sequences shown on screen, from top to bottom.
+pub(super) fn shown_rows(ui: &UI) -> Vec<usize> {
+    match ui.zoom_level {
+        ZoomLevel::ZoomedIn => {
+            let top = ui.top_line as usize;
+            let bottom = (top + ui.max_nb_seq_shown() as usize).min(ui.app.num_seq() as usize);
+            (top..bottom).collect()
+        }
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => retained_seq_ndx(ui),
+    }
+}
+
 fn compute_label_numbers<'a>(ui: &UI) -> Vec<Line<'a>> {
-    let num_cols = ui.app.num_seq().ilog10() as usize + 1;
+    // Numbers are those of the whole alignment, even if some sequences are filtered out.
+    let num_cols = ui.app.alignment.num_seq().ilog10() as usize + 1;
     let numbers = ui
         .app
         .ordering
@@ -107,15 +122,28 @@ fn compute_seq_metrics<'a>(ui: &UI) -> Vec<Line<'a>> {
     }
 }
 
+// The label of the sequence clicked last (if any) is highlighted, as are those of the selected
+// sequences.
+fn label_style(ui: &UI, row: usize, seq: usize) -> Style {
+    let selected = ui.selection.is_some_and(|s| s.rows().contains(&row));
+    if ui.focused_seq == Some(seq) || selected {
+        Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD)
+    } else {
+        Style::default()
+    }
+}
+
 fn zoom_in_lbl_text<'a>(ui: &UI) -> Vec<Line<'a>> {
     ui.app
         .ordering
         .iter()
-        .map(|i| {
-            Line::from(Span::raw(
+        .enumerate()
+        .map(|(row, i)| {
+            Line::from(Span::styled(
                 // TODO: this clne should be avoidable, since Span takes a Cow. This would of
                 // course entail some lifetime wrangling.
                 ui.app.alignment.headers[*i].clone(),
+                label_style(ui, row, *i),
             ))
         })
         .collect()
@@ -125,9 +153,11 @@ fn zoom_out_lbl_text<'a>(ui: &UI) -> Vec<Line<'a>> {
     let mut ztext: Vec<Line> = Vec::new();
 
     for i in retained_seq_ndx(ui) {
-        ztext.push(Line::from(
-            ui.app.alignment.headers[ui.app.ordering[i]].clone(),
-        ));
+        let seq = ui.app.ordering[i];
+        ztext.push(Line::from(Span::styled(
+            ui.app.alignment.headers[seq].clone(),
+            label_style(ui, i, seq),
+        )));
     }
 
     ztext
@@ -164,19 +194,75 @@ fn get_residue_style(video_mode: VideoMode, theme: Theme, color: Color) -> Style
         }
     }
 
-    match video_mode {
-        VideoMode::Inverse  => {
-            style = style.add_modifier(Modifier::REVERSED);
-            if Theme::Light == theme {
-                style = style.bg(Color::Black);
-            }
-        },
-        _ => { },
+    if video_mode == VideoMode::Inverse {
+        style = style.add_modifier(Modifier::REVERSED);
+        if Theme::Light == theme {
+            style = style.bg(Color::Black);
+        }
     }
 
     style
 }
 
+// Motif search hits stand out by their video mode (the opposite of the other residues'), and are
+// in bold and underlined, which also works without color.
+fn get_hit_style(style: Style) -> Style {
+    let style = if style.add_modifier.contains(Modifier::REVERSED) {
+        style.remove_modifier(Modifier::REVERSED)
+    } else {
+        style.add_modifier(Modifier::REVERSED)
+    };
+    style.add_modifier(Modifier::BOLD | Modifier::UNDERLINED)
+}
+
+// Selected cells are shown in the opposite video mode to the others.
+fn get_selection_style(style: Style) -> Style {
+    if style.add_modifier.contains(Modifier::REVERSED) {
+        style.remove_modifier(Modifier::REVERSED)
+    } else {
+        style.add_modifier(Modifier::REVERSED)
+    }
+}
+
+// Residues identical to the reference's are shown as the match character, dimmed. Gaps are always
+// shown, as are the residues of the reference sequence itself.
+fn is_reference_match(ui: &UI, reference: Option<&[u8]>, seq: usize, col: usize, c: char) -> bool {
+    match reference {
+        Some(ref_residues) if ui.reference_seq() != Some(seq) && !is_gap(c) => {
+            (ref_residues[col] as char).eq_ignore_ascii_case(&c)
+        }
+        _ => false,
+    }
+}
+
+fn is_selected(ui: &UI, row: usize, col: usize) -> bool {
+    ui.selection.is_some_and(|s| s.contains(row, col))
+}
+
+// A cell of the alignment pane, for any zoom level. `row` is the sequence's position in the
+// current ordering, `seq` its index in the alignment.
+fn residue_span<'a>(
+    ui: &UI,
+    colormap: &ColorMap,
+    reference: Option<&[u8]>,
+    (row, seq): (usize, usize),
+    col: usize,
+    c: char,
+) -> Span<'a> {
+    let (shown, mut style) = if is_reference_match(ui, reference, seq, col, c) {
+        (ui.match_char, Style::default().add_modifier(Modifier::DIM))
+    } else {
+        (c, get_residue_style(ui.video_mode, ui.theme(), colormap.get(c)))
+    };
+    if ui.app.is_hit(seq, col) {
+        style = get_hit_style(style);
+    }
+    if is_selected(ui, row, col) {
+        style = get_selection_style(style);
+    }
+    Span::styled(shown.to_string(), style)
+}
+
 fn zoom_in_seq_text<'a>(ui: &'a UI) -> Vec<Line<'a>> {
     let top_i = ui.top_line as usize;
     let bot_i = (ui.top_line + ui.max_nb_seq_shown()) as usize;
@@ -189,22 +275,19 @@ fn zoom_in_seq_text<'a>(ui: &'a UI) -> Vec<Line<'a>> {
     // to zoom_out_lbl_text() and zoom_out_ar_seq_text().
     let colormap = ui.color_scheme().current_residue_colormap();
     let ordering = &ui.app.ordering;
+    let reference = ui.reference_residues().map(str::as_bytes);
 
-    for i in top_i..bot_i {
-        if i >= ui.app.num_seq().into() {
-            break;
-        } // if there is extra vertical space
+    // take() stops early if there is extra vertical space
+    for (row, seq_i) in ordering.iter().enumerate().take(bot_i).skip(top_i) {
         let mut spans: Vec<Span> = Vec::new();
         for j in lft_j..rgt_j {
             if j >= ui.app.aln_len().into() {
                 break;
-            } // ", horizontal
-            let cur_seq_ref = &ui.app.alignment.sequences[ordering[i]];
+            } // if there is extra horizontal space
+            let cur_seq_ref = &ui.app.alignment.sequences[*seq_i];
             // TODO: is the conversion to bytes done at _each_ iteration?
             let cur_char = (*cur_seq_ref).as_bytes()[j] as char;
-            let style = get_residue_style(ui.video_mode,
-                ui.theme(), colormap.get(cur_char));
-            spans.push(Span::styled(cur_char.to_string(), style));
+            spans.push(residue_span(ui, colormap, reference, (row, *seq_i), j, cur_char));
         }
         text.push(Line::from(spans));
     }
@@ -215,6 +298,7 @@ fn zoom_in_seq_text<'a>(ui: &'a UI) -> Vec<Line<'a>> {
 fn zoom_out_seq_text<'a>(ui: &UI) -> Vec<Line<'a>> {
     let colormap = ui.color_scheme().current_residue_colormap();
     let ordering = &ui.app.ordering;
+    let reference = ui.reference_residues().map(str::as_bytes);
 
     let mut ztext: Vec<Line> = Vec::new();
     for i in retained_seq_ndx(ui) {
@@ -223,10 +307,7 @@ fn zoom_out_seq_text<'a>(ui: &UI) -> Vec<Line<'a>> {
         let mut spans: Vec<Span> = Vec::new();
         for j in retained_col_ndx(ui) {
             let cur_char: char = seq_chars[j];
-            let style = get_residue_style(ui.video_mode,
-                ui.theme(), colormap.get(cur_char));
-            let span = Span::styled(cur_char.to_string(), style);
-            spans.push(span);
+            spans.push(residue_span(ui, colormap, reference, (i, ordering[i]), j, cur_char));
         }
         ztext.push(Line::from(spans));
     }
@@ -237,6 +318,7 @@ fn zoom_out_seq_text<'a>(ui: &UI) -> Vec<Line<'a>> {
 fn zoom_out_ar_seq_text<'a>(ui: &UI) -> Vec<Line<'a>> {
     let colormap = ui.color_scheme().current_residue_colormap();
     let ordering = &ui.app.ordering;
+    let reference = ui.reference_residues().map(str::as_bytes);
     let mut ztext: Vec<Line> = Vec::new();
     for i in retained_seq_ndx(ui) {
         let seq: &String = &ui.app.alignment.sequences[ordering[i]];
@@ -244,10 +326,7 @@ fn zoom_out_ar_seq_text<'a>(ui: &UI) -> Vec<Line<'a>> {
         let mut spans: Vec<Span> = Vec::new();
         for j in retained_col_ndx(ui) {
             let cur_char: char = seq_chars[j];
-            let style = get_residue_style(ui.video_mode,
-                ui.theme(), colormap.get(cur_char));
-            let span = Span::styled(cur_char.to_string(), style);
-            spans.push(span);
+            spans.push(residue_span(ui, colormap, reference, (i, ordering[i]), j, cur_char));
         }
         ztext.push(Line::from(spans));
     }
@@ -424,9 +503,7 @@ fn draw_zoombox_guides<'a>(aln_bottom: usize, aln_len: usize, ui: &'a UI<'a>) ->
         let left_guide_col = left_guide_pos(j);
         let right_guide_col = right_guide_pos(j);
         for i in 0..ui.max_nb_col_shown() as usize {
-            if i == left_guide_col {
-                line.push('.');
-            } else if i == right_guide_col {
+            if i == left_guide_col || i == right_guide_col {
                 line.push('.');
             } else {
                 line.push(' ');
@@ -482,7 +559,8 @@ fn mark_zoombox_ar(seq_para: &mut [Line], ui: &UI) {
 ****************************************************************/
 
 struct Panes {
-    // Top-left (labels) pane
+    // Top-left (labels) pane, with the tree (if any) on its left
+    tree: Rect,
     lbl_num: Rect,
     labels: Rect,
     seq_metrics: Rect,
@@ -528,9 +606,7 @@ fn max_num_seq(f: &Frame, ui: &UI) -> u16 {
                 "max #seq: {}",
                 (ui.app.num_seq() as f64 * ratio).round() as u16
             );
-            let max_num_seq = (ui.app.num_seq() as f64 * ratio).round() as u16;
-
-            max_num_seq
+            (ui.app.num_seq() as f64 * ratio).round() as u16
         }
     }
 }
@@ -577,18 +653,21 @@ fn make_layout(f: &Frame, ui: &UI) -> Panes {
     };
     let v_panes = Layout::new(Direction::Vertical, constraints).split(f.area());
 
+    // The tree pane widens the left panes rather than narrowing the labels.
+    let left_width = ui.label_pane_width + tree_pane_width(ui);
     let upper_panes = Layout::new(
         Direction::Horizontal,
-        vec![Constraint::Max(ui.label_pane_width), Constraint::Fill(1)],
+        vec![Constraint::Max(left_width), Constraint::Fill(1)],
     )
     .split(v_panes[0]);
     // number of columns for the label number pane :-)
     // which is 1 + the log_10 of the number of sequences (rounded down), plus room for the left
     // border.
-    let lbl_num_pane_num_cols = ui.app.num_seq().ilog10() + 2;
+    let lbl_num_pane_num_cols = ui.app.alignment.num_seq().ilog10() + 2;
     let lbl_pane = Layout::new(
         Direction::Horizontal,
         vec![
+            Constraint::Length(tree_pane_width(ui)),
             Constraint::Length(lbl_num_pane_num_cols.try_into().unwrap()),
             Constraint::Fill(1),
             Constraint::Length(3),
@@ -597,7 +676,7 @@ fn make_layout(f: &Frame, ui: &UI) -> Panes {
     .split(upper_panes[0]);
     let lower_panes = Layout::new(
         Direction::Horizontal,
-        vec![Constraint::Max(ui.label_pane_width), Constraint::Fill(1)],
+        vec![Constraint::Max(left_width), Constraint::Fill(1)],
     )
     .split(v_panes[1]);
 
@@ -605,9 +684,10 @@ fn make_layout(f: &Frame, ui: &UI) -> Panes {
     let help_dialog_pane = delineate_help_pane(f.area());
 
     Panes {
-        lbl_num: lbl_pane[0],
-        labels: lbl_pane[1],
-        seq_metrics: lbl_pane[2],
+        tree: lbl_pane[0],
+        lbl_num: lbl_pane[1],
+        labels: lbl_pane[2],
+        seq_metrics: lbl_pane[3],
         sequence: upper_panes[1],
         corner: lower_panes[0],
         bottom: lower_panes[1],
@@ -644,6 +724,40 @@ fn tick_position(aln_length: usize) -> String {
     intervals.join("")
 }
 
+// Tick marks and positions that follow a reference's residues rather than the alignment columns:
+// residue numbers are right-aligned on the residue's column, and gaps do not count. A number that
+// would overlap the previous one is left out.
+fn reference_ticks(sequence: &str) -> (String, String) {
+    let mut ticks = String::with_capacity(sequence.len());
+    let mut positions = String::with_capacity(sequence.len());
+    let mut residue = 0;
+    for (col, c) in sequence.chars().enumerate() {
+        if is_gap(c) {
+            ticks.push(' ');
+            continue;
+        }
+        residue += 1;
+        ticks.push(if residue % 10 == 0 {
+            '|'
+        } else if residue % 5 == 0 {
+            ':'
+        } else {
+            ' '
+        });
+        if residue == 1 || residue % 10 == 0 {
+            let number = residue.to_string();
+            // Leave at least one blank between numbers.
+            let start = (col + 1).saturating_sub(number.len());
+            let free = positions.trim_end().chars().count();
+            if start > free || (free == 0 && start == 0) {
+                positions.push_str(&" ".repeat(start - positions.chars().count()));
+                positions.push_str(&number);
+            }
+        }
+    }
+    (ticks, positions)
+}
+
 /****************************************************************
 // Draw UI
 ****************************************************************/
@@ -660,14 +774,32 @@ fn compute_title(ui: &UI, aln_para: &[Line]) -> String {
         ui.color_scheme(),
         ui.video_mode,
     );
+    let filter = match ui.app.label_filter() {
+        Some(pattern) if ui.app.stats_are_on_subset() => {
+            format!("| Filter: '{}' (stats) ", pattern)
+        }
+        Some(pattern) => format!("| Filter: '{}' ", pattern),
+        None => String::new(),
+    };
+    let hidden = match ui.app.num_hidden() {
+        0 => String::new(),
+        n => format!("| Hidden: {} ", n),
+    };
+    let reference = match ui.reference_name() {
+        Some(name) => format!("| Ref: {} ", name),
+        None => String::new(),
+    };
     format!(
-        "{} | {} " ,
+        "{} | {} {}{}{}" ,
         title,
         match ui.zoom_level {
             ZoomLevel::ZoomedIn => "Zoomed in",
             ZoomLevel::ZoomedOut => "Zoomed out ",
             ZoomLevel::ZoomedOutAR => "Z. out (Aspect)",
-        }
+        },
+        filter,
+        hidden,
+        reference
     )
 }
 
@@ -724,6 +856,44 @@ fn render_label_nums_pane(f: &mut Frame, num_chunk: Rect, ui: &UI) {
     f.render_widget(lbl_num_para, num_chunk);
 }
 
+// Width of the tree pane, including its left border: 0 unless the sequences are ordered by a tree
+// (and the label pane is shown).
+const TREE_PANE_WIDTH: u16 = 16;
+
+fn tree_pane_width(ui: &UI) -> u16 {
+    if ui.show_tree && ui.label_pane_width > 0 && ui.app.tree().is_some() {
+        TREE_PANE_WIDTH
+    } else {
+        0
+    }
+}
+
+// The tree's leaves are in the order of the sequences, so its lines follow the labels.
+fn render_tree_pane(f: &mut Frame, tree_chunk: Rect, ui: &UI) {
+    let Some(tree) = ui.app.tree() else {
+        return;
+    };
+    let lines = tree.dendrogram(tree_chunk.width.saturating_sub(1) as usize);
+    let text: Vec<Line> = match ui.zoom_level {
+        ZoomLevel::ZoomedIn => lines.into_iter().map(Line::from).collect(),
+        // Sequences that are not in the tree (which come last) have no line.
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => retained_seq_ndx(ui)
+            .into_iter()
+            .map(|i| Line::from(lines.get(i).cloned().unwrap_or_default()))
+            .collect(),
+    };
+    let top_line = match ui.zoom_level() {
+        ZoomLevel::ZoomedIn => ui.top_line,
+        ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => 0,
+    };
+    let tree_block = Block::default().borders(Borders::TOP | Borders::LEFT | Borders::BOTTOM);
+    let tree_para = Paragraph::new(text)
+        .style(get_label_num_style(ui.theme(), ui.get_label_num_color()))
+        .scroll((top_line, 0))
+        .block(tree_block);
+    f.render_widget(tree_para, tree_chunk);
+}
+
 fn render_labels_pane(f: &mut Frame, seq_chunk: Rect, ui: &UI) {
     /* Labels pane */
     let labels = compute_labels_pane_text(ui);
@@ -755,6 +925,10 @@ fn render_seq_metrics_pane(f: &mut Frame, num_chunk: Rect, ui: &UI) {
 }
 
 fn render_alignment_pane(f: &mut Frame, aln_chunk: Rect, ui: &UI) {
+    if ui.show_identity_map {
+        render_identity_map(f, aln_chunk, ui);
+        return;
+    }
     debug!(
         "render_alignment_pane(): max_nb_seq_shown = {}",
         ui.max_nb_seq_shown()
@@ -829,6 +1003,58 @@ fn render_alignment_pane(f: &mut Frame, aln_chunk: Rect, ui: &UI) {
     }
 }
 
+// Identity levels of the identity map, by steps of 20%: shades in monochrome, colors (from blue to
+// red) otherwise.
+const IDENTITY_SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+const IDENTITY_COLORS: [Color; 5] = [
+    Color::Rgb(69, 117, 180),
+    Color::Rgb(145, 191, 219),
+    Color::Rgb(254, 224, 144),
+    Color::Rgb(252, 141, 89),
+    Color::Rgb(215, 48, 39),
+];
+
+fn identity_cell(theme: Theme, identity: f64) -> Span<'static> {
+    let level = ((identity * 5.0) as usize).min(4);
+    match theme {
+        Theme::Dark | Theme::Light => Span::styled("█", Style::default().fg(IDENTITY_COLORS[level])),
+        Theme::Monochrome => Span::raw(IDENTITY_SHADES[level].to_string()),
+    }
+}
+
+// The pairwise identities of the sequences shown in the label pane, as a heatmap: the cell in line
+// i and column j compares the sequences of lines i and j, so the diagonal starts at the top left
+// corner. Columns that do not fit are left out.
+fn render_identity_map(f: &mut Frame, aln_chunk: Rect, ui: &UI) {
+    let seqs: Vec<usize> = shown_rows(ui).iter().map(|&row| ui.app.ordering[row]).collect();
+    let width = aln_chunk.width.saturating_sub(2) as usize;
+    let lines: Vec<Line> = seqs
+        .iter()
+        .map(|&seq1| {
+            let cells: Vec<Span> = seqs
+                .iter()
+                .take(width)
+                .map(|&seq2| {
+                    let identity = ui.app.pairwise_identity(seq1, seq2).unwrap_or(0.0);
+                    identity_cell(ui.theme(), identity)
+                })
+                .collect();
+            Line::from(cells)
+        })
+        .collect();
+    let mut title = vec![Span::raw(format!(
+        " {} | {}/{}s | Pairwise identity ({}) | 0% ",
+        ui.app.filename,
+        seqs.len(),
+        ui.app.num_seq(),
+        ui.app.gap_handling()
+    ))];
+    title.extend((0..5).map(|level| identity_cell(ui.theme(), level as f64 / 5.0)));
+    title.push(Span::raw(" 100% "));
+    let block = Block::default().title(Line::from(title)).borders(Borders::ALL);
+    f.render_widget(Paragraph::new(lines).block(block), aln_chunk);
+}
+
 fn render_corner_pane(f: &mut Frame, corner_chunk: Rect, ui: &UI) {
     // TODO: This render_* function does its own layout. Perhaps this could be done for other
     // non-top-level layouts, e.g. the layout of the left pane (which has three subpanes, namely
@@ -850,7 +1076,7 @@ fn render_corner_pane(f: &mut Frame, corner_chunk: Rect, ui: &UI) {
         format!(
             "{} {}",
             ui.app.get_metric(),
-            ui.app.get_seq_ordering().to_string()
+            ui.app.get_seq_ordering()
         ),
         metric_text_style,
     ))
@@ -858,11 +1084,17 @@ fn render_corner_pane(f: &mut Frame, corner_chunk: Rect, ui: &UI) {
     .right_aligned();
     f.render_widget(metric_para, metric_chunk);
 
-    let cons_text = Text::from(vec![
+    let mut cons_text = Text::from(vec![
         "Position".into(),
         "Consensus".into(),
         "Conservation".into(),
     ]);
+    for track in &ui.app.alignment.annotations.column {
+        cons_text.push_line(track.name.clone());
+    }
+    if ui.show_logo {
+        cons_text.push_line("Logo");
+    }
     let cons_para = Paragraph::new(cons_text).block(cons_block);
     f.render_widget(cons_para, cons_chunk);
 }
@@ -888,8 +1120,7 @@ fn render_bottom_pane(f: &mut Frame, bottom_chunk: Rect, ui: &UI) {
 
     let mut colored_consensus: Vec<Span> = ui
         .app
-        .alignment
-        .consensus
+        .consensus()
         .chars()
         .map(|c| {
             Span::styled(
@@ -915,33 +1146,93 @@ fn render_bottom_pane(f: &mut Frame, bottom_chunk: Rect, ui: &UI) {
         Theme::Monochrome => Color::Reset,
     };
 
-    let btm_text: Vec<Line> = vec![
-        Line::from(Span::styled(
+    let (ticks, positions) = match ui.reference_residues() {
+        Some(residues) if ui.reference_numbering => reference_ticks(residues),
+        _ => (
             tick_marks(ui.app.aln_len() as usize, None, Some(':')),
+            tick_position(ui.app.aln_len() as usize),
+        ),
+    };
+
+    let mut btm_text: Vec<Line> = vec![
+        Line::from(Span::styled(
+            ticks,
             Style::default().fg(pos_color).bg(Color::Reset),
         )),
         Line::from(Span::styled(
-            tick_position(ui.app.aln_len() as usize),
+            positions,
             Style::default().fg(pos_color).bg(Color::Reset),
         )),
         Line::from(colored_consensus),
-        Line::from(values_barchart(&product(
-            &ui.app.alignment.densities,
-            &ones_complement(&normalize(&ui.app.alignment.entropies)),
-        )))
+        Line::from(values_barchart(&ui.app.conservation()))
         .style(conservation_color),
     ];
+    // Column annotations, e.g. #=GC SS_cons (secondary structure) or RF (reference) from Stockholm
+    // files.
+    for track in &ui.app.alignment.annotations.column {
+        btm_text.push(Line::from(track.data.as_str()));
+    }
 
+    // The logo goes below the other tracks. Unlike them, it follows the zoom level, so it is drawn
+    // separately.
+    let logo_area = btm_block.inner(bottom_chunk).intersection(Rect {
+        y: bottom_chunk.y + btm_text.len() as u16,
+        height: LOGO_HEIGHT,
+        ..bottom_chunk
+    });
     let btm_para = Paragraph::new(btm_text)
         .scroll((0, ui.leftmost_col))
         .block(btm_block);
     f.render_widget(btm_para, bottom_chunk);
+
+    if ui.show_logo && !logo_area.is_empty() {
+        let cols: Vec<usize> = match ui.zoom_level {
+            ZoomLevel::ZoomedIn => {
+                let leftmost_col = ui.leftmost_col as usize;
+                (leftmost_col..ui.app.aln_len() as usize)
+                    .take(logo_area.width as usize)
+                    .collect()
+            }
+            ZoomLevel::ZoomedOut | ZoomLevel::ZoomedOutAR => retained_col_ndx(ui),
+        };
+        f.render_widget(Paragraph::new(logo_lines(ui, &cols)), logo_area);
+    }
 }
 
-fn render_help_dialog(f: &mut Frame, dialog_chunk: Rect) {
+// The sequence logo of the given columns: each is a stack of residues (the most frequent on top),
+// whose heights are their shares of the column's information content (see
+// Alignment::logo_stack()), to a scale where LOGO_HEIGHT rows is the maximal information.
+fn logo_lines(ui: &UI, cols: &[usize]) -> Vec<Line<'static>> {
+    let colormap = ui.color_scheme().current_residue_colormap();
+    let height = LOGO_HEIGHT as usize;
+    let scale = height as f64 / ui.app.max_information();
+    let mut lines: Vec<Vec<Span>> = vec![Vec::with_capacity(cols.len()); height];
+    for &col in cols {
+        // From the bottom up
+        let mut cells: Vec<Option<char>> = vec![None; height];
+        let (mut bits, mut bottom) = (0.0, 0);
+        for (residue, residue_bits) in ui.app.logo_stack(col) {
+            bits += residue_bits;
+            let top = ((bits * scale).round() as usize).min(height);
+            cells[bottom..top].fill(Some(residue));
+            bottom = top;
+        }
+        for (line, cell) in lines.iter_mut().zip(cells.iter().rev()) {
+            line.push(match cell {
+                Some(c) => Span::styled(
+                    c.to_string(),
+                    get_residue_style(ui.video_mode, ui.theme(), colormap.get(*c)),
+                ),
+                None => Span::raw(" "),
+            });
+        }
+    }
+    lines.into_iter().map(Line::from).collect()
+}
+
+fn render_help_dialog(f: &mut Frame, dialog_chunk: Rect, ui: &UI) {
     let dialog_block = Block::default().borders(Borders::ALL);
-    let bindings = include_str!("bindings.md");
-    let mut text = Text::from(bindings);
+    let mut text = Text::from(ui.keymap.help_text(false));
     text.push_line("");
     text.push_line("Press any key to close this dialog.");
     let dialog_para = Paragraph::new(Text::from_iter(text))
@@ -951,6 +1242,20 @@ fn render_help_dialog(f: &mut Frame, dialog_chunk: Rect) {
     f.render_widget(dialog_para, dialog_chunk);
 }
 
+// The prompt takes the last line of the screen, over whatever is there.
+fn render_prompt(f: &mut Frame, prompt: &Prompt) {
+    let area = f.area();
+    let prompt_chunk = Rect::new(area.x, area.bottom().saturating_sub(1), area.width, 1);
+    let text = prompt.text();
+    f.render_widget(Clear, prompt_chunk);
+    f.render_widget(
+        Paragraph::new(text.as_str()).style(Style::new().bold()),
+        prompt_chunk,
+    );
+    let cursor_x = (prompt_chunk.x + text.chars().count() as u16).min(area.right().saturating_sub(1));
+    f.set_cursor_position((cursor_x, prompt_chunk.y));
+}
+
 pub fn render_ui(f: &mut Frame, ui: &mut UI) {
     let layout_panes = make_layout(f, ui);
 
@@ -964,6 +1269,13 @@ pub fn render_ui(f: &mut Frame, ui: &mut UI) {
         layout_panes.sequence.as_size()
     );
     ui.aln_pane_size = Some(layout_panes.sequence.as_size());
+    ui.aln_pane_area = Some(layout_panes.sequence);
+    ui.label_pane_area = Some(layout_panes.lbl_num.union(layout_panes.seq_metrics));
+    // The layout depends on the zoom level, so it is redone after setting the initial one.
+    if let Some(zoom_level) = ui.initial_zoom_level.take() {
+        ui.set_zoom_level(zoom_level);
+        return render_ui(f, ui);
+    }
     debug!("render_ui(): max_nb_seq_shown = {}", ui.max_nb_seq_shown());
     // Handle resizing
     ui.adjust_seq_pane_position();
@@ -986,7 +1298,17 @@ pub fn render_ui(f: &mut Frame, ui: &mut UI) {
 
     ui.assert_invariants();
 
+    if ui.show_identity_map {
+        // Only the cells in view are computed (see render_identity_map()).
+        let seqs: Vec<usize> = shown_rows(ui).iter().map(|&row| ui.app.ordering[row]).collect();
+        let width = layout_panes.sequence.width.saturating_sub(2) as usize;
+        ui.app.compute_identities(&seqs, &seqs[..width.min(seqs.len())]);
+    }
+
     /* Render panes */
+    if !layout_panes.tree.is_empty() {
+        render_tree_pane(f, layout_panes.tree, ui);
+    }
     render_label_nums_pane(f, layout_panes.lbl_num, ui);
     render_labels_pane(f, layout_panes.labels, ui);
     render_seq_metrics_pane(f, layout_panes.seq_metrics, ui);
@@ -995,10 +1317,14 @@ pub fn render_ui(f: &mut Frame, ui: &mut UI) {
     render_bottom_pane(f, layout_panes.bottom, ui);
 
     if ui.show_help {
-        render_help_dialog(f, layout_panes.dialog);
+        render_help_dialog(f, layout_panes.dialog, ui);
         // after the first display of the help dialog, remove the message
         ui.message = "".into();
     }
+
+    if let Some(prompt) = &ui.prompt {
+        render_prompt(f, prompt);
+    }
 }
 
 /* Computes n indexes out of l. The indexes are as evenly spaced as possible, and always include
@@ -1020,7 +1346,14 @@ pub fn every_nth(l: usize, n: usize) -> Vec<usize> {
 #[cfg(test)]
 mod tests {
 
-    use crate::ui::render::{every_nth, tick_marks};
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use crate::app::App;
+    use crate::config::Config;
+    use crate::ui::render::{
+        every_nth, is_reference_match, logo_lines, reference_ticks, render_ui, tick_marks,
+    };
+    use crate::ui::{ZoomLevel, UI};
 
     #[test]
     fn test_every_nth_1() {
@@ -1053,4 +1386,78 @@ mod tests {
         let tm = tick_marks(21, Some(':'), Some('.'));
         assert_eq!(tm, "    :    :    .    :");
     }
+
+    #[test]
+    fn test_reference_ticks() {
+        // 12 residues, with gaps at columns 0, 3 and 4
+        let (ticks, positions) = reference_ticks("-AB--CDEFGHIJKL");
+        assert_eq!(ticks, "       :    |  ");
+        assert_eq!(positions, " 1         10");
+    }
+
+    #[test]
+    fn test_reference_match() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        let mut ui = UI::new(&mut app);
+        assert!(ui.set_reference("#2").is_ok());
+        let reference = ui.reference_residues().map(|r| r.as_bytes().to_vec());
+        let reference = reference.as_deref();
+        // seq1 (TTGCCG-CGA) against seq2 (TTCCCGGCGA)
+        assert!(is_reference_match(&ui, reference, 0, 0, 'T'));
+        assert!(!is_reference_match(&ui, reference, 0, 2, 'G'));
+        assert!(!is_reference_match(&ui, reference, 0, 6, '-'));
+        assert!(is_reference_match(&ui, reference, 0, 9, 'a'));
+        // The reference itself is shown as is.
+        assert!(!is_reference_match(&ui, reference, 1, 0, 'T'));
+        assert!(ui.set_reference("seq3").is_ok());
+        assert_eq!(ui.reference_seq(), Some(2));
+        assert!(ui.set_reference("#4").is_err());
+        assert!(ui.set_reference("").is_ok());
+        assert_eq!(ui.reference_name(), None);
+    }
+
+    #[test]
+    fn test_initial_zoom_level() {
+        let settings = Config::parse("zoom = \"out\"").unwrap().settings().unwrap();
+        let draw = |fname: &str| {
+            let mut app = App::new(fname).unwrap();
+            let mut ui = UI::new(&mut app);
+            ui.apply_settings(&settings).unwrap();
+            let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+            terminal.draw(|f| render_ui(f, &mut ui)).unwrap();
+            ui.zoom_level()
+        };
+        assert_eq!(draw("data/example-1.msa"), ZoomLevel::ZoomedOut);
+        // This one fits, so it is not zoomed out.
+        assert_eq!(draw("data/test2.fas"), ZoomLevel::ZoomedIn);
+    }
+
+    #[test]
+    fn test_identity_map() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        let mut ui = UI::new(&mut app);
+        ui.set_monochrome();
+        ui.toggle_identity_map();
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        terminal.draw(|f| render_ui(f, &mut ui)).unwrap();
+        // Identities: 0.8 between seq1 and seq2, 7/9 between seq1 and seq3, 0.7 between seq2 and
+        // seq3.
+        let area = ui.aln_pane_area.unwrap();
+        let buffer = terminal.backend().buffer();
+        let line = |y: u16| -> String {
+            (1..4).map(|x| buffer[(area.x + x, area.y + y)].symbol()).collect()
+        };
+        assert_eq!(line(1), "██▓");
+        assert_eq!(line(2), "██▓");
+        assert_eq!(line(3), "▓▓█");
+    }
+
+    #[test]
+    fn test_logo_lines() {
+        let mut app = App::new("data/test2.fas").unwrap();
+        let ui = UI::new(&mut app);
+        // Column 1 is all T (2 bits: full height), column 7 has one G and two gaps.
+        let lines: Vec<String> = logo_lines(&ui, &[0, 6]).iter().map(|l| l.to_string()).collect();
+        assert_eq!(lines, ["T ", "T ", "T ", "TG"]);
+    }
 }
@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2025 Thomas Junier
+
+/* Visual-mode selections, as in vim: 'V' selects whole sequences (rows), Ctrl-V a block of
+ * sequences and columns. The selection spans from where it was started (the anchor) to the cursor,
+ * which the motion keys move while the selection is active. Rows are positions in the current
+ * ordering, not indices into the alignment. */
+
+use std::ops::{Range, RangeInclusive};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionKind {
+    Rows,
+    Block,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    pub kind: SelectionKind,
+    // (row, column)
+    pub anchor: (usize, usize),
+    pub cursor: (usize, usize),
+}
+
+fn span(a: usize, b: usize) -> RangeInclusive<usize> {
+    a.min(b)..=a.max(b)
+}
+
+impl Selection {
+    pub fn new(kind: SelectionKind, row: usize, col: usize) -> Selection {
+        Selection {
+            kind,
+            anchor: (row, col),
+            cursor: (row, col),
+        }
+    }
+
+    pub fn rows(&self) -> Range<usize> {
+        let rows = span(self.anchor.0, self.cursor.0);
+        *rows.start()..*rows.end() + 1
+    }
+
+    // Row selections span all columns.
+    pub fn cols(&self, aln_len: usize) -> Range<usize> {
+        match self.kind {
+            SelectionKind::Rows => 0..aln_len,
+            SelectionKind::Block => {
+                let cols = span(self.anchor.1, self.cursor.1);
+                *cols.start()..*cols.end() + 1
+            }
+        }
+    }
+
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        span(self.anchor.0, self.cursor.0).contains(&row)
+            && (self.kind == SelectionKind::Rows || span(self.anchor.1, self.cursor.1).contains(&col))
+    }
+}
+
+// The OSC 52 escape sequence, which asks the terminal to put `text` in the clipboard (this also
+// works over SSH, but some terminals disable it or limit its size).
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_spans() {
+        let mut sel = Selection::new(SelectionKind::Block, 5, 20);
+        sel.cursor = (2, 24);
+        assert_eq!(sel.rows(), 2..6);
+        assert_eq!(sel.cols(100), 20..25);
+        assert!(sel.contains(2, 20) && sel.contains(5, 24));
+        assert!(!sel.contains(6, 20) && !sel.contains(3, 25));
+        sel.kind = SelectionKind::Rows;
+        assert_eq!(sel.cols(100), 0..100);
+        assert!(sel.contains(3, 99));
+    }
+
+    #[test]
+    fn test_osc52() {
+        assert_eq!(osc52(">s\nAC\n"), "\x1b]52;c;PnMKQUMK\x07");
+    }
+}
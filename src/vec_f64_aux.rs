@@ -21,12 +21,21 @@ pub fn product(v1: &[f64], v2: &[f64]) -> Vec<f64> {
     v1.iter().zip(v2).map(|(v, w)| v * w).collect()
 }
 
+// Arithmetic mean (0 if there are no values)
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
 #[cfg(test)]
 mod tests {
 
     use crate::vec_f64_aux::normalize;
     use crate::vec_f64_aux::ones_complement;
     use crate::vec_f64_aux::product;
+    use crate::vec_f64_aux::mean;
     use approx::assert_relative_eq;
 
     #[test]
@@ -60,4 +69,10 @@ mod tests {
         let exp = vec![1.0, 1.0, -9.0];
         assert_eq!(exp, product(&v1, &v2));
     }
+
+    #[test]
+    fn test_mean() {
+        assert_eq!(mean(&[1.0, 2.0, 6.0]), 3.0);
+        assert_eq!(mean(&[]), 0.0);
+    }
 }